@@ -11,7 +11,7 @@ import (
 )
 
 func main() {
-	features := lnwire.NewRawFeatureVector(lnwire.DataLossProtectRequired, lnwire.GossipQueriesRequired, lnwire.InitialRoutingSync)
+	features := lnwire.NewRawFeatureVector(wtwire.AltruistSessionsRequired, wtwire.AnchorCommitOptional)
 	hash, err := chainhash.NewHash(make([]byte, 32))
 	if err != nil {
 		log.Panicf("%s", err)
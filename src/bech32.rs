@@ -0,0 +1,156 @@
+// Bech32 as used by BOLT #11 invoices and, without the checksum, by BOLT #12
+// strings. Unlike BIP 173 there is no limit on the length of the string.
+// Data is handled as 5-bit words, one per byte.
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [
+    0x3b6a_57b2,
+    0x2650_8e6d,
+    0x1ea1_19fa,
+    0x3d42_33dd,
+    0x2a14_62b3,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bech32Error {
+    MixedCase,
+    MissingSeparator,
+    InvalidChar(char),
+    InvalidChecksum,
+}
+impl core::fmt::Display for Bech32Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Bech32Error::MixedCase => write!(f, "mixed case bech32 string"),
+            Bech32Error::MissingSeparator => write!(f, "missing bech32 separator"),
+            Bech32Error::InvalidChar(c) => write!(f, "invalid bech32 character {:?}", c),
+            Bech32Error::InvalidChecksum => write!(f, "invalid bech32 checksum"),
+        }
+    }
+}
+impl core::error::Error for Bech32Error {}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    let mut chk: u32 = 1;
+    for v in values {
+        let b = chk >> 25;
+        chk = (chk & 0x01ff_ffff) << 5 ^ u32::from(v);
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (b >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    hrp.iter()
+        .map(|c| c >> 5)
+        .chain(core::iter::once(0))
+        .chain(hrp.iter().map(|c| c & 0x1f))
+}
+
+fn checksum(hrp: &[u8], data: &[u8]) -> [u8; 6] {
+    let m = polymod(
+        hrp_expand(hrp)
+            .chain(data.iter().cloned())
+            .chain([0; 6].iter().cloned()),
+    ) ^ 1;
+    let mut res = [0; 6];
+    for (i, c) in res.iter_mut().enumerate() {
+        *c = ((m >> (5 * (5 - i))) & 0x1f) as u8;
+    }
+    res
+}
+
+pub fn encode(hrp: &str, data: &[u8], with_checksum: bool) -> String {
+    let hrp = hrp.to_ascii_lowercase();
+    let mut res = String::with_capacity(hrp.len() + 1 + data.len() + 6);
+    res.push_str(&hrp);
+    res.push('1');
+    for w in data {
+        res.push(CHARSET[(*w & 0x1f) as usize] as char);
+    }
+    if with_checksum {
+        for w in checksum(hrp.as_bytes(), data).iter() {
+            res.push(CHARSET[*w as usize] as char);
+        }
+    }
+    res
+}
+
+// Splits a bech32 string into its lowercased human-readable part and its
+// data words, with the checksum (if any) removed.
+pub fn decode(s: &str, with_checksum: bool) -> Result<(String, Vec<u8>), Bech32Error> {
+    if s.bytes().any(|c| c.is_ascii_lowercase()) && s.bytes().any(|c| c.is_ascii_uppercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+    let s = s.to_ascii_lowercase();
+    let sep = s.rfind('1').ok_or(Bech32Error::MissingSeparator)?;
+    let (hrp, rest) = s.split_at(sep);
+    if hrp.is_empty() {
+        return Err(Bech32Error::MissingSeparator);
+    }
+    if let Some(c) = hrp.chars().find(|c| !(' '..='~').contains(c)) {
+        return Err(Bech32Error::InvalidChar(c));
+    }
+    let mut data = rest[1..]
+        .chars()
+        .map(|c| {
+            CHARSET
+                .iter()
+                .position(|x| *x as char == c)
+                .map(|w| w as u8)
+                .ok_or(Bech32Error::InvalidChar(c))
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    if with_checksum {
+        if data.len() < 6 || polymod(hrp_expand(hrp.as_bytes()).chain(data.iter().cloned())) != 1 {
+            return Err(Bech32Error::InvalidChecksum);
+        }
+        data.truncate(data.len() - 6);
+    }
+    Ok((hrp.to_owned(), data))
+}
+
+// Converts bytes to 5-bit words, zero-padding the last one.
+pub fn to_u5(bytes: &[u8]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(bytes.len() * 8 / 5 + 1);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for b in bytes {
+        acc = acc << 8 | u32::from(*b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            res.push((acc >> bits) as u8 & 0x1f);
+        }
+    }
+    if bits > 0 {
+        res.push((acc << (5 - bits)) as u8 & 0x1f);
+    }
+    res
+}
+
+// Converts 5-bit words to bytes. Leftover bits are dropped, unless `pad` is
+// set, in which case they are zero-padded into a last byte.
+pub fn from_u5(words: &[u8], pad: bool) -> Vec<u8> {
+    let mut res = Vec::with_capacity(words.len() * 5 / 8 + 1);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for w in words {
+        acc = acc << 5 | u32::from(*w & 0x1f);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((acc >> bits) as u8);
+        }
+    }
+    if pad && bits > 0 {
+        res.push((acc << (8 - bits)) as u8);
+    }
+    res
+}
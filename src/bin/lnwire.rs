@@ -0,0 +1,346 @@
+// Decodes, encodes and validates wire messages from the command line:
+//
+//     lnwire [decode|encode|validate] [--raw] [FILE]
+//
+// `decode` (the default) reads a message as hex or raw bytes and prints its
+// fields as JSON, along with the TLV records and feature bits it does not
+// know. `encode` reads that JSON, or just its `message`, and prints the
+// message as hex, or as raw bytes with `--raw`. `validate` reads a message
+// and explains each rule it breaks. Input is read from FILE, or stdin.
+use lightning_wire_msgs_base::items::channel_type::{ChannelType, InvalidChannelType};
+use lightning_wire_msgs_base::messages::AnyMessage;
+use lightning_wire_msgs_base::tlv::{TlvStream, TlvStreamError};
+use lightning_wire_msgs_base::{LengthError, LimitError};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+type Message = AnyMessage<Vec<u8>>;
+
+const USAGE: &str = "usage: lnwire [decode|encode|validate] [--raw] [FILE]";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Decode,
+    Encode,
+    Validate,
+}
+
+fn main() {
+    let mut mode = None;
+    let mut raw = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "decode" if mode.is_none() => mode = Some(Mode::Decode),
+            "encode" if mode.is_none() => mode = Some(Mode::Encode),
+            "validate" if mode.is_none() => mode = Some(Mode::Validate),
+            "--raw" => raw = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    let input = match &path {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input).map(|_| input)
+        }
+    }
+    .unwrap_or_else(|e| fail(&format!("cannot read input: {}", e)));
+
+    match mode.unwrap_or(Mode::Decode) {
+        Mode::Decode => decode(&from_hex_or_raw(&input)),
+        Mode::Encode => encode(&input, raw),
+        Mode::Validate => validate(&from_hex_or_raw(&input)),
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("lnwire: {}", msg);
+    exit(1)
+}
+
+// Input that is all hex digits, give or take whitespace and a `0x`, is hex.
+// Anything else is taken to be the raw bytes.
+fn from_hex_or_raw(input: &[u8]) -> Vec<u8> {
+    let text: Vec<u8> = input
+        .iter()
+        .cloned()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let digits = text.strip_prefix(b"0x").unwrap_or(&text);
+    let pairs = digits.chunks_exact(2);
+    if digits.is_empty()
+        || !pairs.remainder().is_empty()
+        || !digits.iter().all(u8::is_ascii_hexdigit)
+    {
+        return input.to_vec();
+    }
+    pairs
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).expect("hex digits are ascii");
+            u8::from_str_radix(pair, 16).expect("checked to be hex digits")
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode(bytes: &[u8]) {
+    let (msg, extra) = read_message(bytes).unwrap_or_else(|problem| fail(&problem));
+    let mut doc = json!({
+        "type": msg_type(bytes),
+        "summary": msg.to_string(),
+        "message": msg,
+    });
+    if !extra.is_empty() {
+        doc["unknown_tlvs"] = json!(extra);
+    }
+    let features = unknown_features(&msg);
+    if !features.is_empty() {
+        doc["unknown_features"] = features
+            .into_iter()
+            .map(|(field, bits)| (field.to_string(), json!(bits)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&doc).expect("serializing to a string")
+    );
+}
+
+fn encode(input: &[u8], raw: bool) {
+    let mut doc: Value =
+        serde_json::from_slice(input).unwrap_or_else(|e| fail(&format!("invalid json: {}", e)));
+    // The output of `decode`, or just the message.
+    let (msg, extra) = match doc.get_mut("message") {
+        Some(msg) => (msg.take(), doc.get_mut("unknown_tlvs").map(Value::take)),
+        None => (doc, None),
+    };
+    let msg: Message =
+        serde_json::from_value(msg).unwrap_or_else(|e| fail(&format!("invalid message: {}", e)));
+    let extra: TlvStream = match extra {
+        Some(extra) => serde_json::from_value(extra)
+            .unwrap_or_else(|e| fail(&format!("invalid unknown_tlvs: {}", e))),
+        None => TlvStream::new(),
+    };
+    let mut bytes = Vec::new();
+    extra
+        .encode_message(&msg, &mut bytes)
+        .unwrap_or_else(|e| fail(&format!("cannot encode {}: {}", msg.name(), e)));
+    let mut stdout = io::stdout();
+    let res = if raw {
+        stdout.write_all(&bytes)
+    } else {
+        writeln!(stdout, "{}", to_hex(&bytes))
+    };
+    res.unwrap_or_else(|e| fail(&format!("cannot write output: {}", e)));
+}
+
+fn validate(bytes: &[u8]) {
+    let (msg, extra) = match read_message(bytes) {
+        Ok(decoded) => decoded,
+        Err(problem) => {
+            println!("invalid: {}", problem);
+            exit(1)
+        }
+    };
+    let name = msg.name();
+    let mut problems = Vec::new();
+    let mut notes = Vec::new();
+
+    for record in &extra {
+        if record.tlv_type & 1 == 0 {
+            problems.push(format!(
+                "unknown even tlv type {} in {}: BOLT #1 requires the receiver to fail \
+                 the message, as it must understand even types",
+                record.tlv_type, name
+            ));
+        } else {
+            notes.push(format!(
+                "unknown odd tlv type {} in {} is ignored",
+                record.tlv_type, name
+            ));
+        }
+    }
+    for (field, bits) in unknown_features(&msg) {
+        for bit in bits {
+            if bit & 1 == 0 {
+                problems.push(format!(
+                    "{} sets unknown feature bit {}: BOLT #9 requires the receiver to fail \
+                     the connection, as even bits are required",
+                    field, bit
+                ));
+            } else {
+                notes.push(format!(
+                    "{} sets unknown optional feature bit {}",
+                    field, bit
+                ));
+            }
+        }
+    }
+
+    let mut canonical = Vec::new();
+    match extra.encode_message(&msg, &mut canonical) {
+        Ok(_) if canonical == bytes => (),
+        Ok(_) => {
+            let at = canonical
+                .iter()
+                .zip(bytes)
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| canonical.len().min(bytes.len()));
+            problems.push(format!(
+                "{} is not in its canonical encoding: re-encoding it differs from byte {}",
+                name, at
+            ));
+        }
+        Err(e) => problems.push(format!("{} cannot be re-encoded: {}", name, e)),
+    }
+
+    for note in &notes {
+        println!("note: {}", note);
+    }
+    if problems.is_empty() {
+        println!("ok: {}", msg);
+    } else {
+        for problem in &problems {
+            println!("invalid: {}", problem);
+        }
+        exit(1)
+    }
+}
+
+fn msg_type(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+// Decodes a message with the TLV records none of its fields take, or says
+// what keeps it from being decoded.
+fn read_message(bytes: &[u8]) -> Result<(Message, TlvStream), String> {
+    if bytes.len() < 2 {
+        return Err(format!(
+            "{} byte(s) is too short for the 2-byte message type BOLT #1 requires",
+            bytes.len()
+        ));
+    }
+    let msg_type = msg_type(bytes);
+    let name = match Message::name_of(msg_type) {
+        Some(name) => name,
+        None if msg_type & 1 == 0 => {
+            return Err(format!(
+                "unknown even message type {}: BOLT #1 requires the receiver to close the \
+                 connection",
+                msg_type
+            ))
+        }
+        None => {
+            return Err(format!(
+                "unknown odd message type {}: BOLT #1 lets the receiver ignore it",
+                msg_type
+            ))
+        }
+    };
+    TlvStream::decode_message(&mut &bytes[..]).map_err(|e| explain(name, &e))
+}
+
+// What a decode error means in terms of the rule the message breaks.
+fn explain(name: &str, e: &io::Error) -> String {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        return format!("{} is truncated: it ends in the middle of a field", name);
+    }
+    let inner = match e.get_ref() {
+        Some(inner) => inner,
+        None => return format!("{} has a field with an invalid value", name),
+    };
+    if let Some(e) = inner.downcast_ref::<TlvStreamError>() {
+        let rule = match e {
+            TlvStreamError::Truncated => {
+                "every type and length must be a minimal BigSize and every value must fit in \
+                 the message"
+            }
+            TlvStreamError::NotIncreasing(_) => {
+                "BOLT #1 requires the records to be in strictly increasing type order"
+            }
+            TlvStreamError::TooManyRecords => "it has more records than the decode limits allow",
+            TlvStreamError::UnknownEvenType(_) => {
+                "BOLT #1 requires the receiver to fail on even types it does not understand"
+            }
+        };
+        format!("{} has an invalid tlv stream: {}: {}", name, e, rule)
+    } else if let Some(e) = inner.downcast_ref::<LengthError>() {
+        format!(
+            "{} has a tlv record whose length does not fit its value: {}",
+            name, e
+        )
+    } else if let Some(InvalidChannelType(features)) = inner.downcast_ref() {
+        let unknown: Vec<_> = features.unknown().collect();
+        if unknown.is_empty() {
+            format!(
+                "{} has a channel_type that is not a combination BOLT #2 defines: {}",
+                name, features
+            )
+        } else {
+            format!(
+                "{} has a channel_type with unknown feature bits {:?}: BOLT #2 requires the \
+                 receiver to fail the channel",
+                name, unknown
+            )
+        }
+    } else if let Some(e) = inner.downcast_ref::<LimitError>() {
+        format!("{} is beyond the decode limits: {}", name, e)
+    } else {
+        format!("{} has a field with an invalid value: {}", name, inner)
+    }
+}
+
+// The feature bits of each feature vector in the message that are not
+// defined for it. A `channel_type` with such bits does not decode, and
+// `explain` names them instead.
+fn unknown_features(msg: &Message) -> Vec<(&'static str, Vec<usize>)> {
+    let mut res = Vec::new();
+    match msg {
+        AnyMessage::Init(m) => res.push(("conn_features", m.conn_features.unknown().collect())),
+        AnyMessage::OpenChannel2(m) => res.extend(channel_type(&m.channel_type)),
+        AnyMessage::AcceptChannel2(m) => res.extend(channel_type(&m.channel_type)),
+        // Listed rather than matched with `_`, so that a feature vector added
+        // to a message is not missed here.
+        AnyMessage::Stfu(_)
+        | AnyMessage::TxAddInput(_)
+        | AnyMessage::TxAddOutput(_)
+        | AnyMessage::TxRemoveInput(_)
+        | AnyMessage::TxRemoveOutput(_)
+        | AnyMessage::TxComplete(_)
+        | AnyMessage::TxSignatures(_)
+        | AnyMessage::TxInitRbf(_)
+        | AnyMessage::TxAckRbf(_)
+        | AnyMessage::TxAbort(_)
+        | AnyMessage::SpliceLocked(_)
+        | AnyMessage::SpliceInit(_)
+        | AnyMessage::SpliceAck(_)
+        | AnyMessage::UpdateAddHtlc(_)
+        | AnyMessage::Error(_)
+        | AnyMessage::CreateSession(_)
+        | AnyMessage::CreateSessionReply(_)
+        | AnyMessage::StateUpdate(_)
+        | AnyMessage::StateUpdateReply(_)
+        | AnyMessage::DeleteSession(_)
+        | AnyMessage::DeleteSessionReply(_) => (),
+        #[cfg(feature = "secp256k1")]
+        AnyMessage::OnionMessage(_) => (),
+    }
+    res.retain(|(_, bits): &(_, Vec<_>)| !bits.is_empty());
+    res
+}
+
+fn channel_type(t: &Option<ChannelType>) -> Option<(&'static str, Vec<usize>)> {
+    t.map(|t| ("channel_type", t.features().unknown().collect()))
+}
@@ -0,0 +1,518 @@
+pub mod tagged_field;
+
+use self::tagged_field::{u5_to_u64, Fallback, RouteHintHop, TaggedField};
+use crate::bech32::{self, from_u5, to_u5, Bech32Error};
+use crate::items::feature::InvoiceFeatures;
+use alloc::{format, string::String, vec::Vec};
+use bitcoin_hashes::{sha256, Hash as _};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+pub const DEFAULT_EXPIRY: u64 = 3600;
+pub const DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA: u64 = 18;
+
+const MSAT_PER_BTC: u64 = 100_000_000_000;
+const TIMESTAMP_LEN: usize = 7;
+const SIGNATURE_LEN: usize = 104;
+const MAX_FIELD_LEN: usize = 1023;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Currency {
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+impl Currency {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Currency::Bitcoin => "bc",
+            Currency::Testnet => "tb",
+            Currency::Signet => "tbs",
+            Currency::Regtest => "bcrt",
+        }
+    }
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "bc" => Some(Currency::Bitcoin),
+            "tb" => Some(Currency::Testnet),
+            "tbs" => Some(Currency::Signet),
+            "bcrt" => Some(Currency::Regtest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bolt11Error {
+    Bech32(Bech32Error),
+    InvalidPrefix,
+    InvalidAmount,
+    InvalidTimestamp,
+    // The data part ends in the middle of the timestamp, a field or the
+    // signature.
+    TooShort,
+    FieldTooLong(u8),
+    InvalidDescription,
+    InvalidInteger,
+    InvalidRouteHint,
+    InvalidSignature,
+    MissingPaymentHash,
+    MissingPaymentSecret,
+    MissingDescription,
+    UnknownRequiredFeature(usize),
+}
+impl From<Bech32Error> for Bolt11Error {
+    fn from(e: Bech32Error) -> Self {
+        Bolt11Error::Bech32(e)
+    }
+}
+impl core::fmt::Display for Bolt11Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Bolt11Error::Bech32(e) => write!(f, "{}", e),
+            Bolt11Error::InvalidPrefix => write!(f, "invalid invoice prefix"),
+            Bolt11Error::InvalidAmount => write!(f, "invalid amount"),
+            Bolt11Error::InvalidTimestamp => write!(f, "invalid timestamp"),
+            Bolt11Error::TooShort => write!(f, "invoice data too short"),
+            Bolt11Error::FieldTooLong(tag) => write!(f, "field {} too long", tag),
+            Bolt11Error::InvalidDescription => write!(f, "description is not valid UTF-8"),
+            Bolt11Error::InvalidInteger => write!(f, "integer field overflows"),
+            Bolt11Error::InvalidRouteHint => write!(f, "invalid route hint"),
+            Bolt11Error::InvalidSignature => write!(f, "invalid signature"),
+            Bolt11Error::MissingPaymentHash => write!(f, "missing payment hash"),
+            Bolt11Error::MissingPaymentSecret => write!(f, "missing payment secret"),
+            Bolt11Error::MissingDescription => write!(f, "missing description"),
+            Bolt11Error::UnknownRequiredFeature(bit) => {
+                write!(f, "unknown required feature {}", bit)
+            }
+        }
+    }
+}
+impl core::error::Error for Bolt11Error {}
+
+// Everything in an invoice but its signature. Tagged fields are kept in
+// order, and the accessors return the first valid field of each kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnsignedInvoice {
+    pub currency: Currency,
+    pub amount_msat: Option<u64>,
+    pub timestamp: u64,
+    pub fields: Vec<TaggedField>,
+}
+impl UnsignedInvoice {
+    pub fn hrp(&self) -> String {
+        let mut hrp = format!("ln{}", self.currency.prefix());
+        if let Some(amount) = self.amount_msat {
+            hrp.push_str(&encode_amount(amount));
+        }
+        hrp
+    }
+
+    // The data part without the signature, as 5-bit words.
+    fn data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in (0..TIMESTAMP_LEN).rev() {
+            data.push((self.timestamp >> (5 * i)) as u8 & 0x1f);
+        }
+        for field in &self.fields {
+            let value = field.data();
+            data.push(field.tag());
+            data.push((value.len() >> 5) as u8 & 0x1f);
+            data.push(value.len() as u8 & 0x1f);
+            data.extend(value);
+        }
+        data
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut preimage = self.hrp().into_bytes();
+        preimage.extend(from_u5(&self.data(), true));
+        sha256::Hash::hash(&preimage).to_byte_array()
+    }
+
+    pub fn sign(self, key: &SecretKey) -> Result<Invoice, Bolt11Error> {
+        self.validate()?;
+        if self.timestamp >> (5 * TIMESTAMP_LEN) != 0 {
+            return Err(Bolt11Error::InvalidTimestamp);
+        }
+        if let Some(field) = self.fields.iter().find(|f| f.data().len() > MAX_FIELD_LEN) {
+            return Err(Bolt11Error::FieldTooLong(field.tag()));
+        }
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest(self.signing_hash());
+        Ok(Invoice {
+            signature: secp.sign_ecdsa_recoverable(&msg, key),
+            payee: PublicKey::from_secret_key(&secp, key),
+            unsigned: self,
+        })
+    }
+
+    fn validate(&self) -> Result<(), Bolt11Error> {
+        if self.payment_hash().is_none() {
+            return Err(Bolt11Error::MissingPaymentHash);
+        }
+        if self.payment_secret().is_none() {
+            return Err(Bolt11Error::MissingPaymentSecret);
+        }
+        if self.description().is_none() && self.description_hash().is_none() {
+            return Err(Bolt11Error::MissingDescription);
+        }
+        if let Some(bit) = self.features().and_then(|f| f.unknown_required().next()) {
+            return Err(Bolt11Error::UnknownRequiredFeature(bit));
+        }
+        Ok(())
+    }
+
+    pub fn payment_hash(&self) -> Option<&[u8; 32]> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::PaymentHash(h) => Some(h),
+            _ => None,
+        })
+    }
+    pub fn payment_secret(&self) -> Option<&[u8; 32]> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::PaymentSecret(s) => Some(s),
+            _ => None,
+        })
+    }
+    pub fn description(&self) -> Option<&str> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::Description(d) => Some(d.as_str()),
+            _ => None,
+        })
+    }
+    pub fn description_hash(&self) -> Option<&[u8; 32]> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::DescriptionHash(h) => Some(h),
+            _ => None,
+        })
+    }
+    pub fn payee_pubkey_field(&self) -> Option<&[u8; 33]> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::PayeePubKey(p) => Some(p),
+            _ => None,
+        })
+    }
+    pub fn expiry(&self) -> u64 {
+        self.fields
+            .iter()
+            .find_map(|f| match f {
+                TaggedField::Expiry(x) => Some(*x),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_EXPIRY)
+    }
+    pub fn min_final_cltv_expiry_delta(&self) -> u64 {
+        self.fields
+            .iter()
+            .find_map(|f| match f {
+                TaggedField::MinFinalCltvExpiryDelta(c) => Some(*c),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA)
+    }
+    pub fn fallbacks(&self) -> impl Iterator<Item = &Fallback> {
+        self.fields.iter().filter_map(|f| match f {
+            TaggedField::Fallback(f) => Some(f),
+            _ => None,
+        })
+    }
+    pub fn route_hints(&self) -> impl Iterator<Item = &[RouteHintHop]> {
+        self.fields.iter().filter_map(|f| match f {
+            TaggedField::RouteHint(r) => Some(r.as_slice()),
+            _ => None,
+        })
+    }
+    pub fn features(&self) -> Option<&InvoiceFeatures> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::Features(f) => Some(f),
+            _ => None,
+        })
+    }
+    pub fn metadata(&self) -> Option<&[u8]> {
+        self.fields.iter().find_map(|f| match f {
+            TaggedField::Metadata(m) => Some(m.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+// A signed invoice. The payee is either the `n` field, which the signature
+// has been checked against, or the key recovered from the signature.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invoice {
+    unsigned: UnsignedInvoice,
+    signature: RecoverableSignature,
+    payee: PublicKey,
+}
+impl Invoice {
+    pub fn signature(&self) -> &RecoverableSignature {
+        &self.signature
+    }
+    pub fn payee_pubkey(&self) -> &PublicKey {
+        &self.payee
+    }
+    pub fn expires_at(&self) -> u64 {
+        self.timestamp.saturating_add(self.expiry())
+    }
+    pub fn into_unsigned(self) -> UnsignedInvoice {
+        self.unsigned
+    }
+}
+impl core::ops::Deref for Invoice {
+    type Target = UnsignedInvoice;
+
+    fn deref(&self) -> &UnsignedInvoice {
+        &self.unsigned
+    }
+}
+impl core::fmt::Display for Invoice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut data = self.unsigned.data();
+        let (recid, sig) = self.signature.serialize_compact();
+        let mut sig = sig.to_vec();
+        sig.push(recid.to_i32() as u8);
+        data.extend(to_u5(&sig));
+        write!(f, "{}", bech32::encode(&self.unsigned.hrp(), &data, true))
+    }
+}
+impl core::str::FromStr for Invoice {
+    type Err = Bolt11Error;
+
+    fn from_str(s: &str) -> Result<Self, Bolt11Error> {
+        let (hrp, data) = bech32::decode(s, true)?;
+        let (currency, amount_msat) = parse_hrp(&hrp)?;
+        if data.len() < TIMESTAMP_LEN + SIGNATURE_LEN {
+            return Err(Bolt11Error::TooShort);
+        }
+        let (data, sig) = data.split_at(data.len() - SIGNATURE_LEN);
+        let timestamp = u5_to_u64(&data[..TIMESTAMP_LEN])?;
+        let mut fields = Vec::new();
+        let mut rest = &data[TIMESTAMP_LEN..];
+        while !rest.is_empty() {
+            if rest.len() < 3 {
+                return Err(Bolt11Error::TooShort);
+            }
+            let len = (rest[1] as usize) << 5 | rest[2] as usize;
+            if rest.len() < 3 + len {
+                return Err(Bolt11Error::TooShort);
+            }
+            fields.push(TaggedField::parse(rest[0], &rest[3..3 + len])?);
+            rest = &rest[3 + len..];
+        }
+        let unsigned = UnsignedInvoice {
+            currency,
+            amount_msat,
+            timestamp,
+            fields,
+        };
+        let sig = from_u5(sig, false);
+        let signature = RecoveryId::from_i32(i32::from(sig[64]))
+            .and_then(|recid| RecoverableSignature::from_compact(&sig[..64], recid))
+            .map_err(|_| Bolt11Error::InvalidSignature)?;
+        let secp = Secp256k1::verification_only();
+        let msg = Message::from_digest(unsigned.signing_hash());
+        let payee = match unsigned.payee_pubkey_field() {
+            Some(p) => {
+                let payee = PublicKey::from_slice(p).map_err(|_| Bolt11Error::InvalidSignature)?;
+                secp.verify_ecdsa(&msg, &signature.to_standard(), &payee)
+                    .map_err(|_| Bolt11Error::InvalidSignature)?;
+                payee
+            }
+            None => secp
+                .recover_ecdsa(&msg, &signature)
+                .map_err(|_| Bolt11Error::InvalidSignature)?,
+        };
+        unsigned.validate()?;
+        Ok(Invoice {
+            unsigned,
+            signature,
+            payee,
+        })
+    }
+}
+
+fn parse_hrp(hrp: &str) -> Result<(Currency, Option<u64>), Bolt11Error> {
+    let rest = hrp.strip_prefix("ln").ok_or(Bolt11Error::InvalidPrefix)?;
+    let split = rest
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let currency = Currency::from_prefix(&rest[..split]).ok_or(Bolt11Error::InvalidPrefix)?;
+    let amount = &rest[split..];
+    if amount.is_empty() {
+        Ok((currency, None))
+    } else {
+        Ok((currency, Some(parse_amount(amount)?)))
+    }
+}
+
+fn parse_amount(s: &str) -> Result<u64, Bolt11Error> {
+    let (digits, multiplier) = match s.as_bytes()[s.len() - 1] {
+        b'0'..=b'9' => (s, None),
+        c => (&s[..s.len() - 1], Some(c)),
+    };
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(Bolt11Error::InvalidAmount);
+    }
+    let n: u64 = digits.parse().map_err(|_| Bolt11Error::InvalidAmount)?;
+    match multiplier {
+        None => n.checked_mul(MSAT_PER_BTC),
+        Some(b'm') => n.checked_mul(MSAT_PER_BTC / 1_000),
+        Some(b'u') => n.checked_mul(MSAT_PER_BTC / 1_000_000),
+        Some(b'n') => n.checked_mul(MSAT_PER_BTC / 1_000_000_000),
+        // A pico-bitcoin is a tenth of a millisatoshi.
+        Some(b'p') => Some(n / 10).filter(|m| m * 10 == n),
+        _ => None,
+    }
+    .ok_or(Bolt11Error::InvalidAmount)
+}
+
+// Uses the shortest representation of the amount.
+fn encode_amount(msat: u64) -> String {
+    [
+        (MSAT_PER_BTC, ""),
+        (MSAT_PER_BTC / 1_000, "m"),
+        (MSAT_PER_BTC / 1_000_000, "u"),
+        (MSAT_PER_BTC / 1_000_000_000, "n"),
+    ]
+    .iter()
+    .find(|(unit, _)| msat / unit * unit == msat)
+    .map(|(unit, m)| format!("{}{}", msat / unit, m))
+    .unwrap_or_else(|| format!("{}p", u128::from(msat) * 10))
+}
+
+#[test]
+fn spec_examples() {
+    use crate::items::expiry::CltvExpiryDelta;
+    use crate::items::short_channel_id::ShortChannelId;
+    use core::str::FromStr;
+
+    let key = SecretKey::from_slice(&[
+        0xe1, 0x26, 0xf6, 0x8f, 0x7e, 0xaf, 0xcc, 0x8b, 0x74, 0xf5, 0x4d, 0x26, 0x9f, 0xe2, 0x06,
+        0xbe, 0x71, 0x50, 0x00, 0xf9, 0x4d, 0xac, 0x06, 0x7d, 0x1c, 0x04, 0xa8, 0xca, 0x3b, 0x2d,
+        0xb7, 0x34,
+    ])
+    .unwrap();
+    let payee = PublicKey::from_secret_key(&Secp256k1::new(), &key);
+    let payment_hash = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x00, 0x01, 0x02, 0x03, 0x04,
+        0x05, 0x06, 0x07, 0x08, 0x09, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+        0x01, 0x02,
+    ];
+    let examples = [
+        // Please make a donation of any amount
+        "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql",
+        // Please send $3 for a cup of coffee, within one minute
+        "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh",
+        // Now send $24 for an entire list of things (hashed)
+        "lnbc20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqs9qrsgq7ea976txfraylvgzuxs8kgcw23ezlrszfnh8r6qtfpr6cxga50aj6txm9rxrydzd06dfeawfk6swupvz4erwnyutnjq7x39ymw6j38gp7ynn44",
+        // The same, on testnet, with a fallback address
+        "lntb20m1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygshp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqfpp3x9et2e20v6pu37c5d9vax37wxq72un989qrsgqdj545axuxtnfemtpwkc45hx9d2ft7x04mt8q7y6t0k2dge9e7h8kpy9p34ytyslj3yu569aalz2xdk8xkd7ltxqld94u8h2esmsmacgpghe9k8",
+    ];
+    for s in examples.iter() {
+        let invoice = Invoice::from_str(s).expect("decode");
+        assert_eq!(invoice.timestamp, 1496314658);
+        assert_eq!(invoice.payment_hash(), Some(&payment_hash));
+        assert_eq!(invoice.payment_secret(), Some(&[0x11; 32]));
+        assert_eq!(invoice.payee_pubkey(), &payee);
+        assert_eq!(&invoice.to_string(), s);
+        let resigned = invoice.clone().into_unsigned().sign(&key).expect("sign");
+        assert_eq!(resigned, invoice);
+    }
+
+    let invoice = Invoice::from_str(examples[0]).unwrap();
+    assert_eq!(invoice.amount_msat, None);
+    assert_eq!(
+        invoice.description(),
+        Some("Please consider supporting this project")
+    );
+    let invoice = Invoice::from_str(examples[1]).unwrap();
+    assert_eq!(invoice.amount_msat, Some(250_000_000));
+    assert_eq!(invoice.description(), Some("1 cup coffee"));
+    assert_eq!(invoice.expiry(), 60);
+    let invoice = Invoice::from_str(examples[3]).unwrap();
+    assert_eq!(invoice.currency, Currency::Testnet);
+    assert_eq!(invoice.amount_msat, Some(2_000_000_000));
+    assert_eq!(invoice.fallbacks().next().unwrap().version, 17);
+
+    let mut unsigned = invoice.into_unsigned();
+    unsigned
+        .fields
+        .push(TaggedField::RouteHint(vec![RouteHintHop {
+            node_id: payee.serialize(),
+            short_channel_id: ShortChannelId(0x0102_0304_0506_0708),
+            fee_base_msat: 1,
+            fee_proportional_millionths: 20,
+            cltv_expiry_delta: CltvExpiryDelta(3),
+        }]));
+    let invoice = unsigned.sign(&key).unwrap();
+    assert_eq!(Invoice::from_str(&invoice.to_string()), Ok(invoice));
+
+    // The spec's invalid invoices.
+    let invalid = [
+        // Bech32 checksum is invalid
+        (
+            "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrnt",
+            Bolt11Error::Bech32(Bech32Error::InvalidChecksum),
+        ),
+        // Malformed bech32 string (no 1)
+        (
+            "pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrny",
+            Bolt11Error::Bech32(Bech32Error::MissingSeparator),
+        ),
+        // Malformed bech32 string (mixed case)
+        (
+            "LNBC2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpquwpc4curk03c9wlrswe78q4eyqc7d8d0xqzpuyk0sg5g70me25alkluzd2x62aysf2pyy8edtjeevuv4p2d5p76r4zkmneet7uvyakky2zr4cusd45tftc9c5fh0nnqpnl2jfll544esqchsrny",
+            Bolt11Error::Bech32(Bech32Error::MixedCase),
+        ),
+        // Signature is not recoverable
+        (
+            "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpuaxtrnwngzn3kdzw5hydlzf03qdgm2hdq27cqv3agm2awhz5se903vruatfhq77w3ls4evs3ch9zw97j25emudupq63nyw24cg27h2rspk28uwq",
+            Bolt11Error::InvalidSignature,
+        ),
+        // String is too short
+        (
+            "lnbc1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6na6hlh",
+            Bolt11Error::TooShort,
+        ),
+        // Invalid multiplier
+        (
+            "lnbc2500x1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpujr6jxr9gq9pv6g46y7d20jfkegkg4gljz2ea2a3m9lmvvr95tq2s0kvu70u3axgelz3kyvtp2ywwt0y8hkx2869zq5dll9nelr83zzqqpgl2zg",
+            Bolt11Error::InvalidAmount,
+        ),
+        // Invalid sub-millisatoshi precision
+        (
+            "lnbc2500000001p1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu7hqtk93pkf7sw55rdv4k9z2vj050rxdr6za9ekfs3nlt5lr89jqpdmxsmlj9urqumg0h9wzpqecw7th56tdms40p2ny9q4ddvjsedzcplva53s",
+            Bolt11Error::InvalidAmount,
+        ),
+        // Missing required s field
+        (
+            "lnbc20m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqhp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqs9qrsgq7ea976txfraylvgzuxs8kgcw23ezlrszfnh8r6qtfpr6cxga50aj6txm9rxrydzd06dfeawfk6swupvz4erwnyutnjq7x39ymw6j38gp49qdkj",
+            Bolt11Error::MissingPaymentSecret,
+        ),
+        // Same, but with unknown feature 100
+        (
+            "lnbc25m1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5vdhkven9v5sxyetpdeessp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygs9q4psqqqqqqqqqqqqqqqqsgqtqyx5vggfcsll4wu246hz02kp85x4katwsk9639we5n5yngc3yhqkm35jnjw4len8vrnqnf5ejh0mzj9n3vz2px97evektfm2l6wqccp3y7372",
+            Bolt11Error::UnknownRequiredFeature(100),
+        ),
+    ];
+    for (s, err) in invalid.iter() {
+        assert_eq!(Invoice::from_str(s).as_ref().err(), Some(err), "{}", s);
+    }
+
+    // A single flipped character breaks the checksum.
+    let mut bad = examples[1].to_owned();
+    bad.replace_range(20..21, "q");
+    assert_eq!(
+        Invoice::from_str(&bad),
+        Err(Bolt11Error::Bech32(Bech32Error::InvalidChecksum))
+    );
+    assert_eq!(parse_amount("2500000001p"), Err(Bolt11Error::InvalidAmount));
+    assert_eq!(parse_amount("1a"), Err(Bolt11Error::InvalidAmount));
+    assert_eq!(parse_amount("9678785340p"), Ok(967_878_534));
+    assert_eq!(encode_amount(967_878_534), "9678785340p");
+    assert_eq!(encode_amount(250_000_000), "2500u");
+}
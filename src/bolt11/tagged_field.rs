@@ -0,0 +1,232 @@
+use super::Bolt11Error;
+use crate::bech32::{from_u5, to_u5};
+use crate::io::Write;
+use crate::items::expiry::CltvExpiryDelta;
+use crate::items::feature::InvoiceFeatures;
+use crate::items::short_channel_id::ShortChannelId;
+use crate::{WireItem, WireRead};
+use alloc::{string::String, vec, vec::Vec};
+
+pub const TAG_PAYMENT_HASH: u8 = 1;
+pub const TAG_ROUTE_HINT: u8 = 3;
+pub const TAG_FEATURES: u8 = 5;
+pub const TAG_EXPIRY: u8 = 6;
+pub const TAG_FALLBACK: u8 = 9;
+pub const TAG_DESCRIPTION: u8 = 13;
+pub const TAG_PAYMENT_SECRET: u8 = 16;
+pub const TAG_PAYEE_PUBKEY: u8 = 19;
+pub const TAG_DESCRIPTION_HASH: u8 = 23;
+pub const TAG_MIN_FINAL_CLTV_EXPIRY_DELTA: u8 = 24;
+pub const TAG_METADATA: u8 = 27;
+
+// An on-chain fallback address. Versions 0 to 16 are segwit versions with
+// the witness program, 17 is a P2PKH and 18 a P2SH with the 20-byte hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fallback {
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub program: Vec<u8>,
+}
+
+// One hop of a private route to the payee, starting at `node_id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RouteHintHop {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub node_id: [u8; 33],
+    pub short_channel_id: ShortChannelId,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: CltvExpiryDelta,
+}
+impl RouteHintHop {
+    pub const LEN: usize = 51;
+}
+impl WireItem for RouteHintHop {
+    const FIXED_SIZE: Option<usize> = Some(RouteHintHop::LEN);
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(self.node_id.encode(w)?
+            + self.short_channel_id.encode(w)?
+            + self.fee_base_msat.encode(w)?
+            + self.fee_proportional_millionths.encode(w)?
+            + self.cltv_expiry_delta.encode(w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        RouteHintHop::LEN
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Ok(RouteHintHop {
+            node_id: WireItem::decode(r)?,
+            short_channel_id: WireItem::decode(r)?,
+            fee_base_msat: WireItem::decode(r)?,
+            fee_proportional_millionths: WireItem::decode(r)?,
+            cltv_expiry_delta: WireItem::decode(r)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TaggedField {
+    PaymentHash(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] [u8; 32]),
+    PaymentSecret(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] [u8; 32]),
+    Description(String),
+    PayeePubKey(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] [u8; 33]),
+    DescriptionHash(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] [u8; 32]),
+    Expiry(u64),
+    MinFinalCltvExpiryDelta(u64),
+    Fallback(Fallback),
+    RouteHint(Vec<RouteHintHop>),
+    Features(InvoiceFeatures),
+    Metadata(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] Vec<u8>),
+
+    // A field we do not know, or a known field with a length readers must
+    // skip. The data is kept as 5-bit words so it can be re-encoded as is.
+    Unknown {
+        tag: u8,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        data: Vec<u8>,
+    },
+}
+impl TaggedField {
+    pub fn tag(&self) -> u8 {
+        match self {
+            TaggedField::PaymentHash(_) => TAG_PAYMENT_HASH,
+            TaggedField::PaymentSecret(_) => TAG_PAYMENT_SECRET,
+            TaggedField::Description(_) => TAG_DESCRIPTION,
+            TaggedField::PayeePubKey(_) => TAG_PAYEE_PUBKEY,
+            TaggedField::DescriptionHash(_) => TAG_DESCRIPTION_HASH,
+            TaggedField::Expiry(_) => TAG_EXPIRY,
+            TaggedField::MinFinalCltvExpiryDelta(_) => TAG_MIN_FINAL_CLTV_EXPIRY_DELTA,
+            TaggedField::Fallback(_) => TAG_FALLBACK,
+            TaggedField::RouteHint(_) => TAG_ROUTE_HINT,
+            TaggedField::Features(_) => TAG_FEATURES,
+            TaggedField::Metadata(_) => TAG_METADATA,
+            TaggedField::Unknown { tag, .. } => *tag,
+        }
+    }
+
+    // The field data as 5-bit words.
+    pub fn data(&self) -> Vec<u8> {
+        match self {
+            TaggedField::PaymentHash(h)
+            | TaggedField::PaymentSecret(h)
+            | TaggedField::DescriptionHash(h) => to_u5(h),
+            TaggedField::Description(d) => to_u5(d.as_bytes()),
+            TaggedField::PayeePubKey(p) => to_u5(p),
+            TaggedField::Expiry(n) | TaggedField::MinFinalCltvExpiryDelta(n) => u64_to_u5(*n),
+            TaggedField::Fallback(f) => {
+                let mut res = vec![f.version];
+                res.extend(to_u5(&f.program));
+                res
+            }
+            TaggedField::RouteHint(hops) => {
+                let mut bytes = Vec::with_capacity(hops.len() * RouteHintHop::LEN);
+                for hop in hops {
+                    hop.encode(&mut bytes).expect("writing to a Vec");
+                }
+                to_u5(&bytes)
+            }
+            TaggedField::Features(f) => features_to_u5(f),
+            TaggedField::Metadata(m) => to_u5(m),
+            TaggedField::Unknown { data, .. } => data.clone(),
+        }
+    }
+
+    pub fn parse(tag: u8, data: &[u8]) -> Result<Self, Bolt11Error> {
+        Ok(match tag {
+            TAG_PAYMENT_HASH | TAG_PAYMENT_SECRET | TAG_DESCRIPTION_HASH if data.len() == 52 => {
+                let mut h = [0; 32];
+                h.copy_from_slice(&from_u5(data, false));
+                match tag {
+                    TAG_PAYMENT_HASH => TaggedField::PaymentHash(h),
+                    TAG_PAYMENT_SECRET => TaggedField::PaymentSecret(h),
+                    _ => TaggedField::DescriptionHash(h),
+                }
+            }
+            TAG_PAYEE_PUBKEY if data.len() == 53 => {
+                let mut p = [0; 33];
+                p.copy_from_slice(&from_u5(data, false));
+                TaggedField::PayeePubKey(p)
+            }
+            TAG_DESCRIPTION => TaggedField::Description(
+                String::from_utf8(from_u5(data, false))
+                    .map_err(|_| Bolt11Error::InvalidDescription)?,
+            ),
+            TAG_EXPIRY => TaggedField::Expiry(u5_to_u64(data)?),
+            TAG_MIN_FINAL_CLTV_EXPIRY_DELTA => {
+                TaggedField::MinFinalCltvExpiryDelta(u5_to_u64(data)?)
+            }
+            TAG_FALLBACK if !data.is_empty() && data[0] <= 18 => TaggedField::Fallback(Fallback {
+                version: data[0],
+                program: from_u5(&data[1..], false),
+            }),
+            TAG_ROUTE_HINT => {
+                let bytes = from_u5(data, false);
+                let mut r = &bytes[..];
+                let mut hops = Vec::new();
+                while !r.is_empty() {
+                    hops.push(
+                        RouteHintHop::decode(&mut r).map_err(|_| Bolt11Error::InvalidRouteHint)?,
+                    );
+                }
+                if hops.is_empty() {
+                    return Err(Bolt11Error::InvalidRouteHint);
+                }
+                TaggedField::RouteHint(hops)
+            }
+            TAG_FEATURES => TaggedField::Features(features_from_u5(data)),
+            TAG_METADATA => TaggedField::Metadata(from_u5(data, false)),
+            _ => TaggedField::Unknown {
+                tag,
+                data: data.to_vec(),
+            },
+        })
+    }
+}
+
+fn u64_to_u5(mut n: u64) -> Vec<u8> {
+    let mut res = Vec::new();
+    while n > 0 {
+        res.push((n & 0x1f) as u8);
+        n >>= 5;
+    }
+    res.reverse();
+    res
+}
+
+pub(crate) fn u5_to_u64(words: &[u8]) -> Result<u64, Bolt11Error> {
+    words.iter().try_fold(0_u64, |acc, w| {
+        if acc >> 59 != 0 {
+            Err(Bolt11Error::InvalidInteger)
+        } else {
+            Ok(acc << 5 | u64::from(*w))
+        }
+    })
+}
+
+// Feature bits are numbered from the end of the field, five per word.
+fn features_to_u5(features: &InvoiceFeatures) -> Vec<u8> {
+    let len = features.bits().next().map(|b| b / 5 + 1).unwrap_or(0);
+    let mut res = vec![0; len];
+    for b in features.bits() {
+        res[len - 1 - b / 5] |= 1 << (b % 5);
+    }
+    res
+}
+
+fn features_from_u5(words: &[u8]) -> InvoiceFeatures {
+    let mut features = InvoiceFeatures::new();
+    for (i, w) in words.iter().rev().enumerate() {
+        for b in 0..5 {
+            if (w >> b) & 1 == 1 {
+                features.set_bit(i * 5 + b);
+            }
+        }
+    }
+    features
+}
@@ -0,0 +1,338 @@
+use super::invoice_request::InvoiceRequest;
+use super::{
+    check_mirrored, check_ranges, decode_records, decode_string, encode_records, encode_string,
+    merkle, Bolt12Error, Reader, TlvRecord, Writer, EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+    EXPERIMENTAL_INVOICE_TYPES, EXPERIMENTAL_OFFER_TYPES, INVOICE_REQUEST_TYPES, INVOICE_TYPES,
+    INVREQ_METADATA_TYPE, OFFER_TYPES, SIGNATURE_TYPE, SIGNATURE_TYPES,
+};
+use crate::io::Write;
+use crate::items::blinded_path::BlindedPath;
+use crate::items::expiry::CltvExpiryDelta;
+use crate::items::feature::Bolt12Features;
+use crate::items::fees::MilliSatoshi;
+use crate::items::prefixed::LenPrefixed;
+use crate::{WireItem, WireRead};
+use alloc::vec::Vec;
+use secp256k1::Keypair;
+
+pub const HRP: &str = "lni";
+pub const MESSAGE_NAME: &str = "invoice";
+pub const DEFAULT_RELATIVE_EXPIRY: u32 = 7200;
+
+pub const PATHS_TYPE: u64 = 160;
+pub const BLINDEDPAY_TYPE: u64 = 162;
+pub const CREATED_AT_TYPE: u64 = 164;
+pub const RELATIVE_EXPIRY_TYPE: u64 = 166;
+pub const PAYMENT_HASH_TYPE: u64 = 168;
+pub const AMOUNT_TYPE: u64 = 170;
+pub const FALLBACKS_TYPE: u64 = 172;
+pub const FEATURES_TYPE: u64 = 174;
+pub const NODE_ID_TYPE: u64 = 176;
+
+const KNOWN_TYPES: &[u64] = &[
+    PATHS_TYPE,
+    BLINDEDPAY_TYPE,
+    CREATED_AT_TYPE,
+    RELATIVE_EXPIRY_TYPE,
+    PAYMENT_HASH_TYPE,
+    AMOUNT_TYPE,
+    FALLBACKS_TYPE,
+    FEATURES_TYPE,
+    NODE_ID_TYPE,
+];
+
+// The fees and limits of one of the invoice's blinded paths, as a whole.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlindedPayInfo {
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: CltvExpiryDelta,
+    pub htlc_minimum_msat: MilliSatoshi,
+    pub htlc_maximum_msat: MilliSatoshi,
+    pub features: Bolt12Features,
+}
+impl WireItem for BlindedPayInfo {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(self.fee_base_msat.encode(w)?
+            + self.fee_proportional_millionths.encode(w)?
+            + self.cltv_expiry_delta.encode(w)?
+            + self.htlc_minimum_msat.encode(w)?
+            + self.htlc_maximum_msat.encode(w)?
+            + self.features.encode(w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.fee_base_msat.encoded_len()
+            + self.fee_proportional_millionths.encoded_len()
+            + self.cltv_expiry_delta.encoded_len()
+            + self.htlc_minimum_msat.encoded_len()
+            + self.htlc_maximum_msat.encoded_len()
+            + WireItem::encoded_len(&self.features)
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Ok(BlindedPayInfo {
+            fee_base_msat: WireItem::decode(r)?,
+            fee_proportional_millionths: WireItem::decode(r)?,
+            cltv_expiry_delta: WireItem::decode(r)?,
+            htlc_minimum_msat: WireItem::decode(r)?,
+            htlc_maximum_msat: WireItem::decode(r)?,
+            features: WireItem::decode(r)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FallbackAddress {
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub address: LenPrefixed<u16, Vec<u8>>,
+}
+impl WireItem for FallbackAddress {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(self.version.encode(w)? + self.address.encode(w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.version.encoded_len() + self.address.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Ok(FallbackAddress {
+            version: WireItem::decode(r)?,
+            address: WireItem::decode(r)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Invoice {
+    // The request being answered, mirrored field for field. Its own
+    // signature is not part of the invoice.
+    pub invoice_request: InvoiceRequest,
+    pub paths: Vec<BlindedPath>,
+    // One per path, in the same order.
+    pub blindedpay: Vec<BlindedPayInfo>,
+    pub created_at: u64,
+    pub relative_expiry: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub payment_hash: [u8; 32],
+    pub amount: u64,
+    pub fallbacks: Option<Vec<FallbackAddress>>,
+    pub features: Option<Bolt12Features>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub node_id: [u8; 33],
+
+    // Unknown records in the invoice ranges.
+    pub unknown: Vec<TlvRecord>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub signature: Option<[u8; 64]>,
+}
+impl Invoice {
+    // Answers `request`. The amount is the one requested, or the offer
+    // amount times the quantity when it is in millisatoshis and the request
+    // has none; otherwise it must be set by the caller.
+    pub fn new(
+        request: &InvoiceRequest,
+        paths: Vec<(BlindedPath, BlindedPayInfo)>,
+        created_at: u64,
+        payment_hash: [u8; 32],
+        node_id: [u8; 33],
+    ) -> Self {
+        let amount = request
+            .amount
+            .or_else(|| match request.offer.currency {
+                None => request
+                    .offer
+                    .amount
+                    .map(|a| a.saturating_mul(request.quantity.unwrap_or(1))),
+                Some(_) => None,
+            })
+            .unwrap_or(0);
+        let mut invoice_request = request.clone();
+        invoice_request.signature = None;
+        let (paths, blindedpay) = paths.into_iter().unzip();
+        Invoice {
+            invoice_request,
+            paths,
+            blindedpay,
+            created_at,
+            relative_expiry: None,
+            payment_hash,
+            amount,
+            fallbacks: None,
+            features: None,
+            node_id,
+            unknown: Vec::new(),
+            signature: None,
+        }
+    }
+
+    pub fn from_records(records: &[TlvRecord]) -> Result<Self, Bolt12Error> {
+        let r = Reader { records };
+        let relative_expiry = match r.tu64(RELATIVE_EXPIRY_TYPE)? {
+            Some(x) if x > u64::from(u32::MAX) => {
+                return Err(Bolt12Error::InvalidField(RELATIVE_EXPIRY_TYPE))
+            }
+            x => x.map(|x| x as u32),
+        };
+        Ok(Invoice {
+            invoice_request: InvoiceRequest::from_records(records)?,
+            paths: r
+                .items(PATHS_TYPE)?
+                .ok_or(Bolt12Error::MissingField(PATHS_TYPE))?,
+            blindedpay: r
+                .items(BLINDEDPAY_TYPE)?
+                .ok_or(Bolt12Error::MissingField(BLINDEDPAY_TYPE))?,
+            created_at: r
+                .tu64(CREATED_AT_TYPE)?
+                .ok_or(Bolt12Error::MissingField(CREATED_AT_TYPE))?,
+            relative_expiry,
+            payment_hash: r
+                .item(PAYMENT_HASH_TYPE)?
+                .ok_or(Bolt12Error::MissingField(PAYMENT_HASH_TYPE))?,
+            amount: r
+                .tu64(AMOUNT_TYPE)?
+                .ok_or(Bolt12Error::MissingField(AMOUNT_TYPE))?,
+            fallbacks: r.items(FALLBACKS_TYPE)?,
+            features: r.features(FEATURES_TYPE)?,
+            node_id: r
+                .item(NODE_ID_TYPE)?
+                .ok_or(Bolt12Error::MissingField(NODE_ID_TYPE))?,
+            unknown: r.unknown(&[INVOICE_TYPES, EXPERIMENTAL_INVOICE_TYPES], KNOWN_TYPES)?,
+            signature: None,
+        })
+    }
+
+    pub fn records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        self.write_records(&mut w);
+        w.item(SIGNATURE_TYPE, self.signature.as_ref());
+        w.finish()
+    }
+
+    fn write_records(&self, w: &mut Writer) {
+        self.invoice_request.write_records(w);
+        w.items(PATHS_TYPE, Some(&self.paths));
+        w.items(BLINDEDPAY_TYPE, Some(&self.blindedpay));
+        w.tu64(CREATED_AT_TYPE, Some(self.created_at));
+        w.tu64(RELATIVE_EXPIRY_TYPE, self.relative_expiry.map(u64::from));
+        w.item(PAYMENT_HASH_TYPE, Some(&self.payment_hash));
+        w.tu64(AMOUNT_TYPE, Some(self.amount));
+        w.items(FALLBACKS_TYPE, self.fallbacks.as_deref());
+        w.features(FEATURES_TYPE, self.features.as_ref());
+        w.item(NODE_ID_TYPE, Some(&self.node_id));
+        w.records(&self.unknown);
+    }
+
+    fn unsigned_records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        self.write_records(&mut w);
+        w.finish()
+    }
+
+    pub fn expires_at(&self) -> u64 {
+        self.created_at.saturating_add(u64::from(
+            self.relative_expiry.unwrap_or(DEFAULT_RELATIVE_EXPIRY),
+        ))
+    }
+
+    // Signs with the key of `node_id`.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<(), Bolt12Error> {
+        if keypair.public_key().serialize() != self.node_id {
+            return Err(Bolt12Error::InvalidSignature);
+        }
+        self.signature = Some(merkle::sign(
+            MESSAGE_NAME,
+            &self.unsigned_records(),
+            keypair,
+        ));
+        Ok(())
+    }
+
+    pub fn verify_signature(&self) -> Result<(), Bolt12Error> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(Bolt12Error::MissingField(SIGNATURE_TYPE))?;
+        merkle::verify(
+            MESSAGE_NAME,
+            &self.unsigned_records(),
+            signature,
+            &self.node_id,
+        )
+    }
+
+    // The checks the payer makes against the request it sent: every offer
+    // and invoice request field must be mirrored, and the amount must be the
+    // one requested.
+    pub fn check_request(&self, sent: &InvoiceRequest) -> Result<(), Bolt12Error> {
+        check_mirrored(
+            &self.records(),
+            &sent.records(),
+            &[
+                INVREQ_METADATA_TYPE..=INVREQ_METADATA_TYPE,
+                OFFER_TYPES,
+                INVOICE_REQUEST_TYPES,
+                EXPERIMENTAL_OFFER_TYPES,
+                EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+            ],
+        )?;
+        match sent.amount {
+            Some(amount) if amount != self.amount => Err(Bolt12Error::InvalidField(AMOUNT_TYPE)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_records(&self.records())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Bolt12Error> {
+        let records = decode_records(bytes)?;
+        check_ranges(
+            &records,
+            &[
+                INVREQ_METADATA_TYPE..=INVREQ_METADATA_TYPE,
+                OFFER_TYPES,
+                INVOICE_REQUEST_TYPES,
+                INVOICE_TYPES,
+                SIGNATURE_TYPES,
+                EXPERIMENTAL_OFFER_TYPES,
+                EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+                EXPERIMENTAL_INVOICE_TYPES,
+            ],
+        )?;
+        let mut invoice = Invoice::from_records(&records)?;
+        if invoice.paths.is_empty() {
+            return Err(Bolt12Error::InvalidField(PATHS_TYPE));
+        }
+        if invoice.blindedpay.len() != invoice.paths.len() {
+            return Err(Bolt12Error::InvalidField(BLINDEDPAY_TYPE));
+        }
+        match invoice.invoice_request.offer.issuer_id {
+            Some(id) if id != invoice.node_id => {
+                return Err(Bolt12Error::InvalidField(NODE_ID_TYPE))
+            }
+            _ => (),
+        }
+        invoice.signature = Reader { records: &records }.item(SIGNATURE_TYPE)?;
+        invoice.verify_signature()?;
+        Ok(invoice)
+    }
+}
+impl core::fmt::Display for Invoice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", encode_string(HRP, &self.encode()))
+    }
+}
+impl core::str::FromStr for Invoice {
+    type Err = Bolt12Error;
+
+    fn from_str(s: &str) -> Result<Self, Bolt12Error> {
+        Invoice::decode(&decode_string(s, HRP)?)
+    }
+}
@@ -0,0 +1,61 @@
+use super::{decode_records, encode_records, Bolt12Error, Reader, TlvRecord, Writer};
+use alloc::{string::String, vec::Vec};
+
+pub const ERRONEOUS_FIELD_TYPE: u64 = 1;
+pub const SUGGESTED_VALUE_TYPE: u64 = 3;
+pub const ERROR_TYPE: u64 = 5;
+
+// Sent in reply to an invoice request or an invoice that could not be
+// handled, optionally pointing at the field at fault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvoiceError {
+    pub erroneous_field: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub suggested_value: Option<Vec<u8>>,
+    pub error: String,
+    pub unknown: Vec<TlvRecord>,
+}
+impl InvoiceError {
+    pub fn new(error: String) -> Self {
+        InvoiceError {
+            erroneous_field: None,
+            suggested_value: None,
+            error,
+            unknown: Vec::new(),
+        }
+    }
+
+    pub fn records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        w.tu64(ERRONEOUS_FIELD_TYPE, self.erroneous_field);
+        w.bytes(SUGGESTED_VALUE_TYPE, self.suggested_value.as_deref());
+        w.string(ERROR_TYPE, Some(&self.error));
+        w.records(&self.unknown);
+        w.finish()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_records(&self.records())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Bolt12Error> {
+        let records = decode_records(bytes)?;
+        let r = Reader { records: &records };
+        let err = InvoiceError {
+            erroneous_field: r.tu64(ERRONEOUS_FIELD_TYPE)?,
+            suggested_value: r.bytes(SUGGESTED_VALUE_TYPE),
+            error: r
+                .string(ERROR_TYPE)?
+                .ok_or(Bolt12Error::MissingField(ERROR_TYPE))?,
+            unknown: r.unknown(
+                &[0..=u64::MAX],
+                &[ERRONEOUS_FIELD_TYPE, SUGGESTED_VALUE_TYPE, ERROR_TYPE],
+            )?,
+        };
+        if err.suggested_value.is_some() && err.erroneous_field.is_none() {
+            return Err(Bolt12Error::MissingField(ERRONEOUS_FIELD_TYPE));
+        }
+        Ok(err)
+    }
+}
@@ -0,0 +1,234 @@
+use super::offer::{self, Offer};
+use super::{
+    check_mirrored, check_ranges, decode_records, decode_string, encode_records, encode_string,
+    merkle, Bolt12Error, Reader, TlvRecord, Writer, EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+    EXPERIMENTAL_OFFER_TYPES, INVOICE_REQUEST_TYPES, INVREQ_METADATA_TYPE, OFFER_TYPES,
+    SIGNATURE_TYPE, SIGNATURE_TYPES,
+};
+use crate::items::blinded_path::BlindedPath;
+use crate::items::feature::Bolt12Features;
+use crate::items::hash::BITCOIN_CHAIN_HASH;
+use alloc::{string::String, vec::Vec};
+use secp256k1::Keypair;
+
+pub const HRP: &str = "lnr";
+pub const MESSAGE_NAME: &str = "invoice_request";
+
+pub const CHAIN_TYPE: u64 = 80;
+pub const AMOUNT_TYPE: u64 = 82;
+pub const FEATURES_TYPE: u64 = 84;
+pub const QUANTITY_TYPE: u64 = 86;
+pub const PAYER_ID_TYPE: u64 = 88;
+pub const PAYER_NOTE_TYPE: u64 = 89;
+pub const PATHS_TYPE: u64 = 90;
+
+const KNOWN_TYPES: &[u64] = &[
+    INVREQ_METADATA_TYPE,
+    CHAIN_TYPE,
+    AMOUNT_TYPE,
+    FEATURES_TYPE,
+    QUANTITY_TYPE,
+    PAYER_ID_TYPE,
+    PAYER_NOTE_TYPE,
+    PATHS_TYPE,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvoiceRequest {
+    // The offer being paid, mirrored field for field.
+    pub offer: Offer,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub metadata: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub chain: Option<[u8; 32]>,
+    pub amount: Option<u64>,
+    pub features: Option<Bolt12Features>,
+    pub quantity: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub payer_id: [u8; 33],
+    pub payer_note: Option<String>,
+    pub paths: Option<Vec<BlindedPath>>,
+
+    // Unknown records in the invoice request ranges.
+    pub unknown: Vec<TlvRecord>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub signature: Option<[u8; 64]>,
+}
+impl InvoiceRequest {
+    pub fn new(offer: &Offer, metadata: Vec<u8>, payer_id: [u8; 33]) -> Self {
+        InvoiceRequest {
+            offer: offer.clone(),
+            metadata,
+            chain: None,
+            amount: None,
+            features: None,
+            quantity: None,
+            payer_id,
+            payer_note: None,
+            paths: None,
+            unknown: Vec::new(),
+            signature: None,
+        }
+    }
+
+    // Reads the offer and invoice request fields out of a stream, ignoring
+    // the signature and records of later stages.
+    pub fn from_records(records: &[TlvRecord]) -> Result<Self, Bolt12Error> {
+        let r = Reader { records };
+        Ok(InvoiceRequest {
+            offer: Offer::from_records(records)?,
+            metadata: r
+                .bytes(INVREQ_METADATA_TYPE)
+                .ok_or(Bolt12Error::MissingField(INVREQ_METADATA_TYPE))?,
+            chain: r.item(CHAIN_TYPE)?,
+            amount: r.tu64(AMOUNT_TYPE)?,
+            features: r.features(FEATURES_TYPE)?,
+            quantity: r.tu64(QUANTITY_TYPE)?,
+            payer_id: r
+                .item(PAYER_ID_TYPE)?
+                .ok_or(Bolt12Error::MissingField(PAYER_ID_TYPE))?,
+            payer_note: r.string(PAYER_NOTE_TYPE)?,
+            paths: r.items(PATHS_TYPE)?,
+            unknown: r.unknown(
+                &[
+                    INVREQ_METADATA_TYPE..=INVREQ_METADATA_TYPE,
+                    INVOICE_REQUEST_TYPES,
+                    EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+                ],
+                KNOWN_TYPES,
+            )?,
+            signature: None,
+        })
+    }
+
+    pub fn records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        self.write_records(&mut w);
+        w.item(SIGNATURE_TYPE, self.signature.as_ref());
+        w.finish()
+    }
+
+    // Everything but the signature.
+    pub(super) fn write_records(&self, w: &mut Writer) {
+        self.offer.write_records(w);
+        w.bytes(INVREQ_METADATA_TYPE, Some(&self.metadata));
+        w.item(CHAIN_TYPE, self.chain.as_ref());
+        w.tu64(AMOUNT_TYPE, self.amount);
+        w.features(FEATURES_TYPE, self.features.as_ref());
+        w.tu64(QUANTITY_TYPE, self.quantity);
+        w.item(PAYER_ID_TYPE, Some(&self.payer_id));
+        w.string(PAYER_NOTE_TYPE, self.payer_note.as_ref());
+        w.items(PATHS_TYPE, self.paths.as_deref());
+        w.records(&self.unknown);
+    }
+
+    fn unsigned_records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        self.write_records(&mut w);
+        w.finish()
+    }
+
+    // Signs with the key of `payer_id`.
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<(), Bolt12Error> {
+        if keypair.public_key().serialize() != self.payer_id {
+            return Err(Bolt12Error::InvalidSignature);
+        }
+        self.signature = Some(merkle::sign(
+            MESSAGE_NAME,
+            &self.unsigned_records(),
+            keypair,
+        ));
+        Ok(())
+    }
+
+    pub fn verify_signature(&self) -> Result<(), Bolt12Error> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(Bolt12Error::MissingField(SIGNATURE_TYPE))?;
+        merkle::verify(
+            MESSAGE_NAME,
+            &self.unsigned_records(),
+            signature,
+            &self.payer_id,
+        )
+    }
+
+    // The checks the offer issuer makes: the offer fields must be exactly the
+    // ones of `offer`, and the request must fit its amount, quantity and
+    // chains.
+    pub fn check_offer(&self, offer: &Offer) -> Result<(), Bolt12Error> {
+        check_mirrored(
+            &self.records(),
+            &offer.records(),
+            &[OFFER_TYPES, EXPERIMENTAL_OFFER_TYPES],
+        )?;
+        let chain = self.chain.unwrap_or(BITCOIN_CHAIN_HASH);
+        let chain_ok = match &offer.chains {
+            Some(chains) => chains.contains(&chain),
+            None => chain == BITCOIN_CHAIN_HASH,
+        };
+        if !chain_ok {
+            return Err(Bolt12Error::InvalidField(CHAIN_TYPE));
+        }
+        match (offer.quantity_max, self.quantity) {
+            (Some(_), None) => return Err(Bolt12Error::MissingField(QUANTITY_TYPE)),
+            (Some(max), Some(q)) if q == 0 || (max != 0 && q > max) => {
+                return Err(Bolt12Error::InvalidField(QUANTITY_TYPE))
+            }
+            (None, Some(_)) => return Err(Bolt12Error::UnexpectedField(QUANTITY_TYPE)),
+            _ => (),
+        }
+        // Amounts in a currency are converted by the issuer, so only
+        // millisatoshi amounts can be checked here.
+        if let (Some(expected), Some(amount), None) = (offer.amount, self.amount, &offer.currency) {
+            let expected = expected.saturating_mul(self.quantity.unwrap_or(1));
+            if amount < expected {
+                return Err(Bolt12Error::InvalidField(AMOUNT_TYPE));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_records(&self.records())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Bolt12Error> {
+        let records = decode_records(bytes)?;
+        check_ranges(
+            &records,
+            &[
+                INVREQ_METADATA_TYPE..=INVREQ_METADATA_TYPE,
+                OFFER_TYPES,
+                INVOICE_REQUEST_TYPES,
+                SIGNATURE_TYPES,
+                EXPERIMENTAL_OFFER_TYPES,
+                EXPERIMENTAL_INVOICE_REQUEST_TYPES,
+            ],
+        )?;
+        let mut request = InvoiceRequest::from_records(&records)?;
+        if request.amount.is_none() && request.offer.amount.is_none() {
+            return Err(Bolt12Error::MissingField(AMOUNT_TYPE));
+        }
+        if request.offer.currency.is_some() && request.offer.amount.is_none() {
+            return Err(Bolt12Error::MissingField(offer::AMOUNT_TYPE));
+        }
+        request.signature = Reader { records: &records }.item(SIGNATURE_TYPE)?;
+        request.verify_signature()?;
+        Ok(request)
+    }
+}
+impl core::fmt::Display for InvoiceRequest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", encode_string(HRP, &self.encode()))
+    }
+}
+impl core::str::FromStr for InvoiceRequest {
+    type Err = Bolt12Error;
+
+    fn from_str(s: &str) -> Result<Self, Bolt12Error> {
+        InvoiceRequest::decode(&decode_string(s, HRP)?)
+    }
+}
@@ -0,0 +1,133 @@
+// The merkle tree BOLT #12 signatures commit to. Every record outside the
+// signature range is a leaf, paired with a nonce leaf derived from the first
+// record so that the tree does not leak the values of hidden records.
+
+use super::{Bolt12Error, TlvRecord, SIGNATURE_TYPES};
+use crate::write_varint;
+use alloc::{format, vec::Vec};
+use bitcoin_hashes::{sha256, Hash as _, HashEngine as _};
+use secp256k1::schnorr::Signature;
+use secp256k1::{Keypair, Message, PublicKey, Secp256k1};
+
+pub fn tagged_hash(tag: &[u8], msg: &[u8]) -> [u8; 32] {
+    tagged_hash_from_engine(tagged_engine(tag), msg)
+}
+
+fn tagged_engine(tag: &[u8]) -> sha256::HashEngine {
+    let tag_hash = sha256::Hash::hash(tag);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    engine
+}
+
+fn tagged_hash_from_engine(mut engine: sha256::HashEngine, msg: &[u8]) -> [u8; 32] {
+    engine.input(msg);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn branch(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let (lesser, greater) = if a < b { (a, b) } else { (b, a) };
+    let mut msg = [0; 64];
+    msg[..32].copy_from_slice(&lesser);
+    msg[32..].copy_from_slice(&greater);
+    tagged_hash(b"LnBranch", &msg)
+}
+
+pub fn root(records: &[TlvRecord]) -> [u8; 32] {
+    let mut records = records
+        .iter()
+        .filter(|r| !SIGNATURE_TYPES.contains(&r.tlv_type))
+        .peekable();
+    let first = match records.peek() {
+        Some(first) => {
+            let mut bytes = Vec::new();
+            first.encode(&mut bytes).expect("writing to a Vec");
+            bytes
+        }
+        None => return tagged_hash(b"LnLeaf", &[]),
+    };
+    let nonce_tag = [&b"LnNonce"[..], &first].concat();
+    let mut nodes: Vec<[u8; 32]> = records
+        .map(|r| {
+            let mut tlv = Vec::new();
+            r.encode(&mut tlv).expect("writing to a Vec");
+            let mut tlv_type = Vec::new();
+            write_varint(r.tlv_type, &mut tlv_type).expect("writing to a Vec");
+            branch(
+                tagged_hash(b"LnLeaf", &tlv),
+                tagged_hash(&nonce_tag, &tlv_type),
+            )
+        })
+        .collect();
+    // Unpaired nodes move up a level as is, so the tree is deepest on the
+    // lowest types.
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => branch(*a, *b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    nodes[0]
+}
+
+// The digest signed by the `signature` field of `messagename`, e.g.
+// "invoice_request" or "invoice".
+pub fn signature_digest(messagename: &str, records: &[TlvRecord]) -> [u8; 32] {
+    let tag = format!("lightning{}signature", messagename);
+    tagged_hash(tag.as_bytes(), &root(records))
+}
+
+pub fn sign(messagename: &str, records: &[TlvRecord], keypair: &Keypair) -> [u8; 64] {
+    let msg = Message::from_digest(signature_digest(messagename, records));
+    Secp256k1::signing_only()
+        .sign_schnorr_no_aux_rand(&msg, keypair)
+        .serialize()
+}
+
+pub fn verify(
+    messagename: &str,
+    records: &[TlvRecord],
+    signature: &[u8; 64],
+    pubkey: &[u8; 33],
+) -> Result<(), Bolt12Error> {
+    let pubkey = PublicKey::from_slice(pubkey).map_err(|_| Bolt12Error::InvalidSignature)?;
+    let signature = Signature::from_slice(signature).map_err(|_| Bolt12Error::InvalidSignature)?;
+    let msg = Message::from_digest(signature_digest(messagename, records));
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &msg, &pubkey.x_only_public_key().0)
+        .map_err(|_| Bolt12Error::InvalidSignature)
+}
+
+#[test]
+fn spec_merkle_roots() {
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+    let vectors = [
+        (
+            "010203e8",
+            "b013756c8fee86503a0b4abdab4cddeb1af5d344ca6fc2fa8b6c08938caa6f93",
+        ),
+        (
+            "010203e802080000010000020003",
+            "c3774abbf4815aa54ccaa026bff6581f01f3be5fe814c620a252534f434bc0d1",
+        ),
+        (
+            "010203e802080000010000020003\
+             03310266e4598d1d3c415f572a8488830b60f7e744ed9235eb0b1ba93283b315c0351800000000000000010000000000000002",
+            "ab2e79b1283b0b31e0b035258de23782df6b89a38cfa7237bde69aed1a658c5d",
+        ),
+    ];
+    for (stream, expected) in vectors.iter() {
+        let records = super::decode_records(&hex(stream)).unwrap();
+        assert_eq!(root(&records).to_vec(), hex(expected));
+    }
+}
@@ -0,0 +1,427 @@
+pub mod invoice;
+pub mod invoice_error;
+pub mod invoice_request;
+pub mod merkle;
+pub mod offer;
+
+use crate::bech32::{self, Bech32Error};
+use crate::items::feature::{FeatureSet, RawFeatureVector};
+use crate::items::truncated::Tu64;
+pub use crate::tlv::TlvRecord;
+use crate::tlv::TlvStream;
+use crate::{TLVWireItem, WireItem};
+use alloc::{string::String, vec::Vec};
+use core::ops::RangeInclusive;
+
+pub const OFFER_TYPES: RangeInclusive<u64> = 1..=79;
+pub const INVOICE_REQUEST_TYPES: RangeInclusive<u64> = 80..=159;
+pub const INVOICE_TYPES: RangeInclusive<u64> = 160..=239;
+pub const SIGNATURE_TYPES: RangeInclusive<u64> = 240..=1000;
+pub const EXPERIMENTAL_OFFER_TYPES: RangeInclusive<u64> = 1_000_000_000..=1_999_999_999;
+pub const EXPERIMENTAL_INVOICE_REQUEST_TYPES: RangeInclusive<u64> = 2_000_000_000..=2_999_999_999;
+pub const EXPERIMENTAL_INVOICE_TYPES: RangeInclusive<u64> = 3_000_000_000..=3_999_999_999;
+
+// invreq_metadata sits at type 0, below the offer fields, but belongs to the
+// invoice request.
+pub const INVREQ_METADATA_TYPE: u64 = 0;
+pub const SIGNATURE_TYPE: u64 = 240;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bolt12Error {
+    Bech32(Bech32Error),
+    InvalidPrefix,
+    // The TLV stream is truncated, or its types are not strictly increasing.
+    InvalidTlvStream,
+    InvalidField(u64),
+    // A field outside the type ranges allowed at this stage.
+    UnexpectedField(u64),
+    MissingField(u64),
+    UnknownRequiredFeature(usize),
+    InvalidSignature,
+    // A field that must be mirrored from the previous stage differs.
+    MirrorMismatch(u64),
+}
+impl From<Bech32Error> for Bolt12Error {
+    fn from(e: Bech32Error) -> Self {
+        Bolt12Error::Bech32(e)
+    }
+}
+impl core::fmt::Display for Bolt12Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Bolt12Error::Bech32(e) => write!(f, "{}", e),
+            Bolt12Error::InvalidPrefix => write!(f, "invalid prefix"),
+            Bolt12Error::InvalidTlvStream => write!(f, "invalid tlv stream"),
+            Bolt12Error::InvalidField(t) => write!(f, "invalid field {}", t),
+            Bolt12Error::UnexpectedField(t) => write!(f, "unexpected field {}", t),
+            Bolt12Error::MissingField(t) => write!(f, "missing field {}", t),
+            Bolt12Error::UnknownRequiredFeature(bit) => {
+                write!(f, "unknown required feature {}", bit)
+            }
+            Bolt12Error::InvalidSignature => write!(f, "invalid signature"),
+            Bolt12Error::MirrorMismatch(t) => write!(f, "field {} does not match", t),
+        }
+    }
+}
+impl core::error::Error for Bolt12Error {}
+
+// BOLT #12 messages keep the records they do not know, as they are part of
+// the signed merkle tree and must be mirrored.
+pub fn decode_records(bytes: &[u8]) -> Result<Vec<TlvRecord>, Bolt12Error> {
+    TlvStream::from_bytes(bytes)
+        .map(TlvStream::into_records)
+        .map_err(|_| Bolt12Error::InvalidTlvStream)
+}
+
+pub fn encode_records(records: &[TlvRecord]) -> Vec<u8> {
+    let mut res = Vec::new();
+    for record in records {
+        record.encode(&mut res).expect("writing to a Vec");
+    }
+    res
+}
+
+// Fails on the first record outside `ranges`.
+fn check_ranges(records: &[TlvRecord], ranges: &[RangeInclusive<u64>]) -> Result<(), Bolt12Error> {
+    match records
+        .iter()
+        .find(|r| !ranges.iter().any(|range| range.contains(&r.tlv_type)))
+    {
+        Some(r) => Err(Bolt12Error::UnexpectedField(r.tlv_type)),
+        None => Ok(()),
+    }
+}
+
+// Checks that every record in `ranges` is the same in both streams.
+fn check_mirrored(
+    ours: &[TlvRecord],
+    theirs: &[TlvRecord],
+    ranges: &[RangeInclusive<u64>],
+) -> Result<(), Bolt12Error> {
+    let in_ranges = |r: &&TlvRecord| ranges.iter().any(|range| range.contains(&r.tlv_type));
+    let mut ours = ours.iter().filter(in_ranges);
+    let mut theirs = theirs.iter().filter(in_ranges);
+    loop {
+        match (ours.next(), theirs.next()) {
+            (None, None) => return Ok(()),
+            (Some(a), Some(b)) if a == b => (),
+            (Some(a), Some(b)) => {
+                return Err(Bolt12Error::MirrorMismatch(a.tlv_type.min(b.tlv_type)))
+            }
+            (Some(r), None) | (None, Some(r)) => {
+                return Err(Bolt12Error::MirrorMismatch(r.tlv_type))
+            }
+        }
+    }
+}
+
+fn encode_string(hrp: &str, bytes: &[u8]) -> String {
+    bech32::encode(hrp, &bech32::to_u5(bytes), false)
+}
+
+// BOLT #12 strings have no checksum and may be split with `+` followed by
+// optional whitespace.
+fn decode_string(s: &str, hrp: &str) -> Result<Vec<u8>, Bolt12Error> {
+    let mut joined = String::with_capacity(s.len());
+    let mut parts = s.split('+');
+    joined.push_str(parts.next().unwrap_or(""));
+    for part in parts {
+        let part = part.trim_start();
+        if part.is_empty() || joined.is_empty() {
+            return Err(Bolt12Error::Bech32(Bech32Error::InvalidChar('+')));
+        }
+        joined.push_str(part);
+    }
+    let (found, data) = bech32::decode(&joined, false)?;
+    if found != hrp {
+        return Err(Bolt12Error::InvalidPrefix);
+    }
+    Ok(bech32::from_u5(&data, false))
+}
+
+// Reads typed fields out of a record list.
+struct Reader<'a> {
+    records: &'a [TlvRecord],
+}
+impl<'a> Reader<'a> {
+    fn get(&self, tlv_type: u64) -> Option<&'a [u8]> {
+        self.records
+            .binary_search_by_key(&tlv_type, |r| r.tlv_type)
+            .ok()
+            .map(|i| self.records[i].value.as_slice())
+    }
+    fn bytes(&self, tlv_type: u64) -> Option<Vec<u8>> {
+        self.get(tlv_type).map(|v| v.to_vec())
+    }
+    fn string(&self, tlv_type: u64) -> Result<Option<String>, Bolt12Error> {
+        self.get(tlv_type)
+            .map(|v| String::from_utf8(v.to_vec()).map_err(|_| Bolt12Error::InvalidField(tlv_type)))
+            .transpose()
+    }
+    fn tu64(&self, tlv_type: u64) -> Result<Option<u64>, Bolt12Error> {
+        self.get(tlv_type)
+            .map(|mut v| {
+                let len = v.len();
+                <Tu64 as TLVWireItem>::decode(&mut v, len)
+                    .map(|n| n.0)
+                    .map_err(|_| Bolt12Error::InvalidField(tlv_type))
+            })
+            .transpose()
+    }
+    fn item<T: WireItem>(&self, tlv_type: u64) -> Result<Option<T>, Bolt12Error> {
+        self.get(tlv_type)
+            .map(|mut v| match T::decode(&mut v) {
+                Ok(item) if v.is_empty() => Ok(item),
+                _ => Err(Bolt12Error::InvalidField(tlv_type)),
+            })
+            .transpose()
+    }
+    // A field made of any number of items, up to the end of the value.
+    fn items<T: WireItem>(&self, tlv_type: u64) -> Result<Option<Vec<T>>, Bolt12Error> {
+        self.get(tlv_type)
+            .map(|mut v| {
+                let mut items = Vec::new();
+                while !v.is_empty() {
+                    items.push(T::decode(&mut v).map_err(|_| Bolt12Error::InvalidField(tlv_type))?);
+                }
+                Ok(items)
+            })
+            .transpose()
+    }
+    fn features<F: FeatureSet>(
+        &self,
+        tlv_type: u64,
+    ) -> Result<Option<RawFeatureVector<F>>, Bolt12Error> {
+        let features = match self.get(tlv_type) {
+            Some(mut v) => {
+                let len = v.len();
+                RawFeatureVector::<F>::decode_bits(&mut v, len)
+                    .map_err(|_| Bolt12Error::InvalidField(tlv_type))?
+            }
+            None => return Ok(None),
+        };
+        if let Some(bit) = features.unknown_required().next() {
+            return Err(Bolt12Error::UnknownRequiredFeature(bit));
+        }
+        Ok(Some(features))
+    }
+    // The records in `ranges` that are not in `known`. They must all be odd:
+    // an unknown even record is one the reader is required to understand.
+    fn unknown(
+        &self,
+        ranges: &[RangeInclusive<u64>],
+        known: &[u64],
+    ) -> Result<Vec<TlvRecord>, Bolt12Error> {
+        self.records
+            .iter()
+            .filter(|r| ranges.iter().any(|range| range.contains(&r.tlv_type)))
+            .filter(|r| !known.contains(&r.tlv_type))
+            .map(|r| match r.tlv_type & 1 {
+                0 => Err(Bolt12Error::UnexpectedField(r.tlv_type)),
+                _ => Ok(r.clone()),
+            })
+            .collect()
+    }
+}
+
+// Builds a record list out of typed fields.
+#[derive(Default)]
+struct Writer {
+    records: Vec<TlvRecord>,
+}
+impl Writer {
+    fn bytes(&mut self, tlv_type: u64, value: Option<&[u8]>) {
+        if let Some(value) = value {
+            self.records.push(TlvRecord {
+                tlv_type,
+                value: value.to_vec(),
+            });
+        }
+    }
+    fn string(&mut self, tlv_type: u64, value: Option<&String>) {
+        self.bytes(tlv_type, value.map(|s| s.as_bytes()));
+    }
+    fn tu64(&mut self, tlv_type: u64, value: Option<u64>) {
+        if let Some(n) = value {
+            let mut bytes = Vec::new();
+            TLVWireItem::encode(&Tu64(n), &mut bytes).expect("writing to a Vec");
+            self.bytes(tlv_type, Some(&bytes));
+        }
+    }
+    fn item<T: WireItem>(&mut self, tlv_type: u64, value: Option<&T>) {
+        self.items(tlv_type, value.map(core::slice::from_ref));
+    }
+    fn items<T: WireItem>(&mut self, tlv_type: u64, value: Option<&[T]>) {
+        if let Some(items) = value {
+            let mut bytes = Vec::new();
+            for item in items {
+                item.encode(&mut bytes).expect("writing to a Vec");
+            }
+            self.bytes(tlv_type, Some(&bytes));
+        }
+    }
+    fn features<F: FeatureSet>(&mut self, tlv_type: u64, value: Option<&RawFeatureVector<F>>) {
+        if let Some(features) = value {
+            let mut bytes = Vec::new();
+            features.encode_bits(&mut bytes).expect("writing to a Vec");
+            self.bytes(tlv_type, Some(&bytes));
+        }
+    }
+    fn records(&mut self, records: &[TlvRecord]) {
+        self.records.extend_from_slice(records);
+    }
+    fn finish(mut self) -> Vec<TlvRecord> {
+        self.records.sort_by_key(|r| r.tlv_type);
+        self.records
+    }
+}
+
+#[test]
+fn offer_to_invoice() {
+    use crate::items::blinded_path::{BlindedHop, BlindedPath, IntroductionNode};
+    use crate::items::expiry::CltvExpiryDelta;
+    use crate::items::feature::Bolt12Features;
+    use crate::items::fees::MilliSatoshi;
+    use secp256k1::{Keypair, Secp256k1, SecretKey};
+
+    let secp = Secp256k1::new();
+    let issuer = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[1; 32]).unwrap());
+    let payer = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[2; 32]).unwrap());
+
+    let offer = offer::Offer {
+        amount: Some(1000),
+        description: Some("coffee".to_owned()),
+        quantity_max: Some(0),
+        issuer_id: Some(issuer.public_key().serialize()),
+        ..Default::default()
+    };
+    let s = offer.to_string();
+    assert!(s.starts_with("lno1"));
+    assert_eq!(s.parse::<offer::Offer>().unwrap(), offer);
+    // Strings may be split with '+' and whitespace.
+    let split = format!("{}+\n {}", &s[..20], &s[20..]);
+    assert_eq!(split.parse::<offer::Offer>().unwrap(), offer);
+
+    let mut request =
+        invoice_request::InvoiceRequest::new(&offer, vec![7; 16], payer.public_key().serialize());
+    request.quantity = Some(2);
+    request.amount = Some(2000);
+    request.payer_note = Some("two please".to_owned());
+    assert!(request.sign(&issuer).is_err());
+    request.sign(&payer).unwrap();
+    let parsed: invoice_request::InvoiceRequest = request.to_string().parse().unwrap();
+    assert_eq!(parsed, request);
+    parsed.check_offer(&offer).unwrap();
+
+    let mut other = offer.clone();
+    other.description = Some("tea".to_owned());
+    assert_eq!(
+        parsed.check_offer(&other),
+        Err(Bolt12Error::MirrorMismatch(offer::DESCRIPTION_TYPE))
+    );
+    let mut cheap = request.clone();
+    cheap.amount = Some(1999);
+    assert_eq!(
+        cheap.check_offer(&offer),
+        Err(Bolt12Error::InvalidField(invoice_request::AMOUNT_TYPE))
+    );
+
+    let path = BlindedPath {
+        introduction_node: IntroductionNode::NodeId(issuer.public_key().serialize()),
+        first_path_key: payer.public_key().serialize(),
+        hops: vec![BlindedHop {
+            blinded_node_id: issuer.public_key().serialize(),
+            encrypted_recipient_data: vec![1, 2, 3].into(),
+        }],
+    };
+    let payinfo = invoice::BlindedPayInfo {
+        fee_base_msat: 1,
+        fee_proportional_millionths: 100,
+        cltv_expiry_delta: CltvExpiryDelta(144),
+        htlc_minimum_msat: MilliSatoshi(1),
+        htlc_maximum_msat: MilliSatoshi(1_000_000),
+        features: Bolt12Features::new(),
+    };
+    let mut inv = invoice::Invoice::new(
+        &parsed,
+        vec![(path, payinfo)],
+        1_700_000_000,
+        [9; 32],
+        issuer.public_key().serialize(),
+    );
+    assert_eq!(inv.amount, 2000);
+    inv.sign(&issuer).unwrap();
+    let parsed_inv: invoice::Invoice = inv.to_string().parse().unwrap();
+    assert_eq!(parsed_inv, inv);
+    assert_eq!(parsed_inv.expires_at(), 1_700_007_200);
+    parsed_inv.check_request(&request).unwrap();
+
+    let mut tampered = inv.clone();
+    tampered.invoice_request.payer_note = None;
+    assert_eq!(
+        tampered.check_request(&request),
+        Err(Bolt12Error::MirrorMismatch(
+            invoice_request::PAYER_NOTE_TYPE
+        ))
+    );
+    assert_eq!(
+        invoice::Invoice::decode(&tampered.encode()),
+        Err(Bolt12Error::InvalidSignature)
+    );
+
+    // Invoice records have no place in an invoice request.
+    let mut records = request.records();
+    records.push(TlvRecord {
+        tlv_type: invoice::CREATED_AT_TYPE,
+        value: vec![1],
+    });
+    records.sort_by_key(|r| r.tlv_type);
+    assert_eq!(
+        invoice_request::InvoiceRequest::decode(&encode_records(&records)),
+        Err(Bolt12Error::UnexpectedField(invoice::CREATED_AT_TYPE))
+    );
+
+    // Unknown odd records are kept, unknown even ones are rejected.
+    let with = |mut records: Vec<TlvRecord>, tlv_type| {
+        records.push(TlvRecord {
+            tlv_type,
+            value: vec![1],
+        });
+        records.sort_by_key(|r| r.tlv_type);
+        records
+    };
+    let odd = offer::Offer::from_records(&with(offer.records(), 25)).unwrap();
+    assert_eq!(odd.unknown, with(Vec::new(), 25));
+    assert_eq!(
+        offer::Offer::from_records(&with(offer.records(), 24)),
+        Err(Bolt12Error::UnexpectedField(24))
+    );
+    assert_eq!(
+        offer::Offer::from_records(&with(offer.records(), 1_000_000_000)),
+        Err(Bolt12Error::UnexpectedField(1_000_000_000))
+    );
+    let odd = invoice_request::InvoiceRequest::from_records(&with(request.records(), 101));
+    assert_eq!(odd.unwrap().unknown, with(Vec::new(), 101));
+    assert_eq!(
+        invoice_request::InvoiceRequest::from_records(&with(request.records(), 100)),
+        Err(Bolt12Error::UnexpectedField(100))
+    );
+    assert_eq!(
+        invoice_request::InvoiceRequest::from_records(&with(request.records(), 24)),
+        Err(Bolt12Error::UnexpectedField(24))
+    );
+    let odd = invoice::Invoice::from_records(&with(inv.records(), 201)).unwrap();
+    assert_eq!(odd.unknown, with(Vec::new(), 201));
+    assert_eq!(
+        invoice::Invoice::from_records(&with(inv.records(), 200)),
+        Err(Bolt12Error::UnexpectedField(200))
+    );
+
+    let mut err = invoice_error::InvoiceError::new("amount too low".to_owned());
+    err.erroneous_field = Some(invoice_request::AMOUNT_TYPE);
+    err.suggested_value = Some(vec![0x07, 0xd0]);
+    assert_eq!(
+        invoice_error::InvoiceError::decode(&err.encode()).unwrap(),
+        err
+    );
+}
@@ -0,0 +1,141 @@
+use super::{
+    check_ranges, decode_records, decode_string, encode_records, encode_string, Bolt12Error,
+    Reader, TlvRecord, Writer, EXPERIMENTAL_OFFER_TYPES, OFFER_TYPES,
+};
+use crate::items::blinded_path::BlindedPath;
+use crate::items::feature::Bolt12Features;
+use alloc::{string::String, vec::Vec};
+
+pub const HRP: &str = "lno";
+
+pub const CHAINS_TYPE: u64 = 2;
+pub const METADATA_TYPE: u64 = 4;
+pub const CURRENCY_TYPE: u64 = 6;
+pub const AMOUNT_TYPE: u64 = 8;
+pub const DESCRIPTION_TYPE: u64 = 10;
+pub const FEATURES_TYPE: u64 = 12;
+pub const ABSOLUTE_EXPIRY_TYPE: u64 = 14;
+pub const PATHS_TYPE: u64 = 16;
+pub const ISSUER_TYPE: u64 = 18;
+pub const QUANTITY_MAX_TYPE: u64 = 20;
+pub const ISSUER_ID_TYPE: u64 = 22;
+
+const KNOWN_TYPES: &[u64] = &[
+    CHAINS_TYPE,
+    METADATA_TYPE,
+    CURRENCY_TYPE,
+    AMOUNT_TYPE,
+    DESCRIPTION_TYPE,
+    FEATURES_TYPE,
+    ABSOLUTE_EXPIRY_TYPE,
+    PATHS_TYPE,
+    ISSUER_TYPE,
+    QUANTITY_MAX_TYPE,
+    ISSUER_ID_TYPE,
+];
+
+// The offer fields, which invoice requests and invoices mirror.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Offer {
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub chains: Option<Vec<[u8; 32]>>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub metadata: Option<Vec<u8>>,
+    // An ISO 4217 code. When absent, `amount` is in millisatoshis.
+    pub currency: Option<String>,
+    pub amount: Option<u64>,
+    pub description: Option<String>,
+    pub features: Option<Bolt12Features>,
+    pub absolute_expiry: Option<u64>,
+    pub paths: Option<Vec<BlindedPath>>,
+    pub issuer: Option<String>,
+    pub quantity_max: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub issuer_id: Option<[u8; 33]>,
+
+    // Unknown records in the offer ranges.
+    pub unknown: Vec<TlvRecord>,
+}
+impl Offer {
+    // Reads the offer fields out of a stream, ignoring records of later
+    // stages.
+    pub fn from_records(records: &[TlvRecord]) -> Result<Self, Bolt12Error> {
+        let r = Reader { records };
+        Ok(Offer {
+            chains: r.items(CHAINS_TYPE)?,
+            metadata: r.bytes(METADATA_TYPE),
+            currency: r.string(CURRENCY_TYPE)?,
+            amount: r.tu64(AMOUNT_TYPE)?,
+            description: r.string(DESCRIPTION_TYPE)?,
+            features: r.features(FEATURES_TYPE)?,
+            absolute_expiry: r.tu64(ABSOLUTE_EXPIRY_TYPE)?,
+            paths: r.items(PATHS_TYPE)?,
+            issuer: r.string(ISSUER_TYPE)?,
+            quantity_max: r.tu64(QUANTITY_MAX_TYPE)?,
+            issuer_id: r.item(ISSUER_ID_TYPE)?,
+            unknown: r.unknown(&[OFFER_TYPES, EXPERIMENTAL_OFFER_TYPES], KNOWN_TYPES)?,
+        })
+    }
+
+    pub fn records(&self) -> Vec<TlvRecord> {
+        let mut w = Writer::default();
+        self.write_records(&mut w);
+        w.finish()
+    }
+
+    pub(super) fn write_records(&self, w: &mut Writer) {
+        w.items(CHAINS_TYPE, self.chains.as_deref());
+        w.bytes(METADATA_TYPE, self.metadata.as_deref());
+        w.string(CURRENCY_TYPE, self.currency.as_ref());
+        w.tu64(AMOUNT_TYPE, self.amount);
+        w.string(DESCRIPTION_TYPE, self.description.as_ref());
+        w.features(FEATURES_TYPE, self.features.as_ref());
+        w.tu64(ABSOLUTE_EXPIRY_TYPE, self.absolute_expiry);
+        w.items(PATHS_TYPE, self.paths.as_deref());
+        w.string(ISSUER_TYPE, self.issuer.as_ref());
+        w.tu64(QUANTITY_MAX_TYPE, self.quantity_max);
+        w.item(ISSUER_ID_TYPE, self.issuer_id.as_ref());
+        w.records(&self.unknown);
+    }
+
+    // The checks a reader of an offer makes before sending an invoice
+    // request for it.
+    pub fn validate(&self) -> Result<(), Bolt12Error> {
+        if self.amount.is_some() && self.description.is_none() {
+            return Err(Bolt12Error::MissingField(DESCRIPTION_TYPE));
+        }
+        if self.currency.is_some() && self.amount.is_none() {
+            return Err(Bolt12Error::MissingField(AMOUNT_TYPE));
+        }
+        match &self.paths {
+            Some(paths) if paths.is_empty() => Err(Bolt12Error::InvalidField(PATHS_TYPE)),
+            None if self.issuer_id.is_none() => Err(Bolt12Error::MissingField(ISSUER_ID_TYPE)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_records(&self.records())
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Bolt12Error> {
+        let records = decode_records(bytes)?;
+        check_ranges(&records, &[OFFER_TYPES, EXPERIMENTAL_OFFER_TYPES])?;
+        let offer = Offer::from_records(&records)?;
+        offer.validate()?;
+        Ok(offer)
+    }
+}
+impl core::fmt::Display for Offer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", encode_string(HRP, &self.encode()))
+    }
+}
+impl core::str::FromStr for Offer {
+    type Err = Bolt12Error;
+
+    fn from_str(s: &str) -> Result<Self, Bolt12Error> {
+        Offer::decode(&decode_string(s, HRP)?)
+    }
+}
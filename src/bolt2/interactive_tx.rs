@@ -0,0 +1,1139 @@
+use super::messages::{
+    AnyInteractiveTxMessage, TxAbort, TxAddInput, TxAddOutput, TxComplete, TxRemoveInput,
+    TxRemoveOutput,
+};
+use crate::items::channel_id::ChannelId;
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec::Vec};
+use bitcoin_hashes::{sha256d, Hash as _, HashEngine as _};
+use core::convert::TryFrom;
+
+// Limits from the interactive transaction construction protocol in BOLT #2.
+pub const MAX_RECEIVED_TX_ADD_INPUT_COUNT: usize = 4096;
+pub const MAX_RECEIVED_TX_ADD_OUTPUT_COUNT: usize = 4096;
+pub const MAX_INPUTS_OUTPUTS_COUNT: usize = 252;
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+pub const MAX_MONEY: u64 = 2_100_000_000_000_000;
+
+// Weight of the fields every transaction has (version, segwit marker and
+// flag, input and output counts, locktime), paid for by the initiator.
+pub const COMMON_FIELDS_WEIGHT: u64 = (4 + 1 + 1 + 4) * 4 + 2;
+
+// Witness weight for spending a 2-of-2 P2WSH funding output: item count, empty
+// item, two signatures and the witness script.
+pub const FUNDING_INPUT_WITNESS_WEIGHT: u64 = 1 + 1 + 73 + 73 + 72;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OutPoint {
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+impl TxOut {
+    pub fn weight(&self) -> u64 {
+        let len = self.script_pubkey.len() as u64;
+        (8 + compact_size_len(len) + len) * 4
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxInput {
+    pub serial_id: u64,
+    pub prevout: OutPoint,
+    pub sequence: u32,
+    // The output being spent, taken from the `prevtx` sent with it.
+    pub spent: TxOut,
+}
+impl TxInput {
+    // A lower bound on the weight of the input once signed: the outpoint,
+    // empty script_sig and sequence, plus the smallest witness that can spend
+    // the output's script type.
+    pub fn estimated_weight(&self) -> u64 {
+        let witness = match self.spent.script_pubkey.as_slice() {
+            // P2WPKH: item count, signature, compressed public key
+            [0x00, 0x14, ..] => 1 + 1 + 72 + 1 + 33,
+            // P2TR key path spend: item count, schnorr signature
+            [0x51, 0x20, ..] => 1 + 1 + 64,
+            // unknown witness script: at least the item count
+            _ => 1,
+        };
+        (32 + 4 + 1 + 4) * 4 + witness
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOutput {
+    pub serial_id: u64,
+    pub output: TxOut,
+}
+
+// An input both parties own, such as the current funding output when
+// splicing. It is sent without a `prevtx`, and its value is split between the
+// parties when checking their contributions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedInput {
+    pub prevout: OutPoint,
+    pub script_pubkey: Vec<u8>,
+    pub local_satoshis: u64,
+    pub remote_satoshis: u64,
+}
+impl SharedInput {
+    pub fn estimated_weight(&self) -> u64 {
+        let witness = match self.script_pubkey.as_slice() {
+            [0x51, 0x20, ..] => 1 + 1 + 64,
+            _ => FUNDING_INPUT_WITNESS_WEIGHT,
+        };
+        (32 + 4 + 1 + 4) * 4 + witness
+    }
+}
+
+// The output both parties contribute to, such as the channel funding output.
+// Its value is split between the parties when checking their contributions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharedOutput {
+    pub script_pubkey: Vec<u8>,
+    pub local_satoshis: u64,
+    pub remote_satoshis: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InteractiveTxParams {
+    pub channel_id: ChannelId,
+    pub is_initiator: bool,
+    pub locktime: u32,
+    pub feerate_per_kw: u32,
+    pub dust_limit_satoshis: u64,
+    pub shared_input: Option<SharedInput>,
+    pub shared_output: Option<SharedOutput>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InteractiveTxError {
+    // A message was sent or received while it was the other party's turn.
+    OutOfTurn,
+    // The negotiation has already completed or been aborted.
+    NotNegotiating,
+    ChannelIdMismatch,
+    // A serial_id has the parity of the wrong party.
+    WrongParity(u64),
+    DuplicateSerialId(u64),
+    UnknownSerialId(u64),
+    DuplicateInput(OutPoint),
+    InvalidPrevTx,
+    PrevTxVoutOutOfRange(u32),
+    // The spent output is not a segwit output.
+    NonSegwitInput(u32),
+    // The sequence number does not signal replaceability.
+    InvalidSequence(u32),
+    NonStandardOutput,
+    DustOutput(u64),
+    ExceedsMaxMoney(u64),
+    TooManyAddInputs,
+    TooManyAddOutputs,
+    TooManyInputsOrOutputs,
+    ExceedsMaxWeight(u64),
+    MissingSharedInput,
+    MissingSharedOutput,
+    // The peer referred to a shared input other than the one being spent.
+    UnknownSharedInput,
+    // The party's inputs do not cover its outputs.
+    InsufficientFunds { local: bool },
+    // The party's inputs do not cover its outputs and the fee for its share
+    // of the transaction at the agreed feerate.
+    InsufficientFees { local: bool },
+    // The peer sent `tx_abort`.
+    Aborted(Vec<u8>),
+    UnexpectedMessage(u16),
+}
+impl core::fmt::Display for InteractiveTxError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use InteractiveTxError::*;
+        match self {
+            OutOfTurn => write!(f, "message out of turn"),
+            NotNegotiating => write!(f, "negotiation is not in progress"),
+            ChannelIdMismatch => write!(f, "channel_id mismatch"),
+            WrongParity(id) => write!(f, "serial_id {} has the wrong parity", id),
+            DuplicateSerialId(id) => write!(f, "serial_id {} already added", id),
+            UnknownSerialId(id) => write!(f, "serial_id {} not found", id),
+            DuplicateInput(o) => write!(f, "input {:?} already added", o),
+            InvalidPrevTx => write!(f, "invalid prevtx"),
+            PrevTxVoutOutOfRange(v) => write!(f, "prevtx_vout {} out of range", v),
+            NonSegwitInput(v) => write!(f, "prevtx output {} is not segwit", v),
+            InvalidSequence(s) => write!(f, "invalid sequence {:#x}", s),
+            NonStandardOutput => write!(f, "non-standard output script"),
+            DustOutput(s) => write!(f, "output of {} sats is dust", s),
+            ExceedsMaxMoney(s) => write!(f, "output of {} sats exceeds MAX_MONEY", s),
+            TooManyAddInputs => write!(f, "too many tx_add_input messages"),
+            TooManyAddOutputs => write!(f, "too many tx_add_output messages"),
+            TooManyInputsOrOutputs => write!(f, "too many inputs or outputs"),
+            ExceedsMaxWeight(w) => write!(f, "transaction weight {} too large", w),
+            MissingSharedInput => write!(f, "shared input missing"),
+            MissingSharedOutput => write!(f, "shared output missing"),
+            UnknownSharedInput => write!(f, "unknown shared input"),
+            InsufficientFunds { local } => write!(
+                f,
+                "{} inputs do not cover outputs",
+                if *local { "local" } else { "remote" }
+            ),
+            InsufficientFees { local } => write!(
+                f,
+                "{} inputs do not cover fees",
+                if *local { "local" } else { "remote" }
+            ),
+            Aborted(data) => write!(f, "aborted: {}", String::from_utf8_lossy(data)),
+            UnexpectedMessage(t) => write!(f, "unexpected message type {}", t),
+        }
+    }
+}
+impl core::error::Error for InteractiveTxError {}
+
+// The unsigned transaction both parties agreed on. Inputs and outputs are
+// ordered by serial_id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstructedTransaction {
+    pub is_initiator: bool,
+    pub locktime: u32,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+}
+impl ConstructedTransaction {
+    pub fn is_local(&self, serial_id: u64) -> bool {
+        is_initiator_serial_id(serial_id) == self.is_initiator
+    }
+    // The transaction serialized without witnesses.
+    pub fn encode_unsigned(&self) -> Vec<u8> {
+        let mut tx = Vec::new();
+        tx.extend_from_slice(&2_u32.to_le_bytes());
+        write_compact_size(self.inputs.len() as u64, &mut tx);
+        for input in self.inputs.iter() {
+            tx.extend_from_slice(&input.prevout.txid);
+            tx.extend_from_slice(&input.prevout.vout.to_le_bytes());
+            tx.push(0);
+            tx.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        write_compact_size(self.outputs.len() as u64, &mut tx);
+        for output in self.outputs.iter() {
+            tx.extend_from_slice(&output.output.value.to_le_bytes());
+            write_compact_size(output.output.script_pubkey.len() as u64, &mut tx);
+            tx.extend_from_slice(&output.output.script_pubkey);
+        }
+        tx.extend_from_slice(&self.locktime.to_le_bytes());
+        tx
+    }
+    pub fn txid(&self) -> [u8; 32] {
+        sha256d::Hash::hash(&self.encode_unsigned()).to_byte_array()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    OurTurn,
+    TheirTurn,
+    Complete,
+    Aborted,
+}
+
+// A sans-IO driver for the interactive transaction construction protocol.
+// Local contributions are turned into messages to send, and messages received
+// from the peer are validated before being applied. Once both sides have sent
+// `tx_complete` in a row the agreed transaction is returned.
+#[derive(Clone, Debug)]
+pub struct InteractiveTxConstructor {
+    params: InteractiveTxParams,
+    state: State,
+    inputs: BTreeMap<u64, TxInput>,
+    outputs: BTreeMap<u64, TxOutput>,
+    next_serial_id: u64,
+    received_add_inputs: usize,
+    received_add_outputs: usize,
+    sent_complete: bool,
+    received_complete: bool,
+}
+impl InteractiveTxConstructor {
+    pub fn new(params: InteractiveTxParams) -> Self {
+        InteractiveTxConstructor {
+            state: if params.is_initiator {
+                State::OurTurn
+            } else {
+                State::TheirTurn
+            },
+            next_serial_id: if params.is_initiator { 0 } else { 1 },
+            params,
+            inputs: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+            received_add_inputs: 0,
+            received_add_outputs: 0,
+            sent_complete: false,
+            received_complete: false,
+        }
+    }
+
+    pub fn params(&self) -> &InteractiveTxParams {
+        &self.params
+    }
+
+    pub fn is_our_turn(&self) -> bool {
+        self.state == State::OurTurn
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Complete
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &TxInput> {
+        self.inputs.values()
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &TxOutput> {
+        self.outputs.values()
+    }
+
+    pub fn add_input(
+        &mut self,
+        prevtx: Vec<u8>,
+        prevtx_vout: u32,
+        sequence: u32,
+    ) -> Result<TxAddInput, InteractiveTxError> {
+        self.start_send()?;
+        let serial_id = self.next_serial_id;
+        self.insert_input(serial_id, &prevtx, prevtx_vout, sequence)?;
+        self.finish_send(false);
+        Ok(TxAddInput {
+            channel_id: self.params.channel_id,
+            serial_id,
+            prevtx,
+            prevtx_vout,
+            sequence,
+            shared_input_txid: None,
+        })
+    }
+
+    // Adds the shared input from the params. Only its txid is sent, since
+    // both parties already know the output it spends.
+    pub fn add_shared_input(&mut self, sequence: u32) -> Result<TxAddInput, InteractiveTxError> {
+        self.start_send()?;
+        let prevout = self
+            .params
+            .shared_input
+            .as_ref()
+            .map(|i| i.prevout)
+            .ok_or(InteractiveTxError::MissingSharedInput)?;
+        let serial_id = self.next_serial_id;
+        self.insert_shared_input(serial_id, prevout, sequence)?;
+        self.finish_send(false);
+        Ok(TxAddInput {
+            channel_id: self.params.channel_id,
+            serial_id,
+            prevtx: Vec::new(),
+            prevtx_vout: prevout.vout,
+            sequence,
+            shared_input_txid: Some(prevout.txid),
+        })
+    }
+
+    pub fn add_output(
+        &mut self,
+        sats: u64,
+        script: Vec<u8>,
+    ) -> Result<TxAddOutput, InteractiveTxError> {
+        self.start_send()?;
+        let serial_id = self.next_serial_id;
+        self.insert_output(serial_id, sats, &script)?;
+        self.finish_send(false);
+        Ok(TxAddOutput {
+            channel_id: self.params.channel_id,
+            serial_id,
+            sats,
+            script,
+        })
+    }
+
+    pub fn remove_input(&mut self, serial_id: u64) -> Result<TxRemoveInput, InteractiveTxError> {
+        self.start_send()?;
+        self.check_parity(serial_id, true)?;
+        self.inputs
+            .remove(&serial_id)
+            .ok_or(InteractiveTxError::UnknownSerialId(serial_id))?;
+        self.finish_send(false);
+        Ok(TxRemoveInput {
+            channel_id: self.params.channel_id,
+            serial_id,
+        })
+    }
+
+    pub fn remove_output(&mut self, serial_id: u64) -> Result<TxRemoveOutput, InteractiveTxError> {
+        self.start_send()?;
+        self.check_parity(serial_id, true)?;
+        self.outputs
+            .remove(&serial_id)
+            .ok_or(InteractiveTxError::UnknownSerialId(serial_id))?;
+        self.finish_send(false);
+        Ok(TxRemoveOutput {
+            channel_id: self.params.channel_id,
+            serial_id,
+        })
+    }
+
+    // Sends `tx_complete`. If the peer's last message was also `tx_complete`
+    // the negotiation is over and the agreed transaction is returned with it.
+    pub fn complete(
+        &mut self,
+    ) -> Result<(TxComplete, Option<ConstructedTransaction>), InteractiveTxError> {
+        self.start_send()?;
+        let msg = TxComplete {
+            channel_id: self.params.channel_id,
+        };
+        if self.received_complete {
+            let tx = self.finish()?;
+            return Ok((msg, Some(tx)));
+        }
+        self.finish_send(true);
+        Ok((msg, None))
+    }
+
+    pub fn abort(&mut self, reason: &str) -> TxAbort {
+        self.state = State::Aborted;
+        TxAbort {
+            channel_id: self.params.channel_id,
+            data: reason.as_bytes().to_vec(),
+        }
+    }
+
+    // Applies a message received from the peer. Returns the agreed transaction
+    // once the peer's `tx_complete` follows our own.
+    pub fn receive(
+        &mut self,
+        msg: &AnyInteractiveTxMessage,
+    ) -> Result<Option<ConstructedTransaction>, InteractiveTxError> {
+        let res = self.receive_inner(msg);
+        if res.is_err() {
+            self.state = State::Aborted;
+        }
+        res
+    }
+
+    fn receive_inner(
+        &mut self,
+        msg: &AnyInteractiveTxMessage,
+    ) -> Result<Option<ConstructedTransaction>, InteractiveTxError> {
+        use AnyInteractiveTxMessage as M;
+        if let M::TxAbort(a) = msg {
+            return Err(InteractiveTxError::Aborted(a.data.clone()));
+        }
+        match self.state {
+            State::TheirTurn => (),
+            State::OurTurn => return Err(InteractiveTxError::OutOfTurn),
+            _ => return Err(InteractiveTxError::NotNegotiating),
+        }
+        let channel_id = match msg {
+            M::TxAddInput(m) => &m.channel_id,
+            M::TxAddOutput(m) => &m.channel_id,
+            M::TxRemoveInput(m) => &m.channel_id,
+            M::TxRemoveOutput(m) => &m.channel_id,
+            M::TxComplete(m) => &m.channel_id,
+            m => {
+                return Err(InteractiveTxError::UnexpectedMessage(
+                    crate::AnyWireMessage::msg_type(m),
+                ))
+            }
+        };
+        if channel_id != &self.params.channel_id {
+            return Err(InteractiveTxError::ChannelIdMismatch);
+        }
+        match msg {
+            M::TxAddInput(m) => {
+                self.received_add_inputs += 1;
+                if self.received_add_inputs > MAX_RECEIVED_TX_ADD_INPUT_COUNT {
+                    return Err(InteractiveTxError::TooManyAddInputs);
+                }
+                self.check_parity(m.serial_id, false)?;
+                match m.shared_input_txid {
+                    Some(txid) if m.prevtx.is_empty() => self.insert_shared_input(
+                        m.serial_id,
+                        OutPoint {
+                            txid,
+                            vout: m.prevtx_vout,
+                        },
+                        m.sequence,
+                    )?,
+                    Some(_) => return Err(InteractiveTxError::InvalidPrevTx),
+                    None => self.insert_input(m.serial_id, &m.prevtx, m.prevtx_vout, m.sequence)?,
+                }
+            }
+            M::TxAddOutput(m) => {
+                self.received_add_outputs += 1;
+                if self.received_add_outputs > MAX_RECEIVED_TX_ADD_OUTPUT_COUNT {
+                    return Err(InteractiveTxError::TooManyAddOutputs);
+                }
+                self.check_parity(m.serial_id, false)?;
+                self.insert_output(m.serial_id, m.sats, &m.script)?;
+            }
+            M::TxRemoveInput(m) => {
+                self.check_parity(m.serial_id, false)?;
+                self.inputs
+                    .remove(&m.serial_id)
+                    .ok_or(InteractiveTxError::UnknownSerialId(m.serial_id))?;
+            }
+            M::TxRemoveOutput(m) => {
+                self.check_parity(m.serial_id, false)?;
+                self.outputs
+                    .remove(&m.serial_id)
+                    .ok_or(InteractiveTxError::UnknownSerialId(m.serial_id))?;
+            }
+            _ => {
+                if self.sent_complete {
+                    return self.finish().map(Some);
+                }
+                self.received_complete = true;
+                self.state = State::OurTurn;
+                return Ok(None);
+            }
+        }
+        self.received_complete = false;
+        self.sent_complete = false;
+        self.state = State::OurTurn;
+        Ok(None)
+    }
+
+    fn start_send(&self) -> Result<(), InteractiveTxError> {
+        match self.state {
+            State::OurTurn => Ok(()),
+            State::TheirTurn => Err(InteractiveTxError::OutOfTurn),
+            _ => Err(InteractiveTxError::NotNegotiating),
+        }
+    }
+
+    fn finish_send(&mut self, complete: bool) {
+        if !complete {
+            self.next_serial_id += 2;
+            self.received_complete = false;
+        }
+        self.sent_complete = complete;
+        self.state = State::TheirTurn;
+    }
+
+    fn check_parity(&self, serial_id: u64, local: bool) -> Result<(), InteractiveTxError> {
+        if (is_initiator_serial_id(serial_id) == self.params.is_initiator) == local {
+            Ok(())
+        } else {
+            Err(InteractiveTxError::WrongParity(serial_id))
+        }
+    }
+
+    fn insert_input(
+        &mut self,
+        serial_id: u64,
+        prevtx: &[u8],
+        prevtx_vout: u32,
+        sequence: u32,
+    ) -> Result<(), InteractiveTxError> {
+        if self.inputs.contains_key(&serial_id) {
+            return Err(InteractiveTxError::DuplicateSerialId(serial_id));
+        }
+        if sequence >= 0xfffffffe {
+            return Err(InteractiveTxError::InvalidSequence(sequence));
+        }
+        let (txid, mut outputs) = parse_tx(prevtx).ok_or(InteractiveTxError::InvalidPrevTx)?;
+        if prevtx_vout as usize >= outputs.len() {
+            return Err(InteractiveTxError::PrevTxVoutOutOfRange(prevtx_vout));
+        }
+        let spent = outputs.swap_remove(prevtx_vout as usize);
+        if !is_witness_program(&spent.script_pubkey) {
+            return Err(InteractiveTxError::NonSegwitInput(prevtx_vout));
+        }
+        let prevout = OutPoint {
+            txid,
+            vout: prevtx_vout,
+        };
+        if self.inputs.values().any(|i| i.prevout == prevout) {
+            return Err(InteractiveTxError::DuplicateInput(prevout));
+        }
+        self.inputs.insert(
+            serial_id,
+            TxInput {
+                serial_id,
+                prevout,
+                sequence,
+                spent,
+            },
+        );
+        Ok(())
+    }
+
+    fn insert_shared_input(
+        &mut self,
+        serial_id: u64,
+        prevout: OutPoint,
+        sequence: u32,
+    ) -> Result<(), InteractiveTxError> {
+        let shared = match &self.params.shared_input {
+            Some(shared) if shared.prevout == prevout => shared,
+            _ => return Err(InteractiveTxError::UnknownSharedInput),
+        };
+        if self.inputs.contains_key(&serial_id) {
+            return Err(InteractiveTxError::DuplicateSerialId(serial_id));
+        }
+        if sequence >= 0xfffffffe {
+            return Err(InteractiveTxError::InvalidSequence(sequence));
+        }
+        if self.inputs.values().any(|i| i.prevout == prevout) {
+            return Err(InteractiveTxError::DuplicateInput(prevout));
+        }
+        let spent = TxOut {
+            value: shared.local_satoshis + shared.remote_satoshis,
+            script_pubkey: shared.script_pubkey.clone(),
+        };
+        self.inputs.insert(
+            serial_id,
+            TxInput {
+                serial_id,
+                prevout,
+                sequence,
+                spent,
+            },
+        );
+        Ok(())
+    }
+
+    fn insert_output(
+        &mut self,
+        serial_id: u64,
+        sats: u64,
+        script: &[u8],
+    ) -> Result<(), InteractiveTxError> {
+        if self.outputs.contains_key(&serial_id) {
+            return Err(InteractiveTxError::DuplicateSerialId(serial_id));
+        }
+        if sats < self.params.dust_limit_satoshis {
+            return Err(InteractiveTxError::DustOutput(sats));
+        }
+        if sats > MAX_MONEY {
+            return Err(InteractiveTxError::ExceedsMaxMoney(sats));
+        }
+        if !is_witness_program(script) {
+            return Err(InteractiveTxError::NonStandardOutput);
+        }
+        self.outputs.insert(
+            serial_id,
+            TxOutput {
+                serial_id,
+                output: TxOut {
+                    value: sats,
+                    script_pubkey: script.to_vec(),
+                },
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<ConstructedTransaction, InteractiveTxError> {
+        let res = self.validate();
+        self.state = if res.is_ok() {
+            State::Complete
+        } else {
+            State::Aborted
+        };
+        res
+    }
+
+    fn validate(&self) -> Result<ConstructedTransaction, InteractiveTxError> {
+        if self.inputs.len() > MAX_INPUTS_OUTPUTS_COUNT
+            || self.outputs.len() > MAX_INPUTS_OUTPUTS_COUNT
+        {
+            return Err(InteractiveTxError::TooManyInputsOrOutputs);
+        }
+        let shared_output = match &self.params.shared_output {
+            Some(shared) => Some(
+                self.outputs
+                    .values()
+                    .find(|o| {
+                        o.output.script_pubkey == shared.script_pubkey
+                            && o.output.value == shared.local_satoshis + shared.remote_satoshis
+                    })
+                    .map(|o| o.serial_id)
+                    .ok_or(InteractiveTxError::MissingSharedOutput)?,
+            ),
+            None => None,
+        };
+        let shared_input = match &self.params.shared_input {
+            Some(shared) => Some(
+                self.inputs
+                    .values()
+                    .find(|i| i.prevout == shared.prevout)
+                    .map(|i| i.serial_id)
+                    .ok_or(InteractiveTxError::MissingSharedInput)?,
+            ),
+            None => None,
+        };
+        let mut total_weight = 0;
+        for local in [true, false].iter().cloned() {
+            let is_initiator = local == self.params.is_initiator;
+            let is_ours = |serial_id: u64| is_initiator_serial_id(serial_id) == is_initiator;
+            let mut weight = if is_initiator {
+                COMMON_FIELDS_WEIGHT
+            } else {
+                0
+            };
+            let mut input_sats = 0_u64;
+            let mut output_sats = 0_u64;
+            for input in self.inputs.values() {
+                if Some(input.serial_id) == shared_input {
+                    if is_initiator {
+                        weight += self
+                            .params
+                            .shared_input
+                            .as_ref()
+                            .unwrap()
+                            .estimated_weight();
+                    }
+                } else if is_ours(input.serial_id) {
+                    input_sats = input_sats.saturating_add(input.spent.value);
+                    weight += input.estimated_weight();
+                }
+            }
+            if let Some(shared) = &self.params.shared_input {
+                input_sats = input_sats.saturating_add(if local {
+                    shared.local_satoshis
+                } else {
+                    shared.remote_satoshis
+                });
+            }
+            for output in self.outputs.values() {
+                if Some(output.serial_id) == shared_output {
+                    if is_initiator {
+                        weight += output.output.weight();
+                    }
+                } else if is_ours(output.serial_id) {
+                    output_sats = output_sats.saturating_add(output.output.value);
+                    weight += output.output.weight();
+                }
+            }
+            if let Some(shared) = &self.params.shared_output {
+                output_sats = output_sats.saturating_add(if local {
+                    shared.local_satoshis
+                } else {
+                    shared.remote_satoshis
+                });
+            }
+            if input_sats < output_sats {
+                return Err(InteractiveTxError::InsufficientFunds { local });
+            }
+            let fee = self.params.feerate_per_kw as u64 * weight / 1000;
+            if input_sats - output_sats < fee {
+                return Err(InteractiveTxError::InsufficientFees { local });
+            }
+            total_weight += weight;
+        }
+        if total_weight > MAX_STANDARD_TX_WEIGHT {
+            return Err(InteractiveTxError::ExceedsMaxWeight(total_weight));
+        }
+        Ok(ConstructedTransaction {
+            is_initiator: self.params.is_initiator,
+            locktime: self.params.locktime,
+            inputs: self.inputs.values().cloned().collect(),
+            outputs: self.outputs.values().cloned().collect(),
+        })
+    }
+}
+
+// Serial ids chosen by the initiator are even, those chosen by the
+// non-initiator are odd.
+pub fn is_initiator_serial_id(serial_id: u64) -> bool {
+    serial_id & 1 == 0
+}
+
+// A witness version opcode (OP_0 or OP_1 through OP_16) followed by a single
+// push of 2 to 40 bytes.
+pub fn is_witness_program(script: &[u8]) -> bool {
+    match script {
+        [version, len, program @ ..] => {
+            (*version == 0x00 || (0x51..=0x60).contains(version))
+                && (2..=40).contains(len)
+                && program.len() == *len as usize
+        }
+        _ => false,
+    }
+}
+
+fn compact_size_len(n: u64) -> u64 {
+    match n {
+        n if n < 0xfd => 1,
+        n if n <= 0xffff => 3,
+        n if n <= 0xffffffff => 5,
+        _ => 9,
+    }
+}
+
+fn write_compact_size(n: u64, buf: &mut Vec<u8>) {
+    match n {
+        n if n < 0xfd => buf.push(n as u8),
+        n if n <= 0xffff => {
+            buf.push(0xfd);
+            buf.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        n if n <= 0xffffffff => {
+            buf.push(0xfe);
+            buf.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        n => {
+            buf.push(0xff);
+            buf.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+struct TxReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+impl<'a> TxReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let res = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(res)
+    }
+    fn u32(&mut self) -> Option<u32> {
+        let mut b = [0_u8; 4];
+        b.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(b))
+    }
+    fn u64(&mut self) -> Option<u64> {
+        let mut b = [0_u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(b))
+    }
+    fn compact_size(&mut self) -> Option<u64> {
+        Some(match self.take(1)?[0] {
+            0xfd => {
+                let mut b = [0_u8; 2];
+                b.copy_from_slice(self.take(2)?);
+                u16::from_le_bytes(b) as u64
+            }
+            0xfe => self.u32()? as u64,
+            0xff => self.u64()?,
+            n => n as u64,
+        })
+    }
+    fn var_bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.compact_size()?;
+        self.take(usize::try_from(len).ok()?)
+    }
+}
+
+// Parses a serialized bitcoin transaction, returning its txid and outputs.
+fn parse_tx(tx: &[u8]) -> Option<([u8; 32], Vec<TxOut>)> {
+    let mut r = TxReader { buf: tx, pos: 0 };
+    r.take(4)?;
+    let segwit = tx.get(4..6) == Some(&[0x00, 0x01]);
+    if segwit {
+        r.take(2)?;
+    }
+    let body_start = r.pos;
+    let n_inputs = r.compact_size()?;
+    for _ in 0..n_inputs {
+        r.take(36)?;
+        r.var_bytes()?;
+        r.take(4)?;
+    }
+    let n_outputs = r.compact_size()?;
+    let mut outputs = Vec::new();
+    for _ in 0..n_outputs {
+        let value = r.u64()?;
+        let script_pubkey = r.var_bytes()?.to_vec();
+        outputs.push(TxOut {
+            value,
+            script_pubkey,
+        });
+    }
+    let body_end = r.pos;
+    if segwit {
+        for _ in 0..n_inputs {
+            let n_items = r.compact_size()?;
+            for _ in 0..n_items {
+                r.var_bytes()?;
+            }
+        }
+    }
+    r.take(4)?;
+    if r.pos != tx.len() {
+        return None;
+    }
+    let mut engine = sha256d::Hash::engine();
+    engine.input(&tx[..4]);
+    engine.input(&tx[body_start..body_end]);
+    engine.input(&tx[tx.len() - 4..]);
+    Some((sha256d::Hash::from_engine(engine).to_byte_array(), outputs))
+}
+
+#[test]
+fn construction() {
+    use AnyInteractiveTxMessage as M;
+
+    let channel_id = ChannelId([7; 32]);
+    let params = |is_initiator| InteractiveTxParams {
+        channel_id,
+        is_initiator,
+        locktime: 100,
+        feerate_per_kw: 253,
+        dust_limit_satoshis: 330,
+        shared_input: None,
+        shared_output: None,
+    };
+    let p2wpkh = |b| {
+        let mut script = alloc::vec![0x00, 0x14];
+        script.extend_from_slice(&[b; 20]);
+        script
+    };
+    // A transaction with a single output, made unique by the input it spends.
+    let prevtx = |tag: u16, value: u64, script: &[u8]| {
+        let mut tx = 2_u32.to_le_bytes().to_vec();
+        tx.push(1);
+        tx.extend_from_slice(&[0; 30]);
+        tx.extend_from_slice(&tag.to_le_bytes());
+        tx.extend_from_slice(&[0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        tx.push(1);
+        tx.extend_from_slice(&value.to_le_bytes());
+        tx.push(script.len() as u8);
+        tx.extend_from_slice(script);
+        tx.extend_from_slice(&[0; 4]);
+        tx
+    };
+    let add_input = |serial_id, prevtx: Vec<u8>, sequence| {
+        M::TxAddInput(TxAddInput {
+            channel_id,
+            serial_id,
+            prevtx,
+            prevtx_vout: 0,
+            sequence,
+            shared_input_txid: None,
+        })
+    };
+    let add_output = |serial_id, sats| {
+        M::TxAddOutput(TxAddOutput {
+            channel_id,
+            serial_id,
+            sats,
+            script: p2wpkh(9),
+        })
+    };
+    let complete = M::TxComplete(TxComplete { channel_id });
+
+    // The initiator adds an input and an output and the peer an output and
+    // two inputs, the later one with a lower serial_id.
+    let mut tx = InteractiveTxConstructor::new(params(true));
+    let msg = tx
+        .add_input(prevtx(1, 100_000, &p2wpkh(1)), 0, 0xfffffffd)
+        .unwrap();
+    assert_eq!(msg.serial_id, 0);
+    assert_eq!(
+        tx.add_output(90_000, p2wpkh(2)).err(),
+        Some(InteractiveTxError::OutOfTurn)
+    );
+    tx.receive(&add_input(7, prevtx(2, 60_000, &p2wpkh(3)), 0))
+        .unwrap();
+    assert_eq!(tx.add_output(90_000, p2wpkh(2)).unwrap().serial_id, 2);
+    tx.receive(&add_output(5, 50_000)).unwrap();
+    assert_eq!(tx.complete().unwrap().1, None);
+    // Anything but `tx_complete` in reply starts over.
+    tx.receive(&add_input(3, prevtx(3, 60_000, &p2wpkh(3)), 0))
+        .unwrap();
+    assert_eq!(tx.complete().unwrap().1, None);
+    let agreed = tx.receive(&complete).unwrap().unwrap();
+    assert!(tx.is_complete());
+    assert_eq!(
+        tx.add_output(1000, p2wpkh(2)).err(),
+        Some(InteractiveTxError::NotNegotiating)
+    );
+    let inputs: Vec<_> = agreed.inputs.iter().map(|i| i.serial_id).collect();
+    assert_eq!(inputs, [0, 3, 7]);
+    let outputs: Vec<_> = agreed.outputs.iter().map(|o| o.serial_id).collect();
+    assert_eq!(outputs, [2, 5]);
+    let unsigned = agreed.encode_unsigned();
+    assert_eq!(unsigned[5..37], agreed.inputs[0].prevout.txid);
+    assert_eq!(unsigned[4], 3);
+    assert_eq!(agreed.locktime, 100);
+
+    // The non-initiator completes on receiving `tx_complete` after its own.
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    tx.receive(&add_input(0, prevtx(1, 100_000, &p2wpkh(1)), 0))
+        .unwrap();
+    assert_eq!(tx.receive(&complete), Err(InteractiveTxError::OutOfTurn));
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    tx.receive(&add_input(0, prevtx(1, 100_000, &p2wpkh(1)), 0))
+        .unwrap();
+    assert_eq!(tx.complete().unwrap().1, None);
+    let agreed = tx.receive(&complete).unwrap().unwrap();
+    assert_eq!(agreed.inputs.len(), 1);
+    assert!(!agreed.is_local(0));
+
+    // Each party uses the serial_ids of its own parity.
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    assert_eq!(
+        tx.receive(&add_input(1, prevtx(1, 100_000, &p2wpkh(1)), 0)),
+        Err(InteractiveTxError::WrongParity(1))
+    );
+    assert_eq!(
+        tx.receive(&complete),
+        Err(InteractiveTxError::NotNegotiating)
+    );
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    tx.receive(&add_output(4, 50_000)).unwrap();
+    assert_eq!(
+        tx.remove_output(4).err(),
+        Some(InteractiveTxError::WrongParity(4))
+    );
+    assert_eq!(
+        tx.remove_output(1).err(),
+        Some(InteractiveTxError::UnknownSerialId(1))
+    );
+    let mut tx = InteractiveTxConstructor::new(params(true));
+    tx.add_output(50_000, p2wpkh(2)).unwrap();
+    assert_eq!(
+        tx.receive(&add_output(2, 50_000)),
+        Err(InteractiveTxError::WrongParity(2))
+    );
+
+    // Inputs and serial_ids are only added once.
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    tx.receive(&add_input(0, prevtx(1, 100_000, &p2wpkh(1)), 0))
+        .unwrap();
+    tx.complete().unwrap();
+    assert_eq!(
+        tx.receive(&add_input(0, prevtx(2, 100_000, &p2wpkh(1)), 0)),
+        Err(InteractiveTxError::DuplicateSerialId(0))
+    );
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    tx.receive(&add_input(0, prevtx(1, 100_000, &p2wpkh(1)), 0))
+        .unwrap();
+    let prevout = tx.inputs().next().unwrap().prevout;
+    tx.complete().unwrap();
+    assert_eq!(
+        tx.receive(&add_input(2, prevtx(1, 100_000, &p2wpkh(1)), 0)),
+        Err(InteractiveTxError::DuplicateInput(prevout))
+    );
+
+    // Inputs spend segwit outputs and signal replaceability.
+    let p2pkh = [&[0x76, 0xa9, 0x14][..], &[1; 20], &[0x88, 0xac]].concat();
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    assert_eq!(
+        tx.receive(&add_input(0, prevtx(1, 100_000, &p2pkh), 0)),
+        Err(InteractiveTxError::NonSegwitInput(0))
+    );
+    let mut tx = InteractiveTxConstructor::new(params(false));
+    assert_eq!(
+        tx.receive(&add_input(0, prevtx(1, 100_000, &p2wpkh(1)), 0xfffffffe)),
+        Err(InteractiveTxError::InvalidSequence(0xfffffffe))
+    );
+    let mut tx = InteractiveTxConstructor::new(params(true));
+    assert_eq!(
+        tx.add_input(prevtx(1, 100_000, &p2wpkh(1)), 0, 0xffffffff)
+            .err(),
+        Some(InteractiveTxError::InvalidSequence(0xffffffff))
+    );
+}
+
+#[test]
+fn construction_limits() {
+    use AnyInteractiveTxMessage as M;
+
+    let channel_id = ChannelId([7; 32]);
+    let params = InteractiveTxParams {
+        channel_id,
+        is_initiator: false,
+        locktime: 0,
+        feerate_per_kw: 253,
+        dust_limit_satoshis: 330,
+        shared_input: None,
+        shared_output: None,
+    };
+    let prevtx = |tag: u16| {
+        let mut tx = 2_u32.to_le_bytes().to_vec();
+        tx.push(1);
+        tx.extend_from_slice(&[0; 30]);
+        tx.extend_from_slice(&tag.to_le_bytes());
+        tx.extend_from_slice(&[0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        tx.push(1);
+        tx.extend_from_slice(&100_000_u64.to_le_bytes());
+        tx.extend_from_slice(&[22, 0x00, 0x14]);
+        tx.extend_from_slice(&[2; 20]);
+        tx.extend_from_slice(&[0; 4]);
+        tx
+    };
+    let add_input = |serial_id, tag| {
+        M::TxAddInput(TxAddInput {
+            channel_id,
+            serial_id,
+            prevtx: prevtx(tag),
+            prevtx_vout: 0,
+            sequence: 0,
+            shared_input_txid: None,
+        })
+    };
+    let add_output = |serial_id| {
+        M::TxAddOutput(TxAddOutput {
+            channel_id,
+            serial_id,
+            sats: 1000,
+            script: [&[0x00, 0x14][..], &[3; 20]].concat(),
+        })
+    };
+
+    // At most 4096 `tx_add_input`s and `tx_add_output`s are received, even
+    // if they are removed again.
+    let mut tx = InteractiveTxConstructor::new(params.clone());
+    for serial_id in (0..2 * MAX_RECEIVED_TX_ADD_INPUT_COUNT as u64).step_by(2) {
+        tx.receive(&add_input(serial_id, 0)).unwrap();
+        tx.complete().unwrap();
+        tx.receive(&M::TxRemoveInput(TxRemoveInput {
+            channel_id,
+            serial_id,
+        }))
+        .unwrap();
+        tx.complete().unwrap();
+    }
+    assert_eq!(
+        tx.receive(&add_input(0, 0)),
+        Err(InteractiveTxError::TooManyAddInputs)
+    );
+    let mut tx = InteractiveTxConstructor::new(params.clone());
+    for serial_id in (0..2 * MAX_RECEIVED_TX_ADD_OUTPUT_COUNT as u64).step_by(2) {
+        tx.receive(&add_output(serial_id)).unwrap();
+        tx.complete().unwrap();
+        tx.receive(&M::TxRemoveOutput(TxRemoveOutput {
+            channel_id,
+            serial_id,
+        }))
+        .unwrap();
+        tx.complete().unwrap();
+    }
+    assert_eq!(
+        tx.receive(&add_output(0)),
+        Err(InteractiveTxError::TooManyAddOutputs)
+    );
+
+    // The transaction has at most 252 inputs and 252 outputs.
+    let mut tx = InteractiveTxConstructor::new(params.clone());
+    for i in 0..=MAX_INPUTS_OUTPUTS_COUNT as u16 {
+        tx.receive(&add_input(2 * i as u64, i)).unwrap();
+        tx.complete().unwrap();
+    }
+    assert_eq!(
+        tx.receive(&M::TxComplete(TxComplete { channel_id })),
+        Err(InteractiveTxError::TooManyInputsOrOutputs)
+    );
+    let mut tx = InteractiveTxConstructor::new(params);
+    for i in 0..=MAX_INPUTS_OUTPUTS_COUNT as u64 {
+        tx.receive(&add_output(2 * i)).unwrap();
+        tx.complete().unwrap();
+    }
+    assert_eq!(
+        tx.receive(&M::TxComplete(TxComplete { channel_id })),
+        Err(InteractiveTxError::TooManyInputsOrOutputs)
+    );
+}
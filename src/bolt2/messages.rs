@@ -0,0 +1,288 @@
+use crate::items::{
+    channel_id::ChannelId,
+    channel_type::ChannelType,
+    expiry::CltvExpiry,
+    fees::MilliSatoshi,
+    hash::Hash,
+    key::{RawPublicKey, RawSignature},
+    prefixed::LenPrefixed,
+    TLVBuffer,
+};
+use crate::wire_message::wire_message;
+use alloc::vec::Vec;
+
+wire_message! {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub enum AnyInteractiveTxMessage {
+        TxAddInput(TxAddInput),
+        TxAddOutput(TxAddOutput),
+        TxRemoveInput(TxRemoveInput),
+        TxRemoveOutput(TxRemoveOutput),
+        TxComplete(TxComplete),
+        TxSignatures(TxSignatures),
+        TxInitRbf(TxInitRbf),
+        TxAckRbf(TxAckRbf),
+        TxAbort(TxAbort),
+    }
+}
+
+wire_message! {
+    #[msg_type = 2]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Stfu {
+        pub channel_id: ChannelId,
+        pub initiator: u8,
+    }
+}
+
+wire_message! {
+    #[msg_type = 64]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct OpenChannel2 {
+        pub chain_hash: Hash,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub temporary_channel_id: [u8; 32],
+        pub funding_feerate_perkw: u32,
+        pub commitment_feerate_perkw: u32,
+        pub funding_satoshis: u64,
+        pub dust_limit_satoshis: u64,
+        pub max_htlc_value_in_flight_msat: u64,
+        pub htlc_minimum_msat: u64,
+        pub to_self_delay: u16,
+        pub max_accepted_htlcs: u16,
+        pub locktime: u32,
+        pub funding_pubkey: RawPublicKey,
+        pub revocation_basepoint: RawPublicKey,
+        pub payment_basepoint: RawPublicKey,
+        pub delayed_payment_basepoint: RawPublicKey,
+        pub htlc_basepoint: RawPublicKey,
+        pub first_per_commitment_point: RawPublicKey,
+        pub second_per_commitment_point: RawPublicKey,
+        pub channel_flags: u8,
+        #[tlv_type = 0]
+        pub upfront_shutdown_script: Option<TLVBuffer<Vec<u8>>>,
+        #[tlv_type = 1]
+        pub channel_type: Option<ChannelType>,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 65]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AcceptChannel2 {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub temporary_channel_id: [u8; 32],
+        pub funding_satoshis: u64,
+        pub dust_limit_satoshis: u64,
+        pub max_htlc_value_in_flight_msat: u64,
+        pub htlc_minimum_msat: u64,
+        pub minimum_depth: u32,
+        pub to_self_delay: u16,
+        pub max_accepted_htlcs: u16,
+        pub funding_pubkey: RawPublicKey,
+        pub revocation_basepoint: RawPublicKey,
+        pub payment_basepoint: RawPublicKey,
+        pub delayed_payment_basepoint: RawPublicKey,
+        pub htlc_basepoint: RawPublicKey,
+        pub first_per_commitment_point: RawPublicKey,
+        pub second_per_commitment_point: RawPublicKey,
+        #[tlv_type = 0]
+        pub upfront_shutdown_script: Option<TLVBuffer<Vec<u8>>>,
+        #[tlv_type = 1]
+        pub channel_type: Option<ChannelType>,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 66]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxAddInput {
+        pub channel_id: ChannelId,
+        pub serial_id: u64,
+        #[len_prefix = u16]
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub prevtx: Vec<u8>,
+        pub prevtx_vout: u32,
+        pub sequence: u32,
+        #[tlv_type = 0]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+        pub shared_input_txid: Option<[u8; 32]>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 67]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxAddOutput {
+        pub channel_id: ChannelId,
+        pub serial_id: u64,
+        pub sats: u64,
+        #[len_prefix = u16]
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub script: Vec<u8>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 68]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxRemoveInput {
+        pub channel_id: ChannelId,
+        pub serial_id: u64,
+    }
+}
+
+wire_message! {
+    #[msg_type = 69]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxRemoveOutput {
+        pub channel_id: ChannelId,
+        pub serial_id: u64,
+    }
+}
+
+wire_message! {
+    #[msg_type = 70]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxComplete {
+        pub channel_id: ChannelId,
+    }
+}
+
+wire_message! {
+    #[msg_type = 71]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxSignatures {
+        pub channel_id: ChannelId,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub txid: [u8; 32],
+        // Each witness is a bitcoin-serialized witness stack for one of the
+        // sender's inputs, in serial_id order.
+        #[count_prefix = u16]
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub witnesses: Vec<LenPrefixed<u16, Vec<u8>>>,
+        // The sender's signature for the shared input when splicing.
+        #[tlv_type = 0]
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub shared_input_signature: Option<RawSignature>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 72]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxInitRbf {
+        pub channel_id: ChannelId,
+        pub locktime: u32,
+        pub feerate: u32,
+        #[tlv_type = 0]
+        pub funding_output_contribution: Option<i64>,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 73]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxAckRbf {
+        pub channel_id: ChannelId,
+        #[tlv_type = 0]
+        pub funding_output_contribution: Option<i64>,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 74]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TxAbort {
+        pub channel_id: ChannelId,
+        #[len_prefix = u16]
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub data: Vec<u8>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 77]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct SpliceLocked {
+        pub channel_id: ChannelId,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub splice_txid: [u8; 32],
+    }
+}
+
+wire_message! {
+    #[msg_type = 80]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct SpliceInit {
+        pub channel_id: ChannelId,
+        pub funding_contribution_satoshis: i64,
+        pub funding_feerate_perkw: u32,
+        pub locktime: u32,
+        pub funding_pubkey: RawPublicKey,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 81]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct SpliceAck {
+        pub channel_id: ChannelId,
+        pub funding_contribution_satoshis: i64,
+        pub funding_pubkey: RawPublicKey,
+        #[tlv_type = 2]
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_flag"))]
+        pub require_confirmed_inputs: Option<()>,
+    }
+}
+
+wire_message! {
+    #[msg_type = 128]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct UpdateAddHtlc {
+        pub channel_id: ChannelId,
+        pub id: u64,
+        pub amount_msat: MilliSatoshi,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub payment_hash: [u8; 32],
+        pub cltv_expiry: CltvExpiry,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub onion_routing_packet: [u8; 1366],
+        // The path key of the blinded path the htlc is in, for hops after the
+        // introduction node.
+        #[tlv_type = 0]
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub blinding_point: Option<RawPublicKey>,
+    }
+}
@@ -0,0 +1,4 @@
+pub mod interactive_tx;
+pub mod messages;
+pub mod quiescence;
+pub mod splice;
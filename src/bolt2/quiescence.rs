@@ -0,0 +1,198 @@
+use super::messages::Stfu;
+use crate::items::channel_id::ChannelId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuiescenceError {
+    // We asked for quiescence while already quiescent or waiting for it.
+    AlreadyRequested,
+    ChannelIdMismatch,
+    // The peer sent `stfu` twice.
+    DuplicateStfu,
+    // The peer sent an update message after its `stfu`.
+    UpdateWhileQuiescent,
+}
+impl core::fmt::Display for QuiescenceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            QuiescenceError::AlreadyRequested => write!(f, "quiescence already requested"),
+            QuiescenceError::ChannelIdMismatch => write!(f, "channel_id mismatch"),
+            QuiescenceError::DuplicateStfu => write!(f, "duplicate stfu"),
+            QuiescenceError::UpdateWhileQuiescent => write!(f, "update received after stfu"),
+        }
+    }
+}
+impl core::error::Error for QuiescenceError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Side {
+    // Whether this side wants to send, or has sent, `stfu`.
+    wants: bool,
+    sent: bool,
+    initiator: bool,
+}
+const IDLE: Side = Side {
+    wants: false,
+    sent: false,
+    initiator: false,
+};
+
+// A sans-IO state machine for the quiescence protocol. The caller reports
+// whether it has updates in flight (HTLC additions or removals, fee updates
+// not yet irrevocably committed by both sides) and is told when to send
+// `stfu`, whether it may still send updates, and once the channel is
+// quiescent, which side is the initiator of the downstream protocol.
+#[derive(Clone, Debug)]
+pub struct Quiescence {
+    channel_id: ChannelId,
+    is_funder: bool,
+    local: Side,
+    remote: Side,
+}
+impl Quiescence {
+    pub fn new(channel_id: ChannelId, is_funder: bool) -> Self {
+        Quiescence {
+            channel_id,
+            is_funder,
+            local: IDLE,
+            remote: IDLE,
+        }
+    }
+
+    // Requests quiescence. Returns `stfu` if it can be sent right away,
+    // otherwise `poll` should be called once pending updates are resolved.
+    pub fn initiate(&mut self, has_pending_updates: bool) -> Result<Option<Stfu>, QuiescenceError> {
+        if self.local.wants {
+            return Err(QuiescenceError::AlreadyRequested);
+        }
+        self.local.wants = true;
+        self.local.initiator = !self.remote.sent;
+        Ok(self.poll(has_pending_updates))
+    }
+
+    // Returns `stfu` if we want to send it and nothing is pending anymore.
+    pub fn poll(&mut self, has_pending_updates: bool) -> Option<Stfu> {
+        if !self.local.wants || self.local.sent || has_pending_updates {
+            return None;
+        }
+        self.local.sent = true;
+        Some(Stfu {
+            channel_id: self.channel_id,
+            initiator: self.local.initiator as u8,
+        })
+    }
+
+    // Handles the peer's `stfu`. We must answer with our own, which is
+    // returned once nothing is pending.
+    pub fn receive_stfu(
+        &mut self,
+        msg: &Stfu,
+        has_pending_updates: bool,
+    ) -> Result<Option<Stfu>, QuiescenceError> {
+        if msg.channel_id != self.channel_id {
+            return Err(QuiescenceError::ChannelIdMismatch);
+        }
+        if self.remote.sent {
+            return Err(QuiescenceError::DuplicateStfu);
+        }
+        self.remote = Side {
+            wants: true,
+            sent: true,
+            initiator: msg.initiator != 0,
+        };
+        self.local.wants = true;
+        Ok(self.poll(has_pending_updates))
+    }
+
+    // Once we have sent `stfu` we must not send any more updates.
+    pub fn can_send_update(&self) -> bool {
+        !self.local.sent
+    }
+
+    // Checks an update message received from the peer.
+    pub fn check_received_update(&self) -> Result<(), QuiescenceError> {
+        if self.remote.sent {
+            Err(QuiescenceError::UpdateWhileQuiescent)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn is_quiescent(&self) -> bool {
+        self.local.sent && self.remote.sent
+    }
+
+    // Whether we are the quiescence initiator, once quiescent. If both sides
+    // claimed to be, the channel funder wins.
+    pub fn is_initiator(&self) -> Option<bool> {
+        if !self.is_quiescent() {
+            return None;
+        }
+        Some(match (self.local.initiator, self.remote.initiator) {
+            (true, true) => self.is_funder,
+            (local, _) => local,
+        })
+    }
+
+    // Leaves quiescence once the downstream protocol has finished, or on
+    // reconnection.
+    pub fn exit(&mut self) {
+        self.local = IDLE;
+        self.remote = IDLE;
+    }
+}
+
+#[test]
+fn quiescence() {
+    let channel_id = ChannelId([3; 32]);
+
+    // `stfu` waits for pending updates, and no updates follow it.
+    let mut funder = Quiescence::new(channel_id, true);
+    let mut fundee = Quiescence::new(channel_id, false);
+    assert!(funder.initiate(true).unwrap().is_none());
+    assert_eq!(
+        funder.initiate(false).err(),
+        Some(QuiescenceError::AlreadyRequested)
+    );
+    assert!(funder.can_send_update());
+    let stfu = funder.poll(false).unwrap();
+    assert_eq!(stfu.initiator, 1);
+    assert!(!funder.can_send_update());
+    assert!(fundee.receive_stfu(&stfu, true).unwrap().is_none());
+    assert_eq!(
+        fundee.check_received_update(),
+        Err(QuiescenceError::UpdateWhileQuiescent)
+    );
+    assert_eq!(
+        fundee.receive_stfu(&stfu, false).err(),
+        Some(QuiescenceError::DuplicateStfu)
+    );
+    assert_eq!(fundee.is_initiator(), None);
+    let reply = fundee.poll(false).unwrap();
+    assert_eq!(reply.initiator, 0);
+    assert_eq!(funder.check_received_update(), Ok(()));
+    assert!(funder.receive_stfu(&reply, false).unwrap().is_none());
+    assert!(funder.is_quiescent() && fundee.is_quiescent());
+    assert_eq!(funder.is_initiator(), Some(true));
+    assert_eq!(fundee.is_initiator(), Some(false));
+
+    funder.exit();
+    fundee.exit();
+    assert!(!funder.is_quiescent());
+    assert!(funder.can_send_update());
+    assert_eq!(fundee.check_received_update(), Ok(()));
+
+    // When both sides send `stfu` as initiator, the funder is the initiator.
+    let ours = funder.initiate(false).unwrap().unwrap();
+    let theirs = fundee.initiate(false).unwrap().unwrap();
+    assert_eq!((ours.initiator, theirs.initiator), (1, 1));
+    assert!(funder.receive_stfu(&theirs, false).unwrap().is_none());
+    assert!(fundee.receive_stfu(&ours, false).unwrap().is_none());
+    assert_eq!(funder.is_initiator(), Some(true));
+    assert_eq!(fundee.is_initiator(), Some(false));
+
+    let mut other = Quiescence::new(ChannelId([4; 32]), false);
+    assert_eq!(
+        other.receive_stfu(&ours, false).err(),
+        Some(QuiescenceError::ChannelIdMismatch)
+    );
+}
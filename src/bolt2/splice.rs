@@ -0,0 +1,479 @@
+use super::interactive_tx::{
+    ConstructedTransaction, InteractiveTxConstructor, InteractiveTxError, InteractiveTxParams,
+    OutPoint, SharedInput, SharedOutput,
+};
+use super::messages::{
+    AnyInteractiveTxMessage, SpliceAck, SpliceInit, SpliceLocked, TxAddInput, TxAddOutput,
+    TxComplete,
+};
+use super::quiescence::Quiescence;
+use crate::items::channel_id::ChannelId;
+use crate::items::key::RawPublicKey;
+use alloc::{vec, vec::Vec};
+use bitcoin_hashes::{sha256, Hash as _};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpliceError {
+    // The channel is not quiescent, or we are on the wrong side of it to send
+    // or receive this message.
+    NotQuiescent,
+    WrongRole,
+    ChannelIdMismatch,
+    // A party's contribution would make its balance negative.
+    NegativeBalance { local: bool },
+    UnexpectedMessage,
+    // `splice_locked` named a different transaction than the one negotiated.
+    TxidMismatch,
+    InteractiveTx(InteractiveTxError),
+}
+impl From<InteractiveTxError> for SpliceError {
+    fn from(e: InteractiveTxError) -> Self {
+        SpliceError::InteractiveTx(e)
+    }
+}
+impl core::fmt::Display for SpliceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SpliceError::NotQuiescent => write!(f, "channel is not quiescent"),
+            SpliceError::WrongRole => write!(f, "wrong splice role"),
+            SpliceError::ChannelIdMismatch => write!(f, "channel_id mismatch"),
+            SpliceError::NegativeBalance { local } => write!(
+                f,
+                "{} contribution exceeds balance",
+                if *local { "local" } else { "remote" }
+            ),
+            SpliceError::UnexpectedMessage => write!(f, "unexpected message"),
+            SpliceError::TxidMismatch => write!(f, "splice_locked txid mismatch"),
+            SpliceError::InteractiveTx(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl core::error::Error for SpliceError {}
+
+// The channel being spliced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpliceParams {
+    pub channel_id: ChannelId,
+    pub funding_outpoint: OutPoint,
+    pub funding_script_pubkey: Vec<u8>,
+    pub local_balance_satoshis: u64,
+    pub remote_balance_satoshis: u64,
+    pub dust_limit_satoshis: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    AwaitingAck,
+    Negotiating,
+    Negotiated,
+}
+
+// A sans-IO driver for splicing a quiescent channel. The initiator sends
+// `splice_init`, the peer answers with `splice_ack`, and the splice
+// transaction is then built with the interactive-tx protocol: the initiator
+// adds the current funding output as a shared input and the new funding
+// output, and both sides add their own inputs and outputs.
+#[derive(Clone, Debug)]
+pub struct Splice {
+    params: SpliceParams,
+    is_initiator: bool,
+    feerate_per_kw: u32,
+    locktime: u32,
+    local_contribution: i64,
+    remote_contribution: i64,
+    local_funding_pubkey: RawPublicKey,
+    remote_funding_pubkey: RawPublicKey,
+    state: State,
+    constructor: Option<InteractiveTxConstructor>,
+    tx: Option<ConstructedTransaction>,
+    sent_locked: bool,
+    received_locked: bool,
+}
+impl Splice {
+    pub fn initiate(
+        params: SpliceParams,
+        quiescence: &Quiescence,
+        contribution: i64,
+        feerate_per_kw: u32,
+        locktime: u32,
+        funding_pubkey: RawPublicKey,
+    ) -> Result<(Self, SpliceInit), SpliceError> {
+        match quiescence.is_initiator() {
+            Some(true) => (),
+            Some(false) => return Err(SpliceError::WrongRole),
+            None => return Err(SpliceError::NotQuiescent),
+        }
+        let msg = SpliceInit {
+            channel_id: params.channel_id,
+            funding_contribution_satoshis: contribution,
+            funding_feerate_perkw: feerate_per_kw,
+            locktime,
+            funding_pubkey,
+            require_confirmed_inputs: None,
+        };
+        let splice = Splice {
+            params,
+            is_initiator: true,
+            feerate_per_kw,
+            locktime,
+            local_contribution: contribution,
+            remote_contribution: 0,
+            local_funding_pubkey: funding_pubkey,
+            remote_funding_pubkey: RawPublicKey([0; 33]),
+            state: State::AwaitingAck,
+            constructor: None,
+            tx: None,
+            sent_locked: false,
+            received_locked: false,
+        };
+        splice.new_balance(true)?;
+        Ok((splice, msg))
+    }
+
+    pub fn accept(
+        params: SpliceParams,
+        quiescence: &Quiescence,
+        msg: &SpliceInit,
+        contribution: i64,
+        funding_pubkey: RawPublicKey,
+    ) -> Result<(Self, SpliceAck), SpliceError> {
+        match quiescence.is_initiator() {
+            Some(false) => (),
+            Some(true) => return Err(SpliceError::WrongRole),
+            None => return Err(SpliceError::NotQuiescent),
+        }
+        if msg.channel_id != params.channel_id {
+            return Err(SpliceError::ChannelIdMismatch);
+        }
+        let mut splice = Splice {
+            params,
+            is_initiator: false,
+            feerate_per_kw: msg.funding_feerate_perkw,
+            locktime: msg.locktime,
+            local_contribution: contribution,
+            remote_contribution: msg.funding_contribution_satoshis,
+            local_funding_pubkey: funding_pubkey,
+            remote_funding_pubkey: msg.funding_pubkey,
+            state: State::AwaitingAck,
+            constructor: None,
+            tx: None,
+            sent_locked: false,
+            received_locked: false,
+        };
+        splice.start_negotiation()?;
+        let ack = SpliceAck {
+            channel_id: splice.params.channel_id,
+            funding_contribution_satoshis: contribution,
+            funding_pubkey,
+            require_confirmed_inputs: None,
+        };
+        Ok((splice, ack))
+    }
+
+    pub fn receive_ack(&mut self, msg: &SpliceAck) -> Result<(), SpliceError> {
+        if !self.is_initiator || self.state != State::AwaitingAck {
+            return Err(SpliceError::UnexpectedMessage);
+        }
+        if msg.channel_id != self.params.channel_id {
+            return Err(SpliceError::ChannelIdMismatch);
+        }
+        self.remote_contribution = msg.funding_contribution_satoshis;
+        self.remote_funding_pubkey = msg.funding_pubkey;
+        self.start_negotiation()
+    }
+
+    pub fn is_initiator(&self) -> bool {
+        self.is_initiator
+    }
+
+    pub fn new_capacity(&self) -> Result<u64, SpliceError> {
+        Ok(self.new_balance(true)? + self.new_balance(false)?)
+    }
+
+    // The P2WSH 2-of-2 script of the new funding output.
+    pub fn new_funding_script_pubkey(&self) -> Vec<u8> {
+        funding_script_pubkey(&self.local_funding_pubkey, &self.remote_funding_pubkey)
+    }
+
+    // The interactive-tx negotiation, once `splice_ack` has been exchanged.
+    // Local inputs and outputs are added through it.
+    pub fn constructor(&mut self) -> Result<&mut InteractiveTxConstructor, SpliceError> {
+        self.constructor
+            .as_mut()
+            .ok_or(SpliceError::UnexpectedMessage)
+    }
+
+    // Adds the current funding output as the shared input. Only the
+    // initiator does this.
+    pub fn add_shared_input(&mut self, sequence: u32) -> Result<TxAddInput, SpliceError> {
+        if !self.is_initiator {
+            return Err(SpliceError::WrongRole);
+        }
+        Ok(self.constructor()?.add_shared_input(sequence)?)
+    }
+
+    // Adds the new funding output. Only the initiator does this.
+    pub fn add_funding_output(&mut self) -> Result<TxAddOutput, SpliceError> {
+        if !self.is_initiator {
+            return Err(SpliceError::WrongRole);
+        }
+        let sats = self.new_capacity()?;
+        let script = self.new_funding_script_pubkey();
+        Ok(self.constructor()?.add_output(sats, script)?)
+    }
+
+    pub fn complete(&mut self) -> Result<TxComplete, SpliceError> {
+        let (msg, tx) = self.constructor()?.complete()?;
+        self.set_tx(tx);
+        Ok(msg)
+    }
+
+    pub fn receive(
+        &mut self,
+        msg: &AnyInteractiveTxMessage,
+    ) -> Result<Option<&ConstructedTransaction>, SpliceError> {
+        let tx = self.constructor()?.receive(msg)?;
+        self.set_tx(tx);
+        Ok(self.tx.as_ref())
+    }
+
+    // The negotiated splice transaction.
+    pub fn transaction(&self) -> Option<&ConstructedTransaction> {
+        self.tx.as_ref()
+    }
+
+    // Sends `splice_locked` once the splice transaction has reached the
+    // agreed depth.
+    pub fn splice_locked(&mut self) -> Result<SpliceLocked, SpliceError> {
+        let splice_txid = self
+            .tx
+            .as_ref()
+            .ok_or(SpliceError::UnexpectedMessage)?
+            .txid();
+        self.sent_locked = true;
+        Ok(SpliceLocked {
+            channel_id: self.params.channel_id,
+            splice_txid,
+        })
+    }
+
+    // Handles the peer's `splice_locked`. Returns true once both sides have
+    // locked, at which point the new funding output replaces the old one.
+    pub fn receive_splice_locked(&mut self, msg: &SpliceLocked) -> Result<bool, SpliceError> {
+        if msg.channel_id != self.params.channel_id {
+            return Err(SpliceError::ChannelIdMismatch);
+        }
+        let tx = self.tx.as_ref().ok_or(SpliceError::UnexpectedMessage)?;
+        if msg.splice_txid != tx.txid() {
+            return Err(SpliceError::TxidMismatch);
+        }
+        self.received_locked = true;
+        Ok(self.is_locked())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.sent_locked && self.received_locked
+    }
+
+    fn set_tx(&mut self, tx: Option<ConstructedTransaction>) {
+        if tx.is_some() {
+            self.tx = tx;
+            self.state = State::Negotiated;
+        }
+    }
+
+    fn new_balance(&self, local: bool) -> Result<u64, SpliceError> {
+        let (balance, contribution) = if local {
+            (self.params.local_balance_satoshis, self.local_contribution)
+        } else {
+            (
+                self.params.remote_balance_satoshis,
+                self.remote_contribution,
+            )
+        };
+        let new = balance as i128 + contribution as i128;
+        if new < 0 || new > u64::MAX as i128 {
+            return Err(SpliceError::NegativeBalance { local });
+        }
+        Ok(new as u64)
+    }
+
+    fn start_negotiation(&mut self) -> Result<(), SpliceError> {
+        let shared_input = SharedInput {
+            prevout: self.params.funding_outpoint,
+            script_pubkey: self.params.funding_script_pubkey.clone(),
+            local_satoshis: self.params.local_balance_satoshis,
+            remote_satoshis: self.params.remote_balance_satoshis,
+        };
+        let shared_output = SharedOutput {
+            script_pubkey: self.new_funding_script_pubkey(),
+            local_satoshis: self.new_balance(true)?,
+            remote_satoshis: self.new_balance(false)?,
+        };
+        self.constructor = Some(InteractiveTxConstructor::new(InteractiveTxParams {
+            channel_id: self.params.channel_id,
+            is_initiator: self.is_initiator,
+            locktime: self.locktime,
+            feerate_per_kw: self.feerate_per_kw,
+            dust_limit_satoshis: self.params.dust_limit_satoshis,
+            shared_input: Some(shared_input),
+            shared_output: Some(shared_output),
+        }));
+        self.state = State::Negotiating;
+        Ok(())
+    }
+}
+
+// The P2WSH output for a 2-of-2 multisig of the two funding pubkeys, sorted
+// lexicographically as in BOLT #3.
+pub fn funding_script_pubkey(a: &RawPublicKey, b: &RawPublicKey) -> Vec<u8> {
+    let (first, second) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    let mut witness_script = Vec::with_capacity(71);
+    witness_script.push(0x52);
+    witness_script.push(33);
+    witness_script.extend_from_slice(&first.0);
+    witness_script.push(33);
+    witness_script.extend_from_slice(&second.0);
+    witness_script.push(0x52);
+    witness_script.push(0xae);
+    let mut script = vec![0x00, 0x20];
+    script.extend_from_slice(&sha256::Hash::hash(&witness_script).to_byte_array());
+    script
+}
+
+#[test]
+fn splice() {
+    use AnyInteractiveTxMessage as M;
+
+    let channel_id = ChannelId([5; 32]);
+    let params = |local, remote| SpliceParams {
+        channel_id,
+        funding_outpoint: OutPoint {
+            txid: [9; 32],
+            vout: 1,
+        },
+        funding_script_pubkey: funding_script_pubkey(&[2; 33].into(), &[3; 33].into()),
+        local_balance_satoshis: local,
+        remote_balance_satoshis: remote,
+        dust_limit_satoshis: 330,
+    };
+    let mut funder = Quiescence::new(channel_id, true);
+    let mut fundee = Quiescence::new(channel_id, false);
+    assert_eq!(
+        Splice::initiate(params(500_000, 300_000), &funder, 0, 253, 0, [4; 33].into()).err(),
+        Some(SpliceError::NotQuiescent)
+    );
+    let stfu = funder.initiate(false).unwrap().unwrap();
+    let reply = fundee.receive_stfu(&stfu, false).unwrap().unwrap();
+    funder.receive_stfu(&reply, false).unwrap();
+
+    assert_eq!(
+        Splice::initiate(params(300_000, 500_000), &fundee, 0, 253, 0, [6; 33].into()).err(),
+        Some(SpliceError::WrongRole)
+    );
+    assert_eq!(
+        Splice::initiate(
+            params(500_000, 300_000),
+            &funder,
+            -500_001,
+            253,
+            0,
+            [4; 33].into()
+        )
+        .err(),
+        Some(SpliceError::NegativeBalance { local: true })
+    );
+    let (mut ours, init) = Splice::initiate(
+        params(500_000, 300_000),
+        &funder,
+        100_000,
+        253,
+        0,
+        [4; 33].into(),
+    )
+    .unwrap();
+    let (mut theirs, ack) =
+        Splice::accept(params(300_000, 500_000), &fundee, &init, 0, [6; 33].into()).unwrap();
+    assert_eq!(ack.funding_contribution_satoshis, 0);
+    ours.receive_ack(&ack).unwrap();
+    assert_eq!(ours.receive_ack(&ack), Err(SpliceError::UnexpectedMessage));
+    assert_eq!(ours.new_capacity(), Ok(900_000));
+    assert_eq!(
+        ours.new_funding_script_pubkey(),
+        theirs.new_funding_script_pubkey()
+    );
+    assert_eq!(
+        theirs.add_funding_output().err(),
+        Some(SpliceError::WrongRole)
+    );
+
+    // The initiator adds the shared input, an input of its own to pay for
+    // its contribution, and the new funding output.
+    let mut prevtx = 2_u32.to_le_bytes().to_vec();
+    prevtx.push(1);
+    prevtx.extend_from_slice(&[1; 36]);
+    prevtx.push(0);
+    prevtx.extend_from_slice(&[0xff; 4]);
+    prevtx.push(1);
+    prevtx.extend_from_slice(&150_000_u64.to_le_bytes());
+    prevtx.extend_from_slice(&[22, 0x00, 0x14]);
+    prevtx.extend_from_slice(&[7; 20]);
+    prevtx.extend_from_slice(&[0; 4]);
+
+    let add = ours.add_shared_input(0xfffffffd).unwrap();
+    assert_eq!(add.shared_input_txid, Some([9; 32]));
+    assert!(theirs.receive(&M::TxAddInput(add)).unwrap().is_none());
+    let complete = theirs.complete().unwrap();
+    assert!(ours.receive(&M::TxComplete(complete)).unwrap().is_none());
+    let add = ours
+        .constructor()
+        .unwrap()
+        .add_input(prevtx, 0, 0xfffffffd)
+        .unwrap();
+    theirs.receive(&M::TxAddInput(add)).unwrap();
+    let complete = theirs.complete().unwrap();
+    ours.receive(&M::TxComplete(complete)).unwrap();
+    let add = ours.add_funding_output().unwrap();
+    assert_eq!(add.sats, 900_000);
+    theirs.receive(&M::TxAddOutput(add)).unwrap();
+    let complete = theirs.complete().unwrap();
+    ours.receive(&M::TxComplete(complete)).unwrap();
+    assert!(ours.transaction().is_none());
+    let complete = ours.complete().unwrap();
+    let tx = ours.transaction().unwrap().clone();
+    let agreed = theirs.receive(&M::TxComplete(complete)).unwrap().unwrap();
+    assert_eq!(agreed.txid(), tx.txid());
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(tx.outputs[0].output.value, 900_000);
+
+    let locked = ours.splice_locked().unwrap();
+    assert_eq!(locked.splice_txid, tx.txid());
+    assert!(!ours.is_locked());
+    let mut wrong = locked.clone();
+    wrong.splice_txid = [0; 32];
+    assert_eq!(
+        theirs.receive_splice_locked(&wrong),
+        Err(SpliceError::TxidMismatch)
+    );
+    assert_eq!(theirs.receive_splice_locked(&locked), Ok(false));
+    let locked = theirs.splice_locked().unwrap();
+    assert!(theirs.is_locked());
+    assert_eq!(ours.receive_splice_locked(&locked), Ok(true));
+
+    // `tx_signatures` carries the signature for the shared input in TLV 0.
+    use crate::items::key::RawSignature;
+
+    let sigs = super::messages::TxSignatures {
+        channel_id,
+        txid: tx.txid(),
+        witnesses: Vec::new(),
+        shared_input_signature: Some(RawSignature([8; 64])),
+    };
+    let mut bytes = Vec::new();
+    crate::WireMessage::encode(&sigs, &mut bytes).unwrap();
+    assert_eq!(bytes.len(), 2 + 32 + 32 + 2 + 2 + 64);
+    assert_eq!(bytes[66..70], [0, 0, 0, 64]);
+    let decoded: super::messages::TxSignatures =
+        crate::WireMessage::decode(&mut &bytes[..], true).unwrap();
+    assert_eq!(decoded.shared_input_signature, Some(RawSignature([8; 64])));
+}
@@ -0,0 +1,305 @@
+// Route blinding (BOLT #4). The creator of a blinded path hides the node ids
+// of its hops behind blinded ones, and gives each hop data only that hop can
+// decrypt, using a path key that changes from one hop to the next.
+
+use super::sphinx::{blinding_factor, generate_key, shared_secret};
+use super::{read_records, OnionError};
+use crate::io::Write;
+use crate::items::blinded_path::{BlindedHop, BlindedPath, IntroductionNode};
+use crate::items::expiry::{CltvExpiry, CltvExpiryDelta};
+use crate::items::fees::MilliSatoshi;
+use crate::items::short_channel_id::ShortChannelId;
+use crate::items::truncated::{Tu32, Tu64};
+use crate::items::TLVBuffer;
+use crate::{TLVWireItem, TLVWireItemWriter, WireItem, WireRead};
+use alloc::vec::Vec;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+
+pub const PADDING_TYPE: u64 = 1;
+pub const SHORT_CHANNEL_ID_TYPE: u64 = 2;
+pub const NEXT_NODE_ID_TYPE: u64 = 4;
+pub const PATH_ID_TYPE: u64 = 6;
+pub const NEXT_PATH_KEY_OVERRIDE_TYPE: u64 = 8;
+pub const PAYMENT_RELAY_TYPE: u64 = 10;
+pub const PAYMENT_CONSTRAINTS_TYPE: u64 = 12;
+pub const ALLOWED_FEATURES_TYPE: u64 = 14;
+
+const KNOWN_TYPES: &[u64] = &[
+    PADDING_TYPE,
+    SHORT_CHANNEL_ID_TYPE,
+    NEXT_NODE_ID_TYPE,
+    PATH_ID_TYPE,
+    NEXT_PATH_KEY_OVERRIDE_TYPE,
+    PAYMENT_RELAY_TYPE,
+    PAYMENT_CONSTRAINTS_TYPE,
+    ALLOWED_FEATURES_TYPE,
+];
+
+// The fees and cltv delta a hop of a blinded payment path charges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaymentRelay {
+    pub cltv_expiry_delta: CltvExpiryDelta,
+    pub fee_proportional_millionths: u32,
+    pub fee_base_msat: u32,
+}
+impl PaymentRelay {
+    // What is left of `amount_msat` once the fees are taken, rounded up as
+    // the sender's aggregated fees are.
+    pub fn amount_to_forward(&self, amount: MilliSatoshi) -> Option<MilliSatoshi> {
+        let prop = u128::from(self.fee_proportional_millionths);
+        let amount = u128::from(amount.0).checked_sub(u128::from(self.fee_base_msat))?;
+        let forward = (amount * 1_000_000 + 1_000_000 + prop - 1) / (1_000_000 + prop);
+        core::convert::TryFrom::try_from(forward)
+            .ok()
+            .map(MilliSatoshi)
+    }
+}
+impl TLVWireItem for PaymentRelay {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(WireItem::encode(&self.cltv_expiry_delta, w)?
+            + WireItem::encode(&self.fee_proportional_millionths, w)?
+            + TLVWireItem::encode(&Tu32(self.fee_base_msat), w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        6 + TLVWireItem::encoded_len(&Tu32(self.fee_base_msat))
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        let fee_base_len = len
+            .checked_sub(6)
+            .ok_or(crate::io::ErrorKind::InvalidData)?;
+        Ok(PaymentRelay {
+            cltv_expiry_delta: WireItem::decode(r)?,
+            fee_proportional_millionths: WireItem::decode(r)?,
+            fee_base_msat: <Tu32 as TLVWireItem>::decode(r, fee_base_len)?.0,
+        })
+    }
+}
+
+// The limits a hop of a blinded payment path puts on the htlcs it relays,
+// so that the path cannot be probed with other amounts or expiries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaymentConstraints {
+    pub max_cltv_expiry: CltvExpiry,
+    pub htlc_minimum_msat: MilliSatoshi,
+}
+impl TLVWireItem for PaymentConstraints {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(WireItem::encode(&self.max_cltv_expiry, w)?
+            + TLVWireItem::encode(&Tu64(self.htlc_minimum_msat.0), w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        4 + TLVWireItem::encoded_len(&Tu64(self.htlc_minimum_msat.0))
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        let minimum_len = len
+            .checked_sub(4)
+            .ok_or(crate::io::ErrorKind::InvalidData)?;
+        Ok(PaymentConstraints {
+            max_cltv_expiry: WireItem::decode(r)?,
+            htlc_minimum_msat: MilliSatoshi(<Tu64 as TLVWireItem>::decode(r, minimum_len)?.0),
+        })
+    }
+}
+
+// The `encrypted_data_tlv` stream a blinded hop finds in its
+// `encrypted_recipient_data`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncryptedData {
+    // Ignored; used to make all hops' data the same length.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub padding: Option<Vec<u8>>,
+    pub short_channel_id: Option<ShortChannelId>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub next_node_id: Option<[u8; 33]>,
+    // For the final hop, to recognize paths it created.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub path_id: Option<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub next_path_key_override: Option<[u8; 33]>,
+    pub payment_relay: Option<PaymentRelay>,
+    pub payment_constraints: Option<PaymentConstraints>,
+    // Raw feature bits. None are defined for blinded paths yet, so any bit
+    // set is unsupported.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub allowed_features: Option<Vec<u8>>,
+}
+impl WireItem for EncryptedData {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        if let Some(padding) = &self.padding {
+            count += TLVBuffer::from(&padding[..]).encode_tlv(w, PADDING_TYPE)?;
+        }
+        if let Some(scid) = &self.short_channel_id {
+            count += scid.encode_tlv(w, SHORT_CHANNEL_ID_TYPE)?;
+        }
+        if let Some(node_id) = &self.next_node_id {
+            count += node_id.encode_tlv(w, NEXT_NODE_ID_TYPE)?;
+        }
+        if let Some(path_id) = &self.path_id {
+            count += TLVBuffer::from(&path_id[..]).encode_tlv(w, PATH_ID_TYPE)?;
+        }
+        if let Some(path_key) = &self.next_path_key_override {
+            count += path_key.encode_tlv(w, NEXT_PATH_KEY_OVERRIDE_TYPE)?;
+        }
+        if let Some(relay) = &self.payment_relay {
+            count += relay.encode_tlv(w, PAYMENT_RELAY_TYPE)?;
+        }
+        if let Some(constraints) = &self.payment_constraints {
+            count += constraints.encode_tlv(w, PAYMENT_CONSTRAINTS_TYPE)?;
+        }
+        if let Some(features) = &self.allowed_features {
+            count += TLVBuffer::from(&features[..]).encode_tlv(w, ALLOWED_FEATURES_TYPE)?;
+        }
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let mut len = 0;
+        if let Some(padding) = &self.padding {
+            len += crate::tlv_record_len(PADDING_TYPE, padding);
+        }
+        if let Some(scid) = &self.short_channel_id {
+            len += crate::tlv_record_len(SHORT_CHANNEL_ID_TYPE, scid);
+        }
+        if let Some(node_id) = &self.next_node_id {
+            len += crate::tlv_record_len(NEXT_NODE_ID_TYPE, node_id);
+        }
+        if let Some(path_id) = &self.path_id {
+            len += crate::tlv_record_len(PATH_ID_TYPE, path_id);
+        }
+        if let Some(path_key) = &self.next_path_key_override {
+            len += crate::tlv_record_len(NEXT_PATH_KEY_OVERRIDE_TYPE, path_key);
+        }
+        if let Some(relay) = &self.payment_relay {
+            len += crate::tlv_record_len(PAYMENT_RELAY_TYPE, relay);
+        }
+        if let Some(constraints) = &self.payment_constraints {
+            len += crate::tlv_record_len(PAYMENT_CONSTRAINTS_TYPE, constraints);
+        }
+        if let Some(features) = &self.allowed_features {
+            len += crate::tlv_record_len(ALLOWED_FEATURES_TYPE, features);
+        }
+        len
+    }
+
+    // Reads up to the end of `r`.
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let records = read_records(r, KNOWN_TYPES)?;
+        Ok(EncryptedData {
+            padding: records.get_item(PADDING_TYPE)?,
+            short_channel_id: records.get_item(SHORT_CHANNEL_ID_TYPE)?,
+            next_node_id: records.get_item(NEXT_NODE_ID_TYPE)?,
+            path_id: records.get_item(PATH_ID_TYPE)?,
+            next_path_key_override: records.get_item(NEXT_PATH_KEY_OVERRIDE_TYPE)?,
+            payment_relay: records.get_item(PAYMENT_RELAY_TYPE)?,
+            payment_constraints: records.get_item(PAYMENT_CONSTRAINTS_TYPE)?,
+            allowed_features: records.get_item(ALLOWED_FEATURES_TYPE)?,
+        })
+    }
+}
+
+fn blinding_tweak(shared_secret: &[u8; 32]) -> Result<Scalar, OnionError> {
+    Scalar::from_be_bytes(generate_key(b"blinded_node_id", shared_secret))
+        .map_err(|_| OnionError::InvalidKey)
+}
+
+pub fn blinded_node_id(
+    node_id: &PublicKey,
+    shared_secret: &[u8; 32],
+) -> Result<PublicKey, OnionError> {
+    Ok(node_id.mul_tweak(&Secp256k1::new(), &blinding_tweak(shared_secret)?)?)
+}
+
+// The key a blinded hop peels its onions with.
+pub fn blinded_node_secret(
+    node_secret: &SecretKey,
+    shared_secret: &[u8; 32],
+) -> Result<SecretKey, OnionError> {
+    Ok(node_secret.mul_tweak(&blinding_tweak(shared_secret)?)?)
+}
+
+pub fn next_path_key(
+    path_key: &PublicKey,
+    shared_secret: &[u8; 32],
+) -> Result<PublicKey, OnionError> {
+    Ok(path_key.mul_tweak(
+        &Secp256k1::new(),
+        &blinding_factor(path_key, shared_secret)?,
+    )?)
+}
+
+pub fn encrypt_data(shared_secret: &[u8; 32], data: &EncryptedData) -> Vec<u8> {
+    let mut plaintext = Vec::new();
+    WireItem::encode(data, &mut plaintext).expect("writing to a Vec");
+    encrypt_raw_data(shared_secret, &plaintext)
+}
+
+// Encrypts an already encoded `encrypted_data_tlv` stream, which may hold
+// records `EncryptedData` does not know.
+pub fn encrypt_raw_data(shared_secret: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    ChaCha20Poly1305::new(&generate_key(b"rho", shared_secret).into())
+        .encrypt(&[0; 12].into(), plaintext)
+        .expect("encrypting to a Vec")
+}
+
+pub fn decrypt_data(
+    shared_secret: &[u8; 32],
+    ciphertext: &[u8],
+) -> Result<EncryptedData, OnionError> {
+    let plaintext = ChaCha20Poly1305::new(&generate_key(b"rho", shared_secret).into())
+        .decrypt(&[0; 12].into(), ciphertext)
+        .map_err(|_| OnionError::InvalidEncryptedData)?;
+    <EncryptedData as WireItem>::decode(&mut &plaintext[..])
+        .map_err(|_| OnionError::InvalidEncryptedData)
+}
+
+// Blinds a path through `hops`, the first of which is the introduction node,
+// each given the data it is to decrypt.
+pub fn create_blinded_path(
+    session_key: &SecretKey,
+    hops: &[(PublicKey, EncryptedData)],
+) -> Result<BlindedPath, OnionError> {
+    let hops = hops
+        .iter()
+        .map(|(node_id, data)| {
+            let mut plaintext = Vec::new();
+            WireItem::encode(data, &mut plaintext).expect("writing to a Vec");
+            (*node_id, plaintext)
+        })
+        .collect::<Vec<_>>();
+    create_raw_blinded_path(session_key, &hops)
+}
+
+// As `create_blinded_path`, with each hop's data already encoded.
+pub fn create_raw_blinded_path(
+    session_key: &SecretKey,
+    hops: &[(PublicKey, Vec<u8>)],
+) -> Result<BlindedPath, OnionError> {
+    let (introduction_node, _) = hops.first().ok_or(OnionError::MissingNextHop)?;
+    let secp = Secp256k1::new();
+    let mut secret = *session_key;
+    let mut blinded_hops = Vec::with_capacity(hops.len());
+    for (node_id, plaintext) in hops {
+        let path_key = PublicKey::from_secret_key(&secp, &secret);
+        let ss = shared_secret(node_id, &secret);
+        blinded_hops.push(BlindedHop {
+            blinded_node_id: blinded_node_id(node_id, &ss)?.serialize(),
+            encrypted_recipient_data: encrypt_raw_data(&ss, plaintext).into(),
+        });
+        secret = secret.mul_tweak(&blinding_factor(&path_key, &ss)?)?;
+    }
+    Ok(BlindedPath {
+        introduction_node: IntroductionNode::NodeId(introduction_node.serialize()),
+        first_path_key: PublicKey::from_secret_key(&secp, session_key).serialize(),
+        hops: blinded_hops,
+    })
+}
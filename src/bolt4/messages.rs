@@ -0,0 +1,16 @@
+use crate::wire_message::wire_message;
+use alloc::vec::Vec;
+
+wire_message! {
+    #[msg_type = 513]
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct OnionMessage {
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub path_key: [u8; 33],
+        // A serialized `sphinx::OnionPacket`.
+        #[len_prefix = u16]
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+        pub onion_message_packet: Vec<u8>,
+    }
+}
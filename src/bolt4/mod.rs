@@ -0,0 +1,57 @@
+pub mod blinding;
+pub mod messages;
+pub mod onion_message;
+pub mod payment;
+pub mod sphinx;
+
+use crate::tlv::TlvStream;
+use crate::{WireItem, WireRead};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OnionError {
+    // The packet is malformed or has an unknown version.
+    InvalidPacket,
+    // The packet was not built for us, or was tampered with.
+    InvalidHmac,
+    // The payloads do not fit the packet.
+    PayloadTooLarge,
+    // The decrypted hop payload is malformed.
+    InvalidPayload,
+    // `encrypted_recipient_data` is missing, cannot be decrypted or is
+    // malformed.
+    InvalidEncryptedData,
+    // A key or tweak is out of range.
+    InvalidKey,
+    // A hop that is not the last one has nowhere to forward to.
+    MissingNextHop,
+    // The htlc does not meet the constraints or the fees of the blinded
+    // path it is in.
+    BlindedPathViolation,
+}
+impl core::fmt::Display for OnionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OnionError::InvalidPacket => write!(f, "invalid onion packet"),
+            OnionError::InvalidHmac => write!(f, "invalid onion hmac"),
+            OnionError::PayloadTooLarge => write!(f, "hop payloads too large for the onion"),
+            OnionError::InvalidPayload => write!(f, "invalid hop payload"),
+            OnionError::InvalidEncryptedData => write!(f, "invalid encrypted_recipient_data"),
+            OnionError::InvalidKey => write!(f, "invalid key"),
+            OnionError::MissingNextHop => write!(f, "missing next hop"),
+            OnionError::BlindedPathViolation => write!(f, "htlc violates blinded path"),
+        }
+    }
+}
+impl core::error::Error for OnionError {}
+impl From<secp256k1::Error> for OnionError {
+    fn from(_: secp256k1::Error) -> Self {
+        OnionError::InvalidKey
+    }
+}
+
+// Reads the TLV stream up to the end of `r`, rejecting unknown even types.
+fn read_records<R: WireRead>(r: &mut R, known: &[u64]) -> crate::io::Result<TlvStream> {
+    let records = <TlvStream as WireItem>::decode(r)?;
+    records.check_known(known)?;
+    Ok(records)
+}
@@ -0,0 +1,307 @@
+// Onion messages travel along a blinded path, every hop of which finds in
+// its `encrypted_recipient_data` where to forward the message to.
+
+use super::blinding::{self, EncryptedData};
+use super::messages::OnionMessage;
+use super::sphinx::{self, OnionPacket};
+use super::{read_records, OnionError};
+use crate::io::Write;
+use crate::items::blinded_path::BlindedPath;
+use crate::items::short_channel_id::ShortChannelId;
+use crate::items::TLVBuffer;
+use crate::{TLVWireItemWriter, WireItem, WireRead};
+use alloc::vec::Vec;
+use secp256k1::{PublicKey, SecretKey};
+
+pub const REPLY_PATH_TYPE: u64 = 2;
+pub const ENCRYPTED_RECIPIENT_DATA_TYPE: u64 = 4;
+pub const INVOICE_REQUEST_TYPE: u64 = 64;
+pub const INVOICE_TYPE: u64 = 66;
+pub const INVOICE_ERROR_TYPE: u64 = 68;
+
+const KNOWN_TYPES: &[u64] = &[
+    REPLY_PATH_TYPE,
+    ENCRYPTED_RECIPIENT_DATA_TYPE,
+    INVOICE_REQUEST_TYPE,
+    INVOICE_TYPE,
+    INVOICE_ERROR_TYPE,
+];
+
+// Onion messages use the smaller size when the payloads fit.
+pub const PACKET_LEN: usize = 1300;
+pub const LARGE_PACKET_LEN: usize = 32768;
+
+// The `onionmsg_tlv` payload of a hop. Only the final hop gets a reply path
+// and content.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnionMessagePayload {
+    pub reply_path: Option<BlindedPath>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub encrypted_recipient_data: Option<Vec<u8>>,
+    // BOLT #12 TLV streams, see `bolt12::invoice_request::InvoiceRequest::decode`
+    // and the like.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub invoice_request: Option<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub invoice: Option<Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub invoice_error: Option<Vec<u8>>,
+}
+impl OnionMessagePayload {
+    fn has_content(&self) -> bool {
+        self.reply_path.is_some()
+            || self.invoice_request.is_some()
+            || self.invoice.is_some()
+            || self.invoice_error.is_some()
+    }
+}
+impl WireItem for OnionMessagePayload {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        if let Some(reply_path) = &self.reply_path {
+            count += reply_path.encode_tlv(w, REPLY_PATH_TYPE)?;
+        }
+        let buffers = [
+            (
+                ENCRYPTED_RECIPIENT_DATA_TYPE,
+                &self.encrypted_recipient_data,
+            ),
+            (INVOICE_REQUEST_TYPE, &self.invoice_request),
+            (INVOICE_TYPE, &self.invoice),
+            (INVOICE_ERROR_TYPE, &self.invoice_error),
+        ];
+        for (tlv_type, buffer) in buffers.iter() {
+            if let Some(buffer) = buffer {
+                count += TLVBuffer::from(&buffer[..]).encode_tlv(w, *tlv_type)?;
+            }
+        }
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let mut len = 0;
+        if let Some(reply_path) = &self.reply_path {
+            len += crate::tlv_record_len(REPLY_PATH_TYPE, reply_path);
+        }
+        let buffers = [
+            (
+                ENCRYPTED_RECIPIENT_DATA_TYPE,
+                &self.encrypted_recipient_data,
+            ),
+            (INVOICE_REQUEST_TYPE, &self.invoice_request),
+            (INVOICE_TYPE, &self.invoice),
+            (INVOICE_ERROR_TYPE, &self.invoice_error),
+        ];
+        for (tlv_type, buffer) in buffers.iter() {
+            if let Some(buffer) = buffer {
+                len += crate::tlv_record_len(*tlv_type, buffer);
+            }
+        }
+        len
+    }
+
+    // Reads up to the end of `r`.
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let records = read_records(r, KNOWN_TYPES)?;
+        Ok(OnionMessagePayload {
+            reply_path: records.get_item(REPLY_PATH_TYPE)?,
+            encrypted_recipient_data: records.get_item(ENCRYPTED_RECIPIENT_DATA_TYPE)?,
+            invoice_request: records.get_item(INVOICE_REQUEST_TYPE)?,
+            invoice: records.get_item(INVOICE_TYPE)?,
+            invoice_error: records.get_item(INVOICE_ERROR_TYPE)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NextHop {
+    NodeId([u8; 33]),
+    ShortChannelId(ShortChannelId),
+}
+
+#[derive(Clone, Debug)]
+pub enum PeeledOnionMessage {
+    Forward {
+        next: NextHop,
+        message: OnionMessage,
+    },
+    // We are the final hop. `path_id` is the one we put in the path.
+    Receive {
+        payload: OnionMessagePayload,
+        path_id: Option<Vec<u8>>,
+    },
+}
+
+// Builds a message along `path`, to be sent to its introduction node, with
+// `content` for the final hop. Its `encrypted_recipient_data` is overwritten.
+pub fn build_onion_message(
+    session_key: &SecretKey,
+    path: &BlindedPath,
+    content: OnionMessagePayload,
+) -> Result<OnionMessage, OnionError> {
+    let mut hops = Vec::with_capacity(path.hops.len());
+    for (i, hop) in path.hops.iter().enumerate() {
+        let mut payload = if i + 1 == path.hops.len() {
+            content.clone()
+        } else {
+            OnionMessagePayload::default()
+        };
+        payload.encrypted_recipient_data = Some(hop.encrypted_recipient_data.value.clone());
+        let mut bytes = Vec::new();
+        WireItem::encode(&payload, &mut bytes).map_err(|_| OnionError::InvalidPayload)?;
+        hops.push((PublicKey::from_slice(&hop.blinded_node_id)?, bytes));
+    }
+    let packet = match sphinx::construct(session_key, &hops, PACKET_LEN, &[]) {
+        Err(OnionError::PayloadTooLarge) => {
+            sphinx::construct(session_key, &hops, LARGE_PACKET_LEN, &[])?
+        }
+        packet => packet?,
+    };
+    Ok(OnionMessage {
+        path_key: path.first_path_key,
+        onion_message_packet: packet.to_bytes(),
+    })
+}
+
+// Decrypts our layer of `message`, with the secret key of our node id.
+pub fn peel_onion_message(
+    node_secret: &SecretKey,
+    message: &OnionMessage,
+) -> Result<PeeledOnionMessage, OnionError> {
+    let path_key = PublicKey::from_slice(&message.path_key)?;
+    let shared_secret = sphinx::shared_secret(&path_key, node_secret);
+    let packet = OnionPacket::from_bytes(&message.onion_message_packet)?;
+    let peeled = sphinx::peel(
+        &blinding::blinded_node_secret(node_secret, &shared_secret)?,
+        &packet,
+        &[],
+    )?;
+    let payload = <OnionMessagePayload as WireItem>::decode(&mut &peeled.payload[..])
+        .map_err(|_| OnionError::InvalidPayload)?;
+    let data: EncryptedData = blinding::decrypt_data(
+        &shared_secret,
+        payload
+            .encrypted_recipient_data
+            .as_ref()
+            .ok_or(OnionError::InvalidEncryptedData)?,
+    )?;
+    let packet = match peeled.next {
+        Some(packet) => packet,
+        None => {
+            return Ok(PeeledOnionMessage::Receive {
+                payload,
+                path_id: data.path_id,
+            })
+        }
+    };
+    if payload.has_content() {
+        return Err(OnionError::InvalidPayload);
+    }
+    let next = match (data.next_node_id, data.short_channel_id) {
+        (Some(node_id), _) => NextHop::NodeId(node_id),
+        (None, Some(scid)) => NextHop::ShortChannelId(scid),
+        (None, None) => return Err(OnionError::MissingNextHop),
+    };
+    let path_key = match data.next_path_key_override {
+        Some(path_key) => path_key,
+        None => blinding::next_path_key(&path_key, &shared_secret)?.serialize(),
+    };
+    Ok(PeeledOnionMessage::Forward {
+        next,
+        message: OnionMessage {
+            path_key,
+            onion_message_packet: packet.to_bytes(),
+        },
+    })
+}
+
+#[test]
+fn blinded_path_roundtrip() {
+    use secp256k1::Secp256k1;
+
+    let secp = Secp256k1::new();
+    let secrets = (1..=3)
+        .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+        .collect::<Vec<_>>();
+    let node_ids = secrets
+        .iter()
+        .map(|s| PublicKey::from_secret_key(&secp, s))
+        .collect::<Vec<_>>();
+    let hops = vec![
+        (
+            node_ids[0],
+            EncryptedData {
+                next_node_id: Some(node_ids[1].serialize()),
+                ..Default::default()
+            },
+        ),
+        (
+            node_ids[1],
+            EncryptedData {
+                short_channel_id: Some(ShortChannelId(0x0001_0002_0003)),
+                ..Default::default()
+            },
+        ),
+        (
+            node_ids[2],
+            EncryptedData {
+                path_id: Some(vec![0x42; 32]),
+                ..Default::default()
+            },
+        ),
+    ];
+    let path =
+        blinding::create_blinded_path(&SecretKey::from_slice(&[9; 32]).unwrap(), &hops).unwrap();
+    for (_, data) in hops.iter() {
+        let mut bytes = Vec::new();
+        WireItem::encode(data, &mut bytes).unwrap();
+        assert_eq!(WireItem::encoded_len(data), bytes.len());
+    }
+    let mut bytes = Vec::new();
+    WireItem::encode(&path, &mut bytes).unwrap();
+    assert_eq!(WireItem::encoded_len(&path), bytes.len());
+    assert_eq!(
+        <BlindedPath as WireItem>::decode(&mut &bytes[..]).unwrap(),
+        path
+    );
+
+    let content = OnionMessagePayload {
+        reply_path: Some(path.clone()),
+        invoice_request: Some(vec![1, 2, 3]),
+        ..Default::default()
+    };
+    let mut bytes = Vec::new();
+    WireItem::encode(&content, &mut bytes).unwrap();
+    assert_eq!(WireItem::encoded_len(&content), bytes.len());
+    let mut message = build_onion_message(
+        &SecretKey::from_slice(&[8; 32]).unwrap(),
+        &path,
+        content.clone(),
+    )
+    .unwrap();
+    assert_eq!(message.onion_message_packet.len(), 1 + 33 + PACKET_LEN + 32);
+    assert!(peel_onion_message(&secrets[1], &message).is_err());
+
+    let expected = [
+        NextHop::NodeId(node_ids[1].serialize()),
+        NextHop::ShortChannelId(ShortChannelId(0x0001_0002_0003)),
+    ];
+    for (secret, expected) in secrets.iter().zip(expected.iter()) {
+        match peel_onion_message(secret, &message).unwrap() {
+            PeeledOnionMessage::Forward { next, message: m } => {
+                assert_eq!(&next, expected);
+                message = m;
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    match peel_onion_message(&secrets[2], &message).unwrap() {
+        PeeledOnionMessage::Receive { payload, path_id } => {
+            assert_eq!(path_id, Some(vec![0x42; 32]));
+            assert_eq!(payload.invoice_request, content.invoice_request);
+            assert_eq!(payload.reply_path, content.reply_path);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
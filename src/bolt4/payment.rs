@@ -0,0 +1,535 @@
+// Payment onions, including the parts of a route that go through a blinded
+// path. Inside a blinded path, hops find the fees, cltv delta and next
+// channel in their `encrypted_recipient_data` instead of the payload, and
+// learn the path key from the htlc (or, for the introduction node, from the
+// payload).
+
+use super::blinding::{self, EncryptedData};
+use super::sphinx::{self, OnionPacket};
+use super::{read_records, OnionError};
+use crate::bolt2::messages::UpdateAddHtlc;
+use crate::io::Write;
+use crate::items::blinded_path::BlindedPath;
+use crate::items::expiry::CltvExpiry;
+use crate::items::fees::MilliSatoshi;
+use crate::items::key::RawPublicKey;
+use crate::items::short_channel_id::ShortChannelId;
+use crate::items::truncated::{Tu32, Tu64};
+use crate::items::TLVBuffer;
+use crate::{TLVWireItem, TLVWireItemWriter, WireItem, WireRead};
+use alloc::{boxed::Box, vec::Vec};
+use secp256k1::{PublicKey, SecretKey};
+
+pub const PACKET_LEN: usize = 1300;
+
+pub const AMT_TO_FORWARD_TYPE: u64 = 2;
+pub const OUTGOING_CLTV_VALUE_TYPE: u64 = 4;
+pub const SHORT_CHANNEL_ID_TYPE: u64 = 6;
+pub const PAYMENT_DATA_TYPE: u64 = 8;
+pub const ENCRYPTED_RECIPIENT_DATA_TYPE: u64 = 10;
+pub const CURRENT_PATH_KEY_TYPE: u64 = 12;
+pub const PAYMENT_METADATA_TYPE: u64 = 16;
+pub const TOTAL_AMOUNT_MSAT_TYPE: u64 = 18;
+
+const KNOWN_TYPES: &[u64] = &[
+    AMT_TO_FORWARD_TYPE,
+    OUTGOING_CLTV_VALUE_TYPE,
+    SHORT_CHANNEL_ID_TYPE,
+    PAYMENT_DATA_TYPE,
+    ENCRYPTED_RECIPIENT_DATA_TYPE,
+    CURRENT_PATH_KEY_TYPE,
+    PAYMENT_METADATA_TYPE,
+    TOTAL_AMOUNT_MSAT_TYPE,
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PaymentData {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub payment_secret: [u8; 32],
+    pub total_msat: MilliSatoshi,
+}
+impl TLVWireItem for PaymentData {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(WireItem::encode(&self.payment_secret, w)?
+            + TLVWireItem::encode(&Tu64(self.total_msat.0), w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        32 + TLVWireItem::encoded_len(&Tu64(self.total_msat.0))
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        let total_len = len
+            .checked_sub(32)
+            .ok_or(crate::io::ErrorKind::InvalidData)?;
+        Ok(PaymentData {
+            payment_secret: WireItem::decode(r)?,
+            total_msat: MilliSatoshi(<Tu64 as TLVWireItem>::decode(r, total_len)?.0),
+        })
+    }
+}
+
+// The `payload` TLV stream of a hop.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopPayload {
+    pub amt_to_forward: Option<MilliSatoshi>,
+    pub outgoing_cltv_value: Option<CltvExpiry>,
+    pub short_channel_id: Option<ShortChannelId>,
+    pub payment_data: Option<PaymentData>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub encrypted_recipient_data: Option<Vec<u8>>,
+    // Only for the introduction node of a blinded path.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub current_path_key: Option<[u8; 33]>,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serde_hex"))]
+    pub payment_metadata: Option<Vec<u8>>,
+    // The total amount of the payment, for the final hop of a blinded path.
+    pub total_amount_msat: Option<MilliSatoshi>,
+}
+impl WireItem for HopPayload {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        if let Some(amount) = self.amt_to_forward {
+            count += Tu64(amount.0).encode_tlv(w, AMT_TO_FORWARD_TYPE)?;
+        }
+        if let Some(cltv) = self.outgoing_cltv_value {
+            count += Tu32(cltv.0).encode_tlv(w, OUTGOING_CLTV_VALUE_TYPE)?;
+        }
+        if let Some(scid) = &self.short_channel_id {
+            count += scid.encode_tlv(w, SHORT_CHANNEL_ID_TYPE)?;
+        }
+        if let Some(data) = &self.payment_data {
+            count += data.encode_tlv(w, PAYMENT_DATA_TYPE)?;
+        }
+        if let Some(data) = &self.encrypted_recipient_data {
+            count += TLVBuffer::from(&data[..]).encode_tlv(w, ENCRYPTED_RECIPIENT_DATA_TYPE)?;
+        }
+        if let Some(path_key) = &self.current_path_key {
+            count += path_key.encode_tlv(w, CURRENT_PATH_KEY_TYPE)?;
+        }
+        if let Some(metadata) = &self.payment_metadata {
+            count += TLVBuffer::from(&metadata[..]).encode_tlv(w, PAYMENT_METADATA_TYPE)?;
+        }
+        if let Some(amount) = self.total_amount_msat {
+            count += Tu64(amount.0).encode_tlv(w, TOTAL_AMOUNT_MSAT_TYPE)?;
+        }
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let mut len = 0;
+        if let Some(amount) = self.amt_to_forward {
+            len += crate::tlv_record_len(AMT_TO_FORWARD_TYPE, &Tu64(amount.0));
+        }
+        if let Some(cltv) = self.outgoing_cltv_value {
+            len += crate::tlv_record_len(OUTGOING_CLTV_VALUE_TYPE, &Tu32(cltv.0));
+        }
+        if let Some(scid) = &self.short_channel_id {
+            len += crate::tlv_record_len(SHORT_CHANNEL_ID_TYPE, scid);
+        }
+        if let Some(data) = &self.payment_data {
+            len += crate::tlv_record_len(PAYMENT_DATA_TYPE, data);
+        }
+        if let Some(data) = &self.encrypted_recipient_data {
+            len += crate::tlv_record_len(ENCRYPTED_RECIPIENT_DATA_TYPE, data);
+        }
+        if let Some(path_key) = &self.current_path_key {
+            len += crate::tlv_record_len(CURRENT_PATH_KEY_TYPE, path_key);
+        }
+        if let Some(metadata) = &self.payment_metadata {
+            len += crate::tlv_record_len(PAYMENT_METADATA_TYPE, metadata);
+        }
+        if let Some(amount) = self.total_amount_msat {
+            len += crate::tlv_record_len(TOTAL_AMOUNT_MSAT_TYPE, &Tu64(amount.0));
+        }
+        len
+    }
+
+    // Reads up to the end of `r`.
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let records = read_records(r, KNOWN_TYPES)?;
+        Ok(HopPayload {
+            amt_to_forward: records
+                .get_item::<Tu64>(AMT_TO_FORWARD_TYPE)?
+                .map(|n| MilliSatoshi(n.0)),
+            outgoing_cltv_value: records
+                .get_item::<Tu32>(OUTGOING_CLTV_VALUE_TYPE)?
+                .map(|n| CltvExpiry(n.0)),
+            short_channel_id: records.get_item(SHORT_CHANNEL_ID_TYPE)?,
+            payment_data: records.get_item(PAYMENT_DATA_TYPE)?,
+            encrypted_recipient_data: records.get_item(ENCRYPTED_RECIPIENT_DATA_TYPE)?,
+            current_path_key: records.get_item(CURRENT_PATH_KEY_TYPE)?,
+            payment_metadata: records.get_item(PAYMENT_METADATA_TYPE)?,
+            total_amount_msat: records
+                .get_item::<Tu64>(TOTAL_AMOUNT_MSAT_TYPE)?
+                .map(|n| MilliSatoshi(n.0)),
+        })
+    }
+}
+
+// The payloads of the hops of `path`, the last of which is the recipient,
+// for a payment of `amount_msat` with the given final cltv expiry. The
+// introduction node's layer is encrypted to its real node id, as it only
+// learns the path key from its payload.
+pub fn blinded_path_payloads(
+    path: &BlindedPath,
+    introduction_node_id: &PublicKey,
+    amount_msat: MilliSatoshi,
+    cltv_expiry: CltvExpiry,
+    total_amount_msat: MilliSatoshi,
+) -> Result<Vec<(PublicKey, HopPayload)>, OnionError> {
+    let mut hops = Vec::with_capacity(path.hops.len());
+    for (i, hop) in path.hops.iter().enumerate() {
+        let mut payload = HopPayload {
+            encrypted_recipient_data: Some(hop.encrypted_recipient_data.value.clone()),
+            ..Default::default()
+        };
+        let pubkey = if i == 0 {
+            payload.current_path_key = Some(path.first_path_key);
+            *introduction_node_id
+        } else {
+            PublicKey::from_slice(&hop.blinded_node_id)?
+        };
+        if i + 1 == path.hops.len() {
+            payload.amt_to_forward = Some(amount_msat);
+            payload.outgoing_cltv_value = Some(cltv_expiry);
+            payload.total_amount_msat = Some(total_amount_msat);
+        }
+        hops.push((pubkey, payload));
+    }
+    Ok(hops)
+}
+
+pub fn construct_onion(
+    session_key: &SecretKey,
+    hops: &[(PublicKey, HopPayload)],
+    payment_hash: &[u8; 32],
+) -> Result<[u8; 1366], OnionError> {
+    let hops = hops
+        .iter()
+        .map(|(pubkey, payload)| {
+            let mut bytes = Vec::new();
+            WireItem::encode(payload, &mut bytes).map_err(|_| OnionError::InvalidPayload)?;
+            Ok((*pubkey, bytes))
+        })
+        .collect::<Result<Vec<_>, OnionError>>()?;
+    let mut res = [0; 1366];
+    res.copy_from_slice(
+        &sphinx::construct(session_key, &hops, PACKET_LEN, payment_hash)?.to_bytes(),
+    );
+    Ok(res)
+}
+
+#[derive(Clone, Debug)]
+pub enum PeeledPayment {
+    // The htlc to offer on `short_channel_id`.
+    Forward {
+        short_channel_id: ShortChannelId,
+        amount_msat: MilliSatoshi,
+        cltv_expiry: CltvExpiry,
+        onion_routing_packet: Box<[u8; 1366]>,
+        blinding_point: Option<RawPublicKey>,
+    },
+    // We are the recipient. `path_id` is the one we put in the blinded path
+    // the payment came through, if any.
+    Receive {
+        payload: HopPayload,
+        path_id: Option<Vec<u8>>,
+    },
+}
+
+// Decrypts our layer of the onion of `htlc`, with the secret key of our
+// node id.
+pub fn peel_onion(
+    node_secret: &SecretKey,
+    htlc: &UpdateAddHtlc,
+) -> Result<PeeledPayment, OnionError> {
+    let packet = OnionPacket::from_bytes(&htlc.onion_routing_packet)?;
+    // Inside a blinded path, the onion is encrypted to our blinded node id.
+    let blinding = match &htlc.blinding_point {
+        Some(path_key) => {
+            let path_key = PublicKey::from_slice(&path_key.0)?;
+            Some((path_key, sphinx::shared_secret(&path_key, node_secret)))
+        }
+        None => None,
+    };
+    let secret = match &blinding {
+        Some((_, shared_secret)) => blinding::blinded_node_secret(node_secret, shared_secret)?,
+        None => *node_secret,
+    };
+    let peeled = sphinx::peel(&secret, &packet, &htlc.payment_hash)?;
+    let payload = <HopPayload as WireItem>::decode(&mut &peeled.payload[..])
+        .map_err(|_| OnionError::InvalidPayload)?;
+    let blinding = match (blinding, &payload.current_path_key) {
+        (Some(_), Some(_)) => return Err(OnionError::InvalidPayload),
+        (None, Some(path_key)) => {
+            let path_key = PublicKey::from_slice(path_key)?;
+            Some((path_key, sphinx::shared_secret(&path_key, node_secret)))
+        }
+        (blinding, None) => blinding,
+    };
+    let (path_key, shared_secret) = match blinding {
+        Some(blinding) => blinding,
+        None => return unblinded(htlc, payload, peeled.next),
+    };
+
+    let data: EncryptedData = blinding::decrypt_data(
+        &shared_secret,
+        payload
+            .encrypted_recipient_data
+            .as_ref()
+            .ok_or(OnionError::InvalidEncryptedData)?,
+    )?;
+    if data.allowed_features.iter().flatten().any(|b| *b != 0) {
+        return Err(OnionError::BlindedPathViolation);
+    }
+    if let Some(constraints) = &data.payment_constraints {
+        if htlc.cltv_expiry > constraints.max_cltv_expiry
+            || htlc.amount_msat < constraints.htlc_minimum_msat
+        {
+            return Err(OnionError::BlindedPathViolation);
+        }
+    }
+    let next = match peeled.next {
+        Some(next) => next,
+        None => {
+            if payload.amt_to_forward.is_none()
+                || payload.outgoing_cltv_value.is_none()
+                || payload.total_amount_msat.is_none()
+            {
+                return Err(OnionError::InvalidPayload);
+            }
+            return Ok(PeeledPayment::Receive {
+                payload,
+                path_id: data.path_id,
+            });
+        }
+    };
+    // Hops inside the path take everything from the encrypted data.
+    if payload.amt_to_forward.is_some()
+        || payload.outgoing_cltv_value.is_some()
+        || payload.total_amount_msat.is_some()
+    {
+        return Err(OnionError::InvalidPayload);
+    }
+    let relay = data
+        .payment_relay
+        .as_ref()
+        .ok_or(OnionError::InvalidEncryptedData)?;
+    let next_path_key = match data.next_path_key_override {
+        Some(path_key) => path_key,
+        None => blinding::next_path_key(&path_key, &shared_secret)?.serialize(),
+    };
+    let mut onion_routing_packet = Box::new([0; 1366]);
+    onion_routing_packet.copy_from_slice(&next.to_bytes());
+    Ok(PeeledPayment::Forward {
+        short_channel_id: data.short_channel_id.ok_or(OnionError::MissingNextHop)?,
+        amount_msat: relay
+            .amount_to_forward(htlc.amount_msat)
+            .ok_or(OnionError::BlindedPathViolation)?,
+        cltv_expiry: htlc
+            .cltv_expiry
+            .checked_sub(relay.cltv_expiry_delta)
+            .ok_or(OnionError::BlindedPathViolation)?,
+        onion_routing_packet,
+        blinding_point: Some(RawPublicKey(next_path_key)),
+    })
+}
+
+fn unblinded(
+    htlc: &UpdateAddHtlc,
+    payload: HopPayload,
+    next: Option<OnionPacket>,
+) -> Result<PeeledPayment, OnionError> {
+    if payload.encrypted_recipient_data.is_some() {
+        return Err(OnionError::InvalidPayload);
+    }
+    let (amount_msat, cltv_expiry) = match (payload.amt_to_forward, payload.outgoing_cltv_value) {
+        (Some(amount), Some(cltv)) => (amount, cltv),
+        _ => return Err(OnionError::InvalidPayload),
+    };
+    let next = match next {
+        Some(next) => next,
+        None => {
+            return Ok(PeeledPayment::Receive {
+                payload,
+                path_id: None,
+            })
+        }
+    };
+    if amount_msat > htlc.amount_msat || cltv_expiry > htlc.cltv_expiry {
+        return Err(OnionError::InvalidPayload);
+    }
+    let mut onion_routing_packet = Box::new([0; 1366]);
+    onion_routing_packet.copy_from_slice(&next.to_bytes());
+    Ok(PeeledPayment::Forward {
+        short_channel_id: payload.short_channel_id.ok_or(OnionError::MissingNextHop)?,
+        amount_msat,
+        cltv_expiry,
+        onion_routing_packet,
+        blinding_point: None,
+    })
+}
+
+// The route of the BOLT #4 route blinding test vectors: Eve gives out a
+// blinded path through Dave, Alice pays it through her own blinded path
+// through Bob and Carol.
+#[test]
+fn route_blinding_spec_vectors() {
+    use crate::items::blinded_path::BlindedHop;
+    use crate::items::channel_id::ChannelId;
+    use secp256k1::Secp256k1;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    let secp = Secp256k1::new();
+    let secret = |b: u8| SecretKey::from_slice(&[b; 32]).unwrap();
+    let (bob, carol, dave, eve) = (secret(0x42), secret(0x43), secret(0x44), secret(0x45));
+    let node_id = |s: &SecretKey| PublicKey::from_secret_key(&secp, s);
+
+    // The `encoded_tlvs` of the vector, padding and unknown odd records
+    // included. Bob's carries type 561, Eve's type 65535 and feature 113.
+    let bob_data = unhex(
+        "011a0000000000000000000000000000000000000000000000000000020800000000000006c10a0800240000\
+         009627100c06000b69e505dc0e00fd023103123456",
+    );
+    let carol_data = unhex(
+        "020800000000000004510821031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd07\
+         8f0a0800300000006401f40c06000b69c105dc0e00",
+    );
+    // Dave's is rebuilt from the vector's decoded fields.
+    let dave_data = unhex(
+        "01230000000000000000000000000000000000000000000000000000000000000000000000020800000000\
+         000002310a060090000000fa0c06000b699105dc0e00",
+    );
+    let eve_data = unhex(
+        "011a00000000000000000000000000000000000000000000000000000604deadbeef0c06000b690105dc0e0f\
+         020000000000000000000000000000fdffff0206c1",
+    );
+
+    let eve_path = blinding::create_raw_blinded_path(
+        &secret(0x01),
+        &[(node_id(&dave), dave_data), (node_id(&eve), eve_data)],
+    )
+    .unwrap();
+    assert_eq!(
+        hex(&eve_path.first_path_key),
+        "031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f"
+    );
+    let alice_path = blinding::create_raw_blinded_path(
+        &secret(0x02),
+        &[(node_id(&bob), bob_data), (node_id(&carol), carol_data)],
+    )
+    .unwrap();
+    let blinded_ids = alice_path
+        .hops
+        .iter()
+        .chain(&eve_path.hops)
+        .map(|h| hex(&h.blinded_node_id))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        blinded_ids,
+        [
+            "03da173ad2aee2f701f17e59fbd16cb708906d69838a5f088e8123fb36e89a2c25",
+            "02e466727716f044290abf91a14a6d90e87487da160c2a3cbd0d465d7a78eb83a7",
+            "036861b366f284f0a11738ffbf7eda46241a8977592878fe3175ae1d1e4754eccf",
+            "021982a48086cb8984427d3727fe35a03d396b234f0701f5249daa12e8105c8dae",
+        ]
+    );
+    let encrypted = |hop: &BlindedHop| hex(&hop.encrypted_recipient_data.value);
+    assert_eq!(
+        encrypted(&alice_path.hops[0]),
+        "cd4100ff9c09ed28102b210ac73aa12d63e90852cebc496c49f57c49982088b49f2e70b99287fdee0aa58a\
+         a39913ab405813b999f66783aa2fe637b3cda91ffc0913c30324e2c6ce327e045183e4bffecb"
+    );
+    assert_eq!(
+        encrypted(&alice_path.hops[1]),
+        "cc0f16524fd7f8bb0b1d8d40ad71709ef140174c76faa574cac401bb8992fef76c4d004aa485dd599ed1cf\
+         2715f57ff62da5aaec5d7b10d59b04d8a9d77e472b9b3ecc2179334e411be22fa4c02b467c7e"
+    );
+    assert_eq!(
+        encrypted(&eve_path.hops[1]),
+        "da1a7e5f7881219884beae6ae68971de73bab4c3055d9865b1afb60724a2e4d3f0489ad884f7f3f7714920\
+         9f0df51efd6b276294a02e3949c7254fbc8b5cab58212d9a78983e1cf86fe218b30c4ca8f6d8"
+    );
+
+    let mut path = alice_path.clone();
+    path.hops.extend(eve_path.hops.iter().cloned());
+    let hops = blinded_path_payloads(
+        &path,
+        &node_id(&bob),
+        MilliSatoshi(100_000),
+        CltvExpiry(747_500),
+        MilliSatoshi(150_000),
+    )
+    .unwrap();
+    let payment_hash = [0x42; 32];
+    let mut htlc = UpdateAddHtlc {
+        channel_id: ChannelId([0; 32]),
+        id: 0,
+        amount_msat: MilliSatoshi(110_700),
+        payment_hash,
+        cltv_expiry: CltvExpiry(747_728),
+        onion_routing_packet: construct_onion(&secret(0x03), &hops, &payment_hash).unwrap(),
+        blinding_point: None,
+    };
+
+    let expected = [(1729, 747_692), (1105, 747_644), (561, 747_500)];
+    for (node_secret, (scid, cltv)) in [&bob, &carol, &dave].iter().zip(expected.iter()) {
+        match peel_onion(node_secret, &htlc).unwrap() {
+            PeeledPayment::Forward {
+                short_channel_id,
+                amount_msat,
+                cltv_expiry,
+                onion_routing_packet,
+                blinding_point,
+            } => {
+                assert_eq!(short_channel_id, ShortChannelId(*scid));
+                assert_eq!(cltv_expiry, CltvExpiry(*cltv));
+                assert!(amount_msat < htlc.amount_msat);
+                htlc.amount_msat = amount_msat;
+                htlc.cltv_expiry = cltv_expiry;
+                htlc.onion_routing_packet = *onion_routing_packet;
+                htlc.blinding_point = blinding_point;
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        if *node_secret == &carol {
+            assert_eq!(
+                htlc.blinding_point,
+                Some(RawPublicKey(eve_path.first_path_key))
+            );
+        }
+    }
+    assert!(htlc.amount_msat >= MilliSatoshi(100_000));
+    // Eve's blinded hop cannot be unwrapped without the path key.
+    let mut unblinded = htlc.clone();
+    unblinded.blinding_point = None;
+    assert!(peel_onion(&eve, &unblinded).is_err());
+    // Eve's data allows feature 113, which we do not support.
+    assert_eq!(
+        peel_onion(&eve, &htlc).unwrap_err(),
+        OnionError::BlindedPathViolation
+    );
+
+    // Paying below the htlc minimum of the path fails at the first hop.
+    let mut low = htlc.clone();
+    low.amount_msat = MilliSatoshi(1_499);
+    low.blinding_point = None;
+    low.cltv_expiry = CltvExpiry(747_728);
+    low.onion_routing_packet = construct_onion(&secret(0x03), &hops, &payment_hash).unwrap();
+    assert_eq!(
+        peel_onion(&bob, &low).unwrap_err(),
+        OnionError::BlindedPathViolation
+    );
+}
@@ -0,0 +1,233 @@
+// The Sphinx onion of BOLT #4, shared by payments and onion messages. Each
+// hop can only decrypt its own payload and learns nothing of the route but
+// its position relative to its neighbours.
+
+use super::OnionError;
+use crate::{read_varint, write_varint};
+use alloc::{vec, vec::Vec};
+use bitcoin_hashes::{hmac, sha256, Hash as _, HashEngine as _};
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use secp256k1::ecdh::SharedSecret;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+
+pub const VERSION: u8 = 0;
+pub const HMAC_LEN: usize = 32;
+
+// HMAC-SHA256 keyed by the key type, e.g. "rho" or "mu".
+pub fn generate_key(key_type: &[u8], secret: &[u8; 32]) -> [u8; 32] {
+    compute_hmac(key_type, &[secret])
+}
+
+fn compute_hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(key);
+    for part in parts {
+        engine.input(part);
+    }
+    hmac::Hmac::from_engine(engine).to_byte_array()
+}
+
+// SHA256 of the compressed ECDH point.
+pub fn shared_secret(pubkey: &PublicKey, secret: &SecretKey) -> [u8; 32] {
+    SharedSecret::new(pubkey, secret).secret_bytes()
+}
+
+// The tweak taking a hop's ephemeral key (or path key) to the next hop's.
+pub fn blinding_factor(pubkey: &PublicKey, shared_secret: &[u8; 32]) -> Result<Scalar, OnionError> {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&pubkey.serialize());
+    engine.input(shared_secret);
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array())
+        .map_err(|_| OnionError::InvalidKey)
+}
+
+// XORs `buf` with the ChaCha20 stream of `key` and a zero nonce.
+pub fn xor_stream(key: &[u8; 32], buf: &mut [u8]) {
+    ChaCha20::new(key.into(), &[0; 12].into()).apply_keystream(buf)
+}
+
+fn stream(key: &[u8; 32], len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    xor_stream(key, &mut buf);
+    buf
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnionPacket {
+    pub version: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub public_key: [u8; 33],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub hop_payloads: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub hmac: [u8; 32],
+}
+impl OnionPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(1 + 33 + self.hop_payloads.len() + HMAC_LEN);
+        res.push(self.version);
+        res.extend_from_slice(&self.public_key);
+        res.extend_from_slice(&self.hop_payloads);
+        res.extend_from_slice(&self.hmac);
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OnionError> {
+        if bytes.len() < 1 + 33 + HMAC_LEN || bytes[0] != VERSION {
+            return Err(OnionError::InvalidPacket);
+        }
+        let (hop_payloads, hmac) = bytes[34..].split_at(bytes.len() - 34 - HMAC_LEN);
+        let mut packet = OnionPacket {
+            version: bytes[0],
+            public_key: [0; 33],
+            hop_payloads: hop_payloads.to_vec(),
+            hmac: [0; 32],
+        };
+        packet.public_key.copy_from_slice(&bytes[1..34]);
+        packet.hmac.copy_from_slice(hmac);
+        Ok(packet)
+    }
+}
+
+// Builds an onion with `packet_len` bytes of hop payloads. Each payload is
+// the TLV stream meant for its hop, which is encrypted to its public key;
+// `associated_data` is committed to by every hmac (the payment hash for
+// payments, nothing for onion messages).
+pub fn construct(
+    session_key: &SecretKey,
+    hops: &[(PublicKey, Vec<u8>)],
+    packet_len: usize,
+    associated_data: &[u8],
+) -> Result<OnionPacket, OnionError> {
+    if hops.is_empty() {
+        return Err(OnionError::InvalidPayload);
+    }
+    let secp = Secp256k1::new();
+    let mut ephemeral_secret = *session_key;
+    let mut secrets = Vec::with_capacity(hops.len());
+    for (pubkey, _) in hops {
+        let ephemeral = PublicKey::from_secret_key(&secp, &ephemeral_secret);
+        let secret = shared_secret(pubkey, &ephemeral_secret);
+        ephemeral_secret = ephemeral_secret.mul_tweak(&blinding_factor(&ephemeral, &secret)?)?;
+        secrets.push(secret);
+    }
+    let payloads = hops
+        .iter()
+        .map(|(_, payload)| {
+            let mut res = Vec::with_capacity(payload.len() + 3);
+            write_varint(payload.len() as u64, &mut res).expect("writing to a Vec");
+            res.extend_from_slice(payload);
+            res
+        })
+        .collect::<Vec<_>>();
+    if payloads.iter().map(|p| p.len() + HMAC_LEN).sum::<usize>() > packet_len {
+        return Err(OnionError::PayloadTooLarge);
+    }
+
+    let filler = filler(&payloads, &secrets, packet_len);
+    let mut mix_header = stream(
+        &generate_key(b"pad", &session_key.secret_bytes()),
+        packet_len,
+    );
+    let mut next_hmac = [0; 32];
+    for (i, (payload, secret)) in payloads.iter().zip(&secrets).enumerate().rev() {
+        let shift = payload.len() + HMAC_LEN;
+        mix_header.copy_within(..packet_len - shift, shift);
+        mix_header[..payload.len()].copy_from_slice(payload);
+        mix_header[payload.len()..shift].copy_from_slice(&next_hmac);
+        xor_stream(&generate_key(b"rho", secret), &mut mix_header);
+        if i == payloads.len() - 1 {
+            mix_header[packet_len - filler.len()..].copy_from_slice(&filler);
+        }
+        next_hmac = compute_hmac(
+            &generate_key(b"mu", secret),
+            &[&mix_header, associated_data],
+        );
+    }
+    Ok(OnionPacket {
+        version: VERSION,
+        public_key: PublicKey::from_secret_key(&secp, session_key).serialize(),
+        hop_payloads: mix_header,
+        hmac: next_hmac,
+    })
+}
+
+// The bytes each hop but the last will see shifted in at the end of the
+// packet, precomputed so that the last hmac commits to them.
+fn filler(payloads: &[Vec<u8>], secrets: &[[u8; 32]], packet_len: usize) -> Vec<u8> {
+    let hops = &payloads[..payloads.len() - 1];
+    let mut filler = vec![0; hops.iter().map(|p| p.len() + HMAC_LEN).sum()];
+    let mut start = packet_len;
+    for (payload, secret) in hops.iter().zip(secrets) {
+        let shift = payload.len() + HMAC_LEN;
+        let stream = stream(&generate_key(b"rho", secret), packet_len + shift);
+        for (f, s) in filler.iter_mut().zip(&stream[start..]) {
+            *f ^= s;
+        }
+        start -= shift;
+    }
+    filler
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PeeledPacket {
+    // Our TLV payload.
+    pub payload: Vec<u8>,
+    pub shared_secret: [u8; 32],
+    // The packet for the next hop, or None if we are the last one.
+    pub next: Option<OnionPacket>,
+}
+
+pub fn peel(
+    secret: &SecretKey,
+    packet: &OnionPacket,
+    associated_data: &[u8],
+) -> Result<PeeledPacket, OnionError> {
+    if packet.version != VERSION {
+        return Err(OnionError::InvalidPacket);
+    }
+    let ephemeral = PublicKey::from_slice(&packet.public_key)?;
+    let shared_secret = shared_secret(&ephemeral, secret);
+    let hmac = compute_hmac(
+        &generate_key(b"mu", &shared_secret),
+        &[&packet.hop_payloads, associated_data],
+    );
+    if hmac != packet.hmac {
+        return Err(OnionError::InvalidHmac);
+    }
+
+    let packet_len = packet.hop_payloads.len();
+    let mut buf = packet.hop_payloads.clone();
+    buf.resize(2 * packet_len, 0);
+    xor_stream(&generate_key(b"rho", &shared_secret), &mut buf);
+    let mut r = &buf[..];
+    let len = read_varint(&mut r).map_err(|_| OnionError::InvalidPayload)? as usize;
+    let start = buf.len() - r.len();
+    let end = start
+        .checked_add(len)
+        .filter(|end| len > 0 && end + HMAC_LEN <= packet_len)
+        .ok_or(OnionError::InvalidPayload)?;
+    let mut hmac = [0; 32];
+    hmac.copy_from_slice(&buf[end..end + HMAC_LEN]);
+    let next = if hmac == [0; 32] {
+        None
+    } else {
+        let shift = end + HMAC_LEN;
+        let public_key = ephemeral.mul_tweak(
+            &Secp256k1::new(),
+            &blinding_factor(&ephemeral, &shared_secret)?,
+        )?;
+        Some(OnionPacket {
+            version: VERSION,
+            public_key: public_key.serialize(),
+            hop_payloads: buf[shift..shift + packet_len].to_vec(),
+            hmac,
+        })
+    };
+    Ok(PeeledPacket {
+        payload: buf[start..end].to_vec(),
+        shared_secret,
+        next,
+    })
+}
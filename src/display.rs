@@ -0,0 +1,276 @@
+// The one-line `Display` of wire messages, in the style of lnd's logs, e.g.
+// `CreateSession(blob_type=[No-FlagReward|FlagCommitOutputs], ...)`.
+use crate::items::channel_id::ChannelId;
+use crate::items::channel_type::ChannelType;
+use crate::items::expiry::{BlockHeight, CltvExpiry, CltvExpiryDelta};
+use crate::items::feature::{FeatureSet, RawFeatureVector};
+use crate::items::fees::{MilliSatoshi, SatPerKWeight};
+use crate::items::hash::Hash;
+use crate::items::key::{RawPublicKey, RawSchnorrSignature, RawSignature, RawXOnlyPublicKey};
+use crate::items::prefixed::{Counted, LenPrefixed};
+use crate::items::short_channel_id::ShortChannelId;
+use crate::items::{Buffer, TLVBuffer};
+use crate::watchtower::items::blob;
+use crate::watchtower::items::error::{
+    BaseError, CreateSessionError, DeleteSessionError, ErrorCode, StateUpdateError,
+};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+
+// Byte strings longer than this are cut to a prefix of `BYTES_PREFIX` bytes
+// followed by their length.
+const MAX_BYTES: usize = 64;
+const BYTES_PREFIX: usize = 16;
+
+// How a field is shown in the `Display` of a message.
+pub(crate) trait DisplayField {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    // How an `Option` of this is shown when it is None.
+    fn fmt_none(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "none")
+    }
+}
+
+pub(crate) fn fmt_bytes(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let shown = if bytes.len() > MAX_BYTES {
+        &bytes[..BYTES_PREFIX]
+    } else {
+        bytes
+    };
+    for b in shown {
+        write!(f, "{:02x}", b)?;
+    }
+    if shown.len() < bytes.len() {
+        write!(f, "...({} bytes)", bytes.len())?;
+    }
+    Ok(())
+}
+
+// Writes `name=value`, after a separator unless it is the first field.
+pub(crate) fn fmt_named<T: DisplayField>(
+    f: &mut fmt::Formatter<'_>,
+    first: &mut bool,
+    name: &str,
+    value: &T,
+) -> fmt::Result {
+    if !core::mem::replace(first, false) {
+        write!(f, ", ")?;
+    }
+    write!(f, "{}=", name)?;
+    value.fmt_field(f)
+}
+
+macro_rules! display_field_as_display {
+    ($($ty:ty,)*) => {
+        $(
+            impl DisplayField for $ty {
+                fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt::Display::fmt(self, f)
+                }
+            }
+        )*
+    };
+}
+
+display_field_as_display!(
+    u8,
+    u16,
+    u32,
+    u64,
+    i8,
+    i16,
+    i32,
+    i64,
+    ShortChannelId,
+    MilliSatoshi,
+    SatPerKWeight,
+    blob::Type,
+    ErrorCode,
+    ChannelType,
+);
+
+macro_rules! display_field_as_number {
+    ($($ty:ty,)*) => {
+        $(
+            impl DisplayField for $ty {
+                fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", self.0)
+                }
+            }
+        )*
+    };
+}
+
+display_field_as_number!(BlockHeight, CltvExpiry, CltvExpiryDelta,);
+
+// The error codes of replies, where None is `CodeOK`.
+macro_rules! display_field_as_error_code {
+    ($($ty:ty,)*) => {
+        $(
+            impl DisplayField for $ty {
+                fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", ErrorCode::from(*self))
+                }
+
+                fn fmt_none(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", ErrorCode::from(BaseError::OK))
+                }
+            }
+        )*
+    };
+}
+
+display_field_as_error_code!(CreateSessionError, StateUpdateError, DeleteSessionError,);
+
+impl DisplayField for () {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "()")
+    }
+}
+
+impl<const N: usize> DisplayField for [u8; N] {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_bytes(self, f)
+    }
+}
+
+impl DisplayField for Vec<u8> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_bytes(self, f)
+    }
+}
+
+macro_rules! display_field_as_bytes {
+    ($($ty:ty,)*) => {
+        $(
+            impl DisplayField for $ty {
+                fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    fmt_bytes(&self.0, f)
+                }
+            }
+        )*
+    };
+}
+
+display_field_as_bytes!(
+    Hash,
+    ChannelId,
+    RawPublicKey,
+    RawXOnlyPublicKey,
+    RawSignature,
+    RawSchnorrSignature,
+);
+
+impl<P, T: DisplayField> DisplayField for LenPrefixed<P, T> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt_field(f)
+    }
+}
+
+impl<T: Borrow<[u8]>> DisplayField for Buffer<T> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_bytes(self.borrow(), f)
+    }
+}
+
+impl<T: Borrow<[u8]>> DisplayField for TLVBuffer<T> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_bytes(self.borrow(), f)
+    }
+}
+
+impl<F: FeatureSet> DisplayField for RawFeatureVector<F> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// A list of fields, e.g. the `#[count_prefix = P]` fields of `wire_message!`.
+pub(crate) struct Items<'a, T>(pub(crate) &'a [T]);
+impl<T: DisplayField> DisplayField for Items<'_, T> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, item) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            item.fmt_field(f)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl<P, T: DisplayField> DisplayField for Counted<P, Vec<T>> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Items(&self.items).fmt_field(f)
+    }
+}
+
+impl<T: DisplayField> DisplayField for Option<T> {
+    fn fmt_field(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(value) => value.fmt_field(f),
+            None => T::fmt_none(f),
+        }
+    }
+}
+
+#[test]
+fn one_line() {
+    use crate::items::fees::SatPerKWeight;
+    use crate::watchtower::messages::{AnyWatchtowerMessage, CreateSession, StateUpdate};
+    use alloc::string::ToString;
+
+    let msg = CreateSession {
+        blob_type: blob::Type::from(blob::KnownType::TypeRewardCommit),
+        max_updates: 1024,
+        reward_base: 0,
+        reward_rate: 0,
+        sweep_fee_rate: SatPerKWeight(12500),
+    };
+    assert_eq!(
+        msg.to_string(),
+        "CreateSession(blob_type=[FlagReward|FlagCommitOutputs], max_updates=1024, \
+         reward_base=0, reward_rate=0, sweep_fee_rate=12500 sat/kw)"
+    );
+
+    let msg = StateUpdate::<Vec<u8>> {
+        seq_num: 1,
+        last_applied: 0,
+        is_complete: 0,
+        hint: [0xab; 16],
+        encrypted_blob: Buffer::Other((0..100).collect()),
+    };
+    assert_eq!(
+        msg.to_string(),
+        "StateUpdate(seq_num=1, last_applied=0, is_complete=0, \
+         hint=abababababababababababababababab, \
+         encrypted_blob=000102030405060708090a0b0c0d0e0f...(100 bytes))"
+    );
+
+    let reply = crate::watchtower::messages::StateUpdateReply {
+        code: None,
+        last_applied: 1,
+    };
+    assert_eq!(
+        reply.to_string(),
+        "StateUpdateReply(code=CodeOK, last_applied=1)"
+    );
+
+    let any = AnyWatchtowerMessage::<Vec<u8>>::Init(crate::watchtower::messages::Init {
+        conn_features: [crate::watchtower::items::feature::Feature::AltruistSessionsOptional]
+            .iter()
+            .cloned()
+            .collect(),
+        chain_hash: Hash([0; 32]),
+    });
+    assert_eq!(
+        any.to_string(),
+        alloc::format!(
+            "Init(conn_features=[altruist-sessions/optional], chain_hash={})",
+            "00".repeat(32)
+        )
+    );
+}
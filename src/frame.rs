@@ -0,0 +1,94 @@
+use crate::io::Read;
+use crate::{DecodeLimits, LimitError, WireMessageReader, WireRead};
+use bytes::{Buf, Bytes};
+
+// A reader over the frame being decoded by `DecodeLimits::decode_bytes`.
+// Byte strings are taken with `copy_to_bytes`, which for `Bytes` splits off
+// a slice sharing its allocation and for other `Buf`s copies just them.
+struct BufReader<B> {
+    buf: B,
+    limits: DecodeLimits,
+}
+impl<B: Buf> Read for BufReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        let n = self.buf.remaining().min(buf.len());
+        self.buf.copy_to_slice(&mut buf[..n]);
+        Ok(n)
+    }
+}
+impl<B: Buf> WireRead for BufReader<B> {
+    fn limits(&self) -> DecodeLimits {
+        self.limits
+    }
+
+    fn read_bytes(&mut self, len: u64) -> crate::io::Result<Bytes> {
+        if len > self.limits.max_buffer_len as u64 {
+            return Err(LimitError::BufferTooLarge(len).into());
+        }
+        if len > self.buf.remaining() as u64 {
+            return Err(crate::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(self.buf.copy_to_bytes(len as usize))
+    }
+}
+
+impl DecodeLimits {
+    // Decodes a message from the rest of `frame` under these limits. Byte
+    // strings decoded as `Bytes`, `Buffer` or `TLVBuffer` from a `Bytes`
+    // frame are slices of it, sharing its allocation.
+    pub fn decode_bytes<T, B>(&self, frame: B, check_type: bool) -> crate::io::Result<T>
+    where
+        T: WireMessageReader,
+        B: Buf,
+    {
+        if frame.remaining() > self.max_message_size {
+            return Err(LimitError::MessageTooLarge.into());
+        }
+        let mut reader = BufReader {
+            buf: frame,
+            limits: *self,
+        };
+        T::decode_wire(&mut reader, check_type)
+    }
+}
+
+#[test]
+fn shared_buffers() {
+    use crate::items::Buffer;
+    use crate::watchtower::messages::StateUpdate;
+    use crate::WireItem;
+    use core::borrow::Borrow;
+
+    let mut bytes = vec![2, 0x5c, 0, 1, 0, 0, 1];
+    bytes.extend_from_slice(&[9; 16]);
+    bytes.extend_from_slice(&[0xfd, 0x01, 0x00]);
+    bytes.extend((0..256).map(|i| i as u8));
+    let frame = Bytes::from(bytes);
+
+    let msg: StateUpdate<Bytes> = DecodeLimits::DEFAULT
+        .decode_bytes(frame.clone(), true)
+        .unwrap();
+    let blob: &[u8] = msg.encrypted_blob.borrow();
+    assert_eq!(blob, &frame[26..]);
+    assert!(matches!(msg.encrypted_blob, Buffer::Shared(_)));
+    assert_eq!(blob.as_ptr(), frame[26..].as_ptr());
+
+    // From other readers and `Buf`s, they are copied.
+    let copy = <Buffer<Bytes> as WireItem>::decode(&mut &frame[23..]).unwrap();
+    assert_eq!(Borrow::<[u8]>::borrow(&copy), &frame[26..]);
+    assert_ne!(Borrow::<[u8]>::borrow(&copy).as_ptr(), frame[26..].as_ptr());
+    let msg: StateUpdate<Bytes> = DecodeLimits::DEFAULT
+        .decode_bytes(&frame[..], true)
+        .unwrap();
+    let blob: &[u8] = msg.encrypted_blob.borrow();
+    assert_eq!(blob, &frame[26..]);
+    assert_ne!(blob.as_ptr(), frame[26..].as_ptr());
+
+    let limits = DecodeLimits {
+        max_message_size: frame.len() - 1,
+        ..DecodeLimits::DEFAULT
+    };
+    assert!(limits
+        .decode_bytes::<StateUpdate<Bytes>, _>(frame, true)
+        .is_err());
+}
@@ -0,0 +1,234 @@
+// The readers and writers items are encoded to and decoded from. With the
+// `std` feature these are `std::io`'s own. Without it they are a minimal
+// stand-in with the same names and behaviour, covering what the items and
+// the derived messages use.
+#[cfg(feature = "std")]
+pub use std::io::{copy, sink, Error, ErrorKind, Read, Result, Sink, Take, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::*;
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        InvalidInput,
+        InvalidData,
+        UnexpectedEof,
+        WriteZero,
+        Other,
+    }
+    impl fmt::Display for ErrorKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ErrorKind::InvalidInput => write!(f, "invalid input parameter"),
+                ErrorKind::InvalidData => write!(f, "invalid data"),
+                ErrorKind::UnexpectedEof => write!(f, "unexpected end of file"),
+                ErrorKind::WriteZero => write!(f, "write zero"),
+                ErrorKind::Other => write!(f, "other error"),
+            }
+        }
+    }
+
+    pub struct Error {
+        kind: ErrorKind,
+        error: Option<Box<dyn core::error::Error + Send + Sync>>,
+    }
+    impl Error {
+        pub fn new<E>(kind: ErrorKind, error: E) -> Self
+        where
+            E: Into<Box<dyn core::error::Error + Send + Sync>>,
+        {
+            Error {
+                kind,
+                error: Some(error.into()),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+
+        pub fn get_ref(&self) -> Option<&(dyn core::error::Error + Send + Sync + 'static)> {
+            self.error.as_deref()
+        }
+
+        pub fn into_inner(self) -> Option<Box<dyn core::error::Error + Send + Sync>> {
+            self.error
+        }
+    }
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error { kind, error: None }
+        }
+    }
+    impl fmt::Debug for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.error {
+                Some(error) => write!(f, "{:?}: {:?}", self.kind, error),
+                None => write!(f, "{:?}", self.kind),
+            }
+        }
+    }
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.error {
+                Some(error) => write!(f, "{}", error),
+                None => write!(f, "{}", self.kind),
+            }
+        }
+    }
+    impl core::error::Error for Error {}
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf)? {
+                    0 => return Err(ErrorKind::UnexpectedEof.into()),
+                    n => buf = &mut buf[n..],
+                }
+            }
+            Ok(())
+        }
+
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let mut chunk = [0_u8; 512];
+            let mut count = 0;
+            loop {
+                match self.read(&mut chunk)? {
+                    0 => return Ok(count),
+                    n => {
+                        buf.extend_from_slice(&chunk[..n]);
+                        count += n;
+                    }
+                }
+            }
+        }
+
+        fn take(self, limit: u64) -> Take<Self>
+        where
+            Self: Sized,
+        {
+            Take { inner: self, limit }
+        }
+
+        fn by_ref(&mut self) -> &mut Self
+        where
+            Self: Sized,
+        {
+            self
+        }
+    }
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = self.len().min(buf.len());
+            let (data, rest) = self.split_at(n);
+            buf[..n].copy_from_slice(data);
+            *self = rest;
+            Ok(n)
+        }
+    }
+
+    pub struct Take<R> {
+        inner: R,
+        limit: u64,
+    }
+    impl<R> Take<R> {
+        pub fn limit(&self) -> u64 {
+            self.limit
+        }
+
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+    impl<R: Read> Read for Take<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let max = (buf.len() as u64).min(self.limit) as usize;
+            let n = self.inner.read(&mut buf[..max])?;
+            self.limit -= n as u64;
+            Ok(n)
+        }
+    }
+
+    pub trait Write {
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn flush(&mut self) -> Result<()>;
+
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => return Err(ErrorKind::WriteZero.into()),
+                    n => buf = &buf[n..],
+                }
+            }
+            Ok(())
+        }
+    }
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    pub struct Sink;
+    pub fn sink() -> Sink {
+        Sink
+    }
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    pub fn copy<R, W>(r: &mut R, w: &mut W) -> Result<u64>
+    where
+        R: Read + ?Sized,
+        W: Write + ?Sized,
+    {
+        let mut chunk = [0_u8; 512];
+        let mut count = 0;
+        loop {
+            match r.read(&mut chunk)? {
+                0 => return Ok(count),
+                n => {
+                    w.write_all(&chunk[..n])?;
+                    count += n as u64;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::io::Write;
+use crate::items::prefixed::LenPrefixed;
+use crate::items::short_channel_id::ShortChannelId;
+use crate::{WireItem, WireRead};
+use alloc::vec::Vec;
+
+// The introduction node of a blinded path: either a node id, or a channel
+// and the direction to take it in, in which case the introduction node is
+// the channel's node_1 (direction 0) or node_2 (direction 1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntroductionNode {
+    NodeId(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] [u8; 33]),
+    DirectedShortChannelId {
+        direction: u8,
+        short_channel_id: ShortChannelId,
+    },
+}
+impl WireItem for IntroductionNode {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        match self {
+            IntroductionNode::NodeId(id) => id.encode(w),
+            IntroductionNode::DirectedShortChannelId {
+                direction,
+                short_channel_id,
+            } => Ok(direction.encode(w)? + short_channel_id.encode(w)?),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            IntroductionNode::NodeId(_) => 33,
+            IntroductionNode::DirectedShortChannelId { .. } => 9,
+        }
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let prefix = u8::decode(r)?;
+        match prefix {
+            0 | 1 => Ok(IntroductionNode::DirectedShortChannelId {
+                direction: prefix,
+                short_channel_id: WireItem::decode(r)?,
+            }),
+            2 | 3 => {
+                let mut id = [prefix; 33];
+                r.read_exact(&mut id[1..])?;
+                Ok(IntroductionNode::NodeId(id))
+            }
+            _ => Err(crate::io::ErrorKind::InvalidData.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlindedHop {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub blinded_node_id: [u8; 33],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub encrypted_recipient_data: LenPrefixed<u16, Vec<u8>>,
+}
+impl WireItem for BlindedHop {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Ok(self.blinded_node_id.encode(w)? + self.encrypted_recipient_data.encode(w)?)
+    }
+
+    fn encoded_len(&self) -> usize {
+        33 + self.encrypted_recipient_data.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Ok(BlindedHop {
+            blinded_node_id: WireItem::decode(r)?,
+            encrypted_recipient_data: WireItem::decode(r)?,
+        })
+    }
+}
+
+// A blinded path as used in onion messages and BOLT #12: the introduction
+// node is in the clear, the following hops only by their blinded node ids.
+// The first hop is the introduction node itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlindedPath {
+    pub introduction_node: IntroductionNode,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub first_path_key: [u8; 33],
+    pub hops: Vec<BlindedHop>,
+}
+impl WireItem for BlindedPath {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        if self.hops.is_empty() || self.hops.len() > u8::MAX as usize {
+            return Err(crate::io::ErrorKind::InvalidInput.into());
+        }
+        let mut count = self.introduction_node.encode(w)?;
+        count += self.first_path_key.encode(w)?;
+        count += (self.hops.len() as u8).encode(w)?;
+        for hop in self.hops.iter() {
+            count += hop.encode(w)?;
+        }
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let hops: usize = self.hops.iter().map(WireItem::encoded_len).sum();
+        self.introduction_node.encoded_len() + 33 + 1 + hops
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let introduction_node = WireItem::decode(r)?;
+        let first_path_key = WireItem::decode(r)?;
+        let num_hops = u8::decode(r)?;
+        if num_hops == 0 {
+            return Err(crate::io::ErrorKind::InvalidData.into());
+        }
+        let mut hops = Vec::with_capacity(num_hops as usize);
+        for _ in 0..num_hops {
+            hops.push(WireItem::decode(r)?);
+        }
+        Ok(BlindedPath {
+            introduction_node,
+            first_path_key,
+            hops,
+        })
+    }
+}
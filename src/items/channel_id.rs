@@ -0,0 +1,56 @@
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+use bitcoin_hashes::{sha256, Hash as _, HashEngine as _};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ChannelId(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] pub [u8; 32]);
+impl ChannelId {
+    // The id of a channel opened with `open_channel`: the funding txid, in
+    // its serialized byte order, with the funding output index XORed into
+    // the last two bytes.
+    pub fn from_funding_outpoint(funding_txid: &[u8; 32], output_index: u16) -> Self {
+        let mut id = *funding_txid;
+        id[30] ^= (output_index >> 8) as u8;
+        id[31] ^= output_index as u8;
+        ChannelId(id)
+    }
+
+    // The id of a channel opened with `open_channel2`, from both peers'
+    // revocation basepoints.
+    pub fn v2(revocation_basepoint_a: &[u8; 33], revocation_basepoint_b: &[u8; 33]) -> Self {
+        let (lesser, greater) = if revocation_basepoint_a < revocation_basepoint_b {
+            (revocation_basepoint_a, revocation_basepoint_b)
+        } else {
+            (revocation_basepoint_b, revocation_basepoint_a)
+        };
+        let mut engine = sha256::Hash::engine();
+        engine.input(lesser);
+        engine.input(greater);
+        ChannelId(sha256::Hash::from_engine(engine).to_byte_array())
+    }
+}
+impl From<[u8; 32]> for ChannelId {
+    fn from(id: [u8; 32]) -> Self {
+        ChannelId(id)
+    }
+}
+impl WireItem for ChannelId {
+    const FIXED_SIZE: Option<usize> = <[u8; 32]>::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        <[u8; 32]>::decode(r).map(ChannelId)
+    }
+}
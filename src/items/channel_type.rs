@@ -0,0 +1,320 @@
+use crate::io::Write;
+use crate::items::feature::{
+    ChannelTypeFeature, ChannelTypeFeatures, Feature, FeatureSet, InitFeatures,
+};
+use crate::{TLVWireItem, WireRead};
+use core::convert::TryFrom;
+
+// The commitment format of a channel type. Each one is a fixed set of
+// `channel_type` bits, which `scid_alias` and `zero_conf` may be added to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommitmentType {
+    // option_static_remotekey
+    StaticRemoteKey,
+
+    // option_anchors_zero_fee_htlc_tx and option_static_remotekey
+    AnchorsZeroFeeHtlcTx,
+
+    // option_simple_taproot
+    SimpleTaproot,
+}
+impl CommitmentType {
+    pub fn features(&self) -> &'static [ChannelTypeFeature] {
+        use ChannelTypeFeature::*;
+        match self {
+            CommitmentType::StaticRemoteKey => &[StaticRemoteKeyRequired],
+            CommitmentType::AnchorsZeroFeeHtlcTx => {
+                &[StaticRemoteKeyRequired, AnchorsZeroFeeHtlcTxRequired]
+            }
+            CommitmentType::SimpleTaproot => &[SimpleTaprootRequired],
+        }
+    }
+}
+
+// A validated `channel_type`, as sent in the TLV stream of `open_channel` and
+// `accept_channel`. Only the combinations defined in BOLT #2 can be
+// represented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelType {
+    pub commitment: CommitmentType,
+    pub scid_alias: bool,
+    pub zero_conf: bool,
+}
+impl ChannelType {
+    pub const fn new(commitment: CommitmentType) -> Self {
+        ChannelType {
+            commitment,
+            scid_alias: false,
+            zero_conf: false,
+        }
+    }
+    pub const fn with_scid_alias(mut self) -> Self {
+        self.scid_alias = true;
+        self
+    }
+    pub const fn with_zero_conf(mut self) -> Self {
+        self.zero_conf = true;
+        self
+    }
+    pub fn features(&self) -> ChannelTypeFeatures {
+        let mut features: ChannelTypeFeatures =
+            self.commitment.features().iter().cloned().collect();
+        if self.scid_alias {
+            features.add(ChannelTypeFeature::ScidAliasRequired);
+        }
+        if self.zero_conf {
+            features.add(ChannelTypeFeature::ZeroConfRequired);
+        }
+        features
+    }
+    // Returns true if the peer advertised every feature this channel type is
+    // made of in its `init` message.
+    pub fn supported_by(&self, theirs: &InitFeatures) -> bool {
+        self.features().0.iter().all(|f| {
+            Feature::try_from(f.idx())
+                .map(|f| theirs.has(f))
+                .unwrap_or(false)
+        })
+    }
+}
+impl core::fmt::Display for ChannelType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.features())
+    }
+}
+impl From<ChannelType> for ChannelTypeFeatures {
+    fn from(t: ChannelType) -> Self {
+        t.features()
+    }
+}
+impl TryFrom<ChannelTypeFeatures> for ChannelType {
+    type Error = ChannelTypeFeatures;
+
+    fn try_from(features: ChannelTypeFeatures) -> Result<Self, ChannelTypeFeatures> {
+        use ChannelTypeFeature::*;
+        if features.unknown().next().is_some() || features.0.iter().any(|f| !f.is_required()) {
+            return Err(features);
+        }
+        let mut rest = features.clone();
+        let scid_alias = rest.remove(ScidAliasRequired);
+        let zero_conf = rest.remove(ZeroConfRequired);
+        let commitment = [
+            CommitmentType::StaticRemoteKey,
+            CommitmentType::AnchorsZeroFeeHtlcTx,
+            CommitmentType::SimpleTaproot,
+        ]
+        .iter()
+        .cloned()
+        .find(|c| rest.0.iter().eq(c.features().iter()))
+        .ok_or(features)?;
+        Ok(ChannelType {
+            commitment,
+            scid_alias,
+            zero_conf,
+        })
+    }
+}
+impl TLVWireItem for ChannelType {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.features().encode_bits(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.features().byte_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        Ok(
+            ChannelType::try_from(ChannelTypeFeatures::decode_bits(r, len)?)
+                .map_err(InvalidChannelType)?,
+        )
+    }
+}
+
+// A `channel_type` that is not one of the combinations BOLT #2 defines, such
+// as one with bits unknown to `ChannelTypeFeature`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidChannelType(pub ChannelTypeFeatures);
+impl core::fmt::Display for InvalidChannelType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid channel type {}", self.0)
+    }
+}
+impl core::error::Error for InvalidChannelType {}
+impl From<InvalidChannelType> for crate::io::Error {
+    fn from(e: InvalidChannelType) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelTypeError {
+    // None of our supported channel types are supported by the peer.
+    NoCommonType,
+
+    // The peer proposed a channel type we do not support.
+    Unsupported(ChannelType),
+
+    // We proposed a channel type and the peer did not echo it back in
+    // `accept_channel`.
+    Missing,
+
+    // The peer echoed back a different channel type than the one we proposed.
+    Mismatch {
+        proposed: ChannelType,
+        received: ChannelType,
+    },
+}
+impl core::fmt::Display for ChannelTypeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChannelTypeError::NoCommonType => write!(f, "no common channel type"),
+            ChannelTypeError::Unsupported(t) => write!(f, "unsupported channel type {:?}", t),
+            ChannelTypeError::Missing => write!(f, "channel_type missing from accept_channel"),
+            ChannelTypeError::Mismatch { proposed, received } => write!(
+                f,
+                "channel_type mismatch: proposed {:?}, received {:?}",
+                proposed, received
+            ),
+        }
+    }
+}
+impl core::error::Error for ChannelTypeError {}
+
+// Picks the channel type to send in `open_channel`: the first of `supported`,
+// in order of preference, that the peer's `init` features allow.
+pub fn propose(
+    supported: &[ChannelType],
+    theirs: &InitFeatures,
+) -> Result<ChannelType, ChannelTypeError> {
+    supported
+        .iter()
+        .cloned()
+        .find(|t| t.supported_by(theirs))
+        .ok_or(ChannelTypeError::NoCommonType)
+}
+
+// Checks a channel type received in `open_channel` against the types we
+// support. On success it is the type to echo back in `accept_channel`.
+pub fn check_proposed(
+    supported: &[ChannelType],
+    proposed: ChannelType,
+) -> Result<ChannelType, ChannelTypeError> {
+    if supported.contains(&proposed) {
+        Ok(proposed)
+    } else {
+        Err(ChannelTypeError::Unsupported(proposed))
+    }
+}
+
+// Checks the channel type echoed back in `accept_channel` against the one we
+// proposed in `open_channel`. BOLT #2 requires them to be identical.
+pub fn check_accepted(
+    proposed: ChannelType,
+    accepted: Option<ChannelType>,
+) -> Result<ChannelType, ChannelTypeError> {
+    match accepted {
+        None => Err(ChannelTypeError::Missing),
+        Some(received) if received != proposed => {
+            Err(ChannelTypeError::Mismatch { proposed, received })
+        }
+        Some(received) => Ok(received),
+    }
+}
+
+#[test]
+fn channel_types() {
+    use crate::items::feature::Feature;
+    use ChannelTypeFeature::*;
+
+    for commitment in [
+        CommitmentType::StaticRemoteKey,
+        CommitmentType::AnchorsZeroFeeHtlcTx,
+        CommitmentType::SimpleTaproot,
+    ] {
+        let plain = ChannelType::new(commitment);
+        for t in [
+            plain,
+            plain.with_scid_alias(),
+            plain.with_zero_conf(),
+            plain.with_scid_alias().with_zero_conf(),
+        ] {
+            let mut bytes = Vec::new();
+            let len = TLVWireItem::encode(&t, &mut bytes).unwrap();
+            assert_eq!(len, bytes.len());
+            assert_eq!(len, TLVWireItem::encoded_len(&t));
+            assert_eq!(
+                <ChannelType as TLVWireItem>::decode(&mut &bytes[..], len).unwrap(),
+                t
+            );
+            assert_eq!(ChannelType::try_from(t.features()), Ok(t));
+        }
+    }
+    let anchors = ChannelType::new(CommitmentType::AnchorsZeroFeeHtlcTx);
+    let mut bytes = Vec::new();
+    TLVWireItem::encode(&anchors.with_zero_conf(), &mut bytes).unwrap();
+    assert_eq!(bytes, [0x04, 0x00, 0x00, 0x00, 0x40, 0x10, 0x00]);
+
+    // Optional bits, unknown bits and combinations BOLT #2 does not define
+    // are not channel types.
+    let mut optional = anchors.features();
+    optional.add(ScidAliasOptional);
+    assert_eq!(ChannelType::try_from(optional.clone()), Err(optional));
+    let mut unknown = anchors.features();
+    unknown.set_bit(100);
+    assert_eq!(ChannelType::try_from(unknown.clone()), Err(unknown));
+    let undefined: ChannelTypeFeatures = [AnchorsZeroFeeHtlcTxRequired].iter().cloned().collect();
+    assert!(ChannelType::try_from(undefined).is_err());
+    assert!(ChannelType::try_from(ChannelTypeFeatures::new()).is_err());
+    // option_static_remotekey as odd, and an unknown even bit 100.
+    assert!(<ChannelType as TLVWireItem>::decode(&mut &[0x20, 0x00][..], 2).is_err());
+    let mut bytes = [0; 13];
+    bytes[0] = 0x10;
+    bytes[11] = 0x10;
+    let err = <ChannelType as TLVWireItem>::decode(&mut &bytes[..], 13).unwrap_err();
+    let invalid = err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<InvalidChannelType>());
+    assert_eq!(
+        invalid.map(|e| e.0.unknown().collect::<Vec<_>>()),
+        Some(vec![100])
+    );
+
+    let supported = [
+        ChannelType::new(CommitmentType::SimpleTaproot),
+        anchors.with_scid_alias(),
+        anchors,
+    ];
+    let mut theirs = InitFeatures::new();
+    assert_eq!(
+        propose(&supported, &theirs),
+        Err(ChannelTypeError::NoCommonType)
+    );
+    theirs.add(Feature::StaticRemoteKeyOptional);
+    theirs.add(Feature::AnchorsZeroFeeHtlcTxOptional);
+    assert_eq!(propose(&supported, &theirs), Ok(anchors));
+    theirs.add(Feature::ScidAliasRequired);
+    assert_eq!(propose(&supported, &theirs), Ok(anchors.with_scid_alias()));
+
+    assert_eq!(check_proposed(&supported, anchors), Ok(anchors));
+    let zero_conf = anchors.with_zero_conf();
+    assert_eq!(
+        check_proposed(&supported, zero_conf),
+        Err(ChannelTypeError::Unsupported(zero_conf))
+    );
+
+    assert_eq!(check_accepted(anchors, Some(anchors)), Ok(anchors));
+    assert_eq!(
+        check_accepted(anchors, None),
+        Err(ChannelTypeError::Missing)
+    );
+    assert_eq!(
+        check_accepted(anchors, Some(zero_conf)),
+        Err(ChannelTypeError::Mismatch {
+            proposed: anchors,
+            received: zero_conf,
+        })
+    );
+}
@@ -0,0 +1,94 @@
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct BlockHeight(pub u32);
+impl BlockHeight {
+    // The expiry `delta` blocks from now.
+    pub fn checked_add(&self, delta: CltvExpiryDelta) -> Option<CltvExpiry> {
+        self.0.checked_add(u32::from(delta.0)).map(CltvExpiry)
+    }
+}
+impl WireItem for BlockHeight {
+    const FIXED_SIZE: Option<usize> = u32::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        u32::decode(r).map(BlockHeight)
+    }
+}
+
+// The absolute block height at which an htlc times out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct CltvExpiry(pub u32);
+impl CltvExpiry {
+    pub fn checked_add(&self, delta: CltvExpiryDelta) -> Option<CltvExpiry> {
+        self.0.checked_add(u32::from(delta.0)).map(CltvExpiry)
+    }
+
+    // The expiry of the outgoing htlc of a hop charging `delta`.
+    pub fn checked_sub(&self, delta: CltvExpiryDelta) -> Option<CltvExpiry> {
+        self.0.checked_sub(u32::from(delta.0)).map(CltvExpiry)
+    }
+
+    pub fn is_expired_at(&self, height: BlockHeight) -> bool {
+        height.0 >= self.0
+    }
+}
+impl WireItem for CltvExpiry {
+    const FIXED_SIZE: Option<usize> = u32::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        u32::decode(r).map(CltvExpiry)
+    }
+}
+
+// A number of blocks between the expiries of an incoming and an outgoing
+// htlc.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct CltvExpiryDelta(pub u16);
+impl WireItem for CltvExpiryDelta {
+    const FIXED_SIZE: Option<usize> = u16::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        u16::decode(r).map(CltvExpiryDelta)
+    }
+}
@@ -1,85 +1,487 @@
-use crate::WireItem;
-use std::collections::BTreeSet;
-use std::convert::TryFrom;
-use std::io::{Read, Write};
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive)]
-#[repr(usize)]
-pub enum Feature {
-    DataLossProtectRequired = 0,
-    DataLossProtectOptional = 1,
-    InitialRoutingSync = 3,
-    GossipQueriesRequired = 6,
-    GossipQueriesOptional = 7,
-}
-impl Feature {
-    pub fn idx(&self) -> usize {
-        *self as usize
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+use alloc::collections::BTreeSet;
+use core::convert::TryFrom;
+
+// A namespace of feature bits. Each context that negotiates features (BOLT #9
+// init/node features, channel types, invoices, watchtowers, ...) defines its
+// own set, so that bits from one namespace can never be decoded as bits from
+// another.
+pub trait FeatureSet: Copy + Ord + TryFrom<usize> + 'static {
+    fn idx(&self) -> usize;
+
+    // The name of the feature, shared by both bits of a required/optional pair.
+    fn name(&self) -> &'static str;
+
+    fn is_required(&self) -> bool {
+        self.idx() & 1 == 0
+    }
+
+    // The other bit of the required/optional pair, if it is defined in this
+    // namespace.
+    fn pair(&self) -> Option<Self> {
+        Self::try_from(self.idx() ^ 1).ok()
     }
+
+    // Every feature in the namespace, from the lowest bit up.
+    fn all() -> &'static [Self];
+}
+
+macro_rules! feature_set {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[$var_attr:meta])*
+                $variant:ident = $bit:literal => $feat_name:literal,
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive)]
+        #[repr(usize)]
+        pub enum $name {
+            $(
+                $(#[$var_attr])*
+                $variant = $bit,
+            )*
+        }
+        impl crate::items::feature::FeatureSet for $name {
+            fn idx(&self) -> usize {
+                *self as usize
+            }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        $name::$variant => $feat_name,
+                    )*
+                }
+            }
+
+            fn all() -> &'static [Self] {
+                &[$($name::$variant,)*]
+            }
+        }
+    };
 }
+pub(crate) use feature_set;
+
+feature_set!(
+    // BOLT #9 features found in the `init` message and `node_announcement`.
+    pub enum Feature {
+        DataLossProtectRequired = 0 => "option_data_loss_protect",
+        DataLossProtectOptional = 1 => "option_data_loss_protect",
+        InitialRoutingSync = 3 => "initial_routing_sync",
+        UpfrontShutdownScriptRequired = 4 => "option_upfront_shutdown_script",
+        UpfrontShutdownScriptOptional = 5 => "option_upfront_shutdown_script",
+        GossipQueriesRequired = 6 => "gossip_queries",
+        GossipQueriesOptional = 7 => "gossip_queries",
+        VarOnionOptinRequired = 8 => "var_onion_optin",
+        VarOnionOptinOptional = 9 => "var_onion_optin",
+        GossipQueriesExRequired = 10 => "gossip_queries_ex",
+        GossipQueriesExOptional = 11 => "gossip_queries_ex",
+        StaticRemoteKeyRequired = 12 => "option_static_remotekey",
+        StaticRemoteKeyOptional = 13 => "option_static_remotekey",
+        PaymentSecretRequired = 14 => "payment_secret",
+        PaymentSecretOptional = 15 => "payment_secret",
+        BasicMppRequired = 16 => "basic_mpp",
+        BasicMppOptional = 17 => "basic_mpp",
+        LargeChannelRequired = 18 => "option_support_large_channel",
+        LargeChannelOptional = 19 => "option_support_large_channel",
+        AnchorOutputsRequired = 20 => "option_anchor_outputs",
+        AnchorOutputsOptional = 21 => "option_anchor_outputs",
+        AnchorsZeroFeeHtlcTxRequired = 22 => "option_anchors_zero_fee_htlc_tx",
+        AnchorsZeroFeeHtlcTxOptional = 23 => "option_anchors_zero_fee_htlc_tx",
+        RouteBlindingRequired = 24 => "option_route_blinding",
+        RouteBlindingOptional = 25 => "option_route_blinding",
+        ShutdownAnySegwitRequired = 26 => "option_shutdown_anysegwit",
+        ShutdownAnySegwitOptional = 27 => "option_shutdown_anysegwit",
+        DualFundRequired = 28 => "option_dual_fund",
+        DualFundOptional = 29 => "option_dual_fund",
+        QuiesceRequired = 34 => "option_quiesce",
+        QuiesceOptional = 35 => "option_quiesce",
+        OnionMessagesRequired = 38 => "option_onion_messages",
+        OnionMessagesOptional = 39 => "option_onion_messages",
+        ChannelTypeRequired = 44 => "option_channel_type",
+        ChannelTypeOptional = 45 => "option_channel_type",
+        ScidAliasRequired = 46 => "option_scid_alias",
+        ScidAliasOptional = 47 => "option_scid_alias",
+        ZeroConfRequired = 50 => "option_zeroconf",
+        ZeroConfOptional = 51 => "option_zeroconf",
+        SpliceRequired = 62 => "option_splice",
+        SpliceOptional = 63 => "option_splice",
+        SimpleTaprootRequired = 80 => "option_simple_taproot",
+        SimpleTaprootOptional = 81 => "option_simple_taproot",
+    }
+);
+
+feature_set!(
+    // BOLT #9 features allowed in the `channel_type` TLV of `open_channel` and
+    // `accept_channel` (context T).
+    pub enum ChannelTypeFeature {
+        StaticRemoteKeyRequired = 12 => "option_static_remotekey",
+        StaticRemoteKeyOptional = 13 => "option_static_remotekey",
+        AnchorOutputsRequired = 20 => "option_anchor_outputs",
+        AnchorOutputsOptional = 21 => "option_anchor_outputs",
+        AnchorsZeroFeeHtlcTxRequired = 22 => "option_anchors_zero_fee_htlc_tx",
+        AnchorsZeroFeeHtlcTxOptional = 23 => "option_anchors_zero_fee_htlc_tx",
+        ScidAliasRequired = 46 => "option_scid_alias",
+        ScidAliasOptional = 47 => "option_scid_alias",
+        ZeroConfRequired = 50 => "option_zeroconf",
+        ZeroConfOptional = 51 => "option_zeroconf",
+        SimpleTaprootRequired = 80 => "option_simple_taproot",
+        SimpleTaprootOptional = 81 => "option_simple_taproot",
+    }
+);
+
+feature_set!(
+    // BOLT #9 features allowed in the `9` field of a BOLT #11 invoice.
+    pub enum InvoiceFeature {
+        VarOnionOptinRequired = 8 => "var_onion_optin",
+        VarOnionOptinOptional = 9 => "var_onion_optin",
+        PaymentSecretRequired = 14 => "payment_secret",
+        PaymentSecretOptional = 15 => "payment_secret",
+        BasicMppRequired = 16 => "basic_mpp",
+        BasicMppOptional = 17 => "basic_mpp",
+        RouteBlindingRequired = 24 => "option_route_blinding",
+        RouteBlindingOptional = 25 => "option_route_blinding",
+        PaymentMetadataRequired = 48 => "option_payment_metadata",
+        PaymentMetadataOptional = 49 => "option_payment_metadata",
+    }
+);
 
-#[derive(Clone, Debug)]
-pub struct RawFeatureVector(pub BTreeSet<Feature>);
-impl RawFeatureVector {
+feature_set!(
+    // BOLT #9 features allowed in the `offer_features`, `invreq_features` and
+    // `invoice_features` fields of BOLT #12 offers (context B).
+    pub enum Bolt12Feature {
+        BasicMppRequired = 16 => "basic_mpp",
+        BasicMppOptional = 17 => "basic_mpp",
+    }
+);
+
+pub type InitFeatures = RawFeatureVector<Feature>;
+pub type ChannelTypeFeatures = RawFeatureVector<ChannelTypeFeature>;
+pub type InvoiceFeatures = RawFeatureVector<InvoiceFeature>;
+pub type Bolt12Features = RawFeatureVector<Bolt12Feature>;
+
+// A feature vector over the namespace `F`. Bits that are set on the wire but
+// are not defined in `F` are kept in the second field, so that they survive a
+// decode/encode round trip and unknown required bits can be rejected by the
+// caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawFeatureVector<F: FeatureSet = Feature>(pub BTreeSet<F>, pub BTreeSet<usize>);
+impl<F> RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
     pub fn new() -> Self {
-        RawFeatureVector(BTreeSet::new())
+        RawFeatureVector(BTreeSet::new(), BTreeSet::new())
     }
-    pub fn add(&mut self, f: Feature) -> bool {
+    pub fn add(&mut self, f: F) -> bool {
         self.0.insert(f)
     }
+    pub fn remove(&mut self, f: F) -> bool {
+        self.0.remove(&f)
+    }
+    // Returns true if either bit of the feature's pair is set.
+    pub fn has(&self, f: F) -> bool {
+        self.0.contains(&f) || f.pair().map(|p| self.0.contains(&p)).unwrap_or(false)
+    }
+    // Returns true if the required bit of the feature's pair is set.
+    pub fn requires(&self, f: F) -> bool {
+        if f.is_required() {
+            self.0.contains(&f)
+        } else {
+            f.pair().map(|p| self.0.contains(&p)).unwrap_or(false)
+        }
+    }
+    pub fn set_bit(&mut self, idx: usize) -> bool {
+        match F::try_from(idx) {
+            Ok(f) => self.0.insert(f),
+            Err(_) => self.1.insert(idx),
+        }
+    }
+    pub fn has_bit(&self, idx: usize) -> bool {
+        match F::try_from(idx) {
+            Ok(f) => self.0.contains(&f),
+            Err(_) => self.1.contains(&idx),
+        }
+    }
+    pub fn unknown(&self) -> impl Iterator<Item = usize> + '_ {
+        self.1.iter().cloned()
+    }
+    // Unknown even bits: per BOLT #9 the receiver must fail the connection if
+    // any of these are set.
+    pub fn unknown_required(&self) -> impl Iterator<Item = usize> + '_ {
+        self.unknown().filter(|i| i & 1 == 0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty() && self.1.is_empty()
+    }
+    // All set bits, known and unknown, from highest to lowest.
+    pub fn bits(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut known = self.0.iter().rev().map(|f| f.idx()).peekable();
+        let mut unknown = self.1.iter().rev().cloned().peekable();
+        core::iter::from_fn(move || match (known.peek(), unknown.peek()) {
+            (Some(k), Some(u)) if k > u => known.next(),
+            (_, Some(_)) => unknown.next(),
+            (Some(_), None) => known.next(),
+            (None, None) => None,
+        })
+    }
+    // Number of bytes needed to hold the highest set bit.
+    pub fn byte_len(&self) -> usize {
+        self.bits().next().map(|a| a / 8 + 1).unwrap_or(0)
+    }
+}
+impl<F> Default for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn default() -> Self {
+        RawFeatureVector::new()
+    }
 }
-impl WireItem for RawFeatureVector {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
-        let len = self
-            .0
-            .iter()
-            .next_back()
-            .map(|a| a.idx() / 8 + 1)
-            .unwrap_or(0) as u16;
-        let mut count = w.write(&u16::to_be_bytes(len))?;
-        let mut feat_iter = self.0.iter();
-        let mut current = feat_iter.next_back();
-        let mut byte = 0_u8;
-        let mut byte_idx = len as usize - 1;
-        while let Some(feat) = current {
-            let new_byte_idx = feat.idx() / 8;
-            while byte_idx > new_byte_idx {
-                count += w.write(&[byte])?;
-                byte = 0;
-                byte_idx -= 1;
+impl<F> core::iter::FromIterator<F> for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn from_iter<I: IntoIterator<Item = F>>(iter: I) -> Self {
+        RawFeatureVector(iter.into_iter().collect(), BTreeSet::new())
+    }
+}
+impl<F> RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    // Writes the bit field without a length prefix, for contexts (such as a
+    // TLV value) where the length is carried elsewhere.
+    pub fn encode_bits<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        let mut bits = self.bits().peekable();
+        for byte_idx in (0..self.byte_len()).rev() {
+            let mut byte = 0_u8;
+            while let Some(bit) = bits.peek() {
+                if bit / 8 != byte_idx {
+                    break;
+                }
+                byte |= 1 << (bit % 8);
+                bits.next();
             }
-            let bit_idx = feat.idx() % 8;
-            byte |= 1 << bit_idx;
-            current = feat_iter.next_back()
+            count += w.write(&[byte])?;
         }
-        count += w.write(&[byte])?;
-        w.flush()?;
-
         Ok(count)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
-        let mut len = [0_u8; 2];
-        r.read_exact(&mut len)?;
-        let len = u16::from_be_bytes(len);
-        let mut ret = BTreeSet::new();
-        if len == 0 {
-            return Ok(RawFeatureVector(ret));
-        }
-        let mut byte_idx = len as usize;
+    // Reads a bit field of `len` bytes without a length prefix.
+    pub fn decode_bits<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        let mut ret = RawFeatureVector::new();
+        let mut byte_idx = len;
         for _ in 0..len {
             byte_idx -= 1;
             let mut byte = [0_u8];
             r.read_exact(&mut byte)?;
             for bit_idx in 0..8 {
                 if byte[0] & (1 << bit_idx) != 0 {
-                    let feat = 8 * byte_idx + bit_idx;
-                    ret.insert(
-                        Feature::try_from(feat).map_err(|_| std::io::ErrorKind::InvalidData)?,
-                    );
+                    ret.set_bit(8 * byte_idx + bit_idx);
                 }
             }
         }
-        Ok(RawFeatureVector(ret))
+        Ok(ret)
+    }
+}
+impl<F> WireItem for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let len = self.byte_len();
+        if len > u16::MAX as usize {
+            return Err(crate::io::ErrorKind::InvalidInput.into());
+        }
+        let mut count = w.write(&u16::to_be_bytes(len as u16))?;
+        count += self.encode_bits(w)?;
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        2 + self.byte_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let mut len = [0_u8; 2];
+        r.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len);
+        Self::decode_bits(r, len as usize)
+    }
+}
+
+fn feature_name<F: FeatureSet>(f: &F) -> alloc::string::String {
+    let kind = if f.is_required() {
+        "required"
+    } else {
+        "optional"
+    };
+    alloc::format!("{}/{}", f.name(), kind)
+}
+// The set bits from the lowest up, as `name/required` or `name/optional`, and
+// those not in `F` as numbers, e.g.
+// `[option_static_remotekey/required, gossip_queries/optional, 99]`.
+impl<F> core::fmt::Display for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut bits: alloc::vec::Vec<usize> = self.bits().collect();
+        bits.reverse();
+        write!(f, "[")?;
+        for (i, bit) in bits.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match F::try_from(bit) {
+                Ok(feature) => write!(f, "{}", feature_name(&feature))?,
+                Err(_) => write!(f, "{}", bit)?,
+            }
+        }
+        write!(f, "]")
     }
 }
+
+// As displayed in human-readable formats, but as a list of strings and
+// numbers, e.g. `["option_static_remotekey/required", 99]`. The other formats
+// get the numbers of all the bits.
+#[cfg(feature = "serde")]
+impl<F> serde::Serialize for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        let human = s.is_human_readable();
+        let mut bits: alloc::vec::Vec<usize> = self.bits().collect();
+        bits.reverse();
+        let mut seq = s.serialize_seq(Some(bits.len()))?;
+        for bit in bits {
+            match F::try_from(bit) {
+                Ok(f) if human => seq.serialize_element(&feature_name(&f))?,
+                _ => seq.serialize_element(&bit)?,
+            }
+        }
+        seq.end()
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, F> serde::Deserialize<'de> for RawFeatureVector<F>
+where
+    F: FeatureSet,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use alloc::{string::String, vec::Vec};
+        use serde::de::Error;
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Bit {
+            Name(String),
+            Number(usize),
+        }
+
+        let mut ret = RawFeatureVector::new();
+        if !d.is_human_readable() {
+            for bit in Vec::<usize>::deserialize(d)? {
+                ret.set_bit(bit);
+            }
+            return Ok(ret);
+        }
+        for bit in Vec::<Bit>::deserialize(d)? {
+            match bit {
+                Bit::Number(bit) => {
+                    ret.set_bit(bit);
+                }
+                Bit::Name(name) => {
+                    let f = F::all()
+                        .iter()
+                        .find(|f| feature_name(*f) == name)
+                        .ok_or_else(|| {
+                            D::Error::custom(alloc::format!("unknown feature {}", name))
+                        })?;
+                    ret.add(*f);
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[test]
+fn namespaces() {
+    use crate::watchtower::items::feature::Feature as TowerFeature;
+
+    fn check<F: FeatureSet + core::fmt::Debug>() {
+        let all = F::all();
+        assert!(all.windows(2).all(|w| w[0].idx() < w[1].idx()));
+        for f in all {
+            assert_eq!(F::try_from(f.idx()).ok(), Some(*f));
+            assert_eq!(f.is_required(), f.idx() % 2 == 0);
+            if let Some(p) = f.pair() {
+                assert_eq!(p.pair(), Some(*f));
+                assert_eq!(p.name(), f.name());
+                assert_ne!(p.is_required(), f.is_required());
+            }
+        }
+        assert!(F::try_from(1000).is_err());
+    }
+    check::<Feature>();
+    check::<ChannelTypeFeature>();
+    check::<InvoiceFeature>();
+    check::<Bolt12Feature>();
+    check::<TowerFeature>();
+
+    assert_eq!(
+        Feature::StaticRemoteKeyRequired.name(),
+        "option_static_remotekey"
+    );
+    assert_eq!(
+        Feature::StaticRemoteKeyRequired.pair(),
+        Some(Feature::StaticRemoteKeyOptional)
+    );
+    assert_eq!(Feature::InitialRoutingSync.pair(), None);
+    assert_eq!(Feature::try_from(2).ok(), None);
+    assert_eq!(
+        ChannelTypeFeature::try_from(22).ok(),
+        Some(ChannelTypeFeature::AnchorsZeroFeeHtlcTxRequired)
+    );
+    assert!(ChannelTypeFeature::try_from(14).is_err());
+    assert_eq!(
+        InvoiceFeature::PaymentMetadataOptional.name(),
+        "option_payment_metadata"
+    );
+    assert!(InvoiceFeature::try_from(12).is_err());
+    assert_eq!(
+        Bolt12Feature::all(),
+        [
+            Bolt12Feature::BasicMppRequired,
+            Bolt12Feature::BasicMppOptional
+        ]
+    );
+    assert_eq!(TowerFeature::all().len(), 6);
+    assert_eq!(TowerFeature::TaprootCommitOptional.name(), "taproot-commit");
+}
+
+#[test]
+fn watchtower_namespace() {
+    use crate::watchtower::items::feature::{Feature as TowerFeature, Features};
+    use alloc::string::ToString;
+
+    // Bit 0 of a watchtower vector is not BOLT #9's data loss protect.
+    let bytes = [0, 1, 0x01];
+    let tower = Features::decode(&mut &bytes[..]).unwrap();
+    assert!(tower.requires(TowerFeature::AltruistSessionsRequired));
+    assert_eq!(tower.to_string(), "[altruist-sessions/required]");
+    let init = InitFeatures::decode(&mut &bytes[..]).unwrap();
+    assert!(init.requires(Feature::DataLossProtectRequired));
+    assert_eq!(init.to_string(), "[option_data_loss_protect/required]");
+}
@@ -1,9 +1,14 @@
-use crate::WireItem;
-use std::io::{Read, Write};
+use crate::io::Write;
+use crate::{WireItem, WireRead};
 
 pub type Sats = i64;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct SatPerKWeight(pub Sats);
 
 impl SatPerKWeight {
@@ -12,12 +17,77 @@ impl SatPerKWeight {
     }
 }
 
+impl core::fmt::Display for SatPerKWeight {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} sat/kw", self.0)
+    }
+}
 impl WireItem for SatPerKWeight {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = Sats::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.0.encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         i64::decode(r).map(SatPerKWeight)
     }
 }
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct MilliSatoshi(pub u64);
+impl MilliSatoshi {
+    // None if `sats` is negative or too large.
+    pub fn from_sats(sats: Sats) -> Option<Self> {
+        if sats < 0 {
+            return None;
+        }
+        (sats as u64).checked_mul(1000).map(MilliSatoshi)
+    }
+
+    // Rounded down.
+    pub fn to_sats(&self) -> Sats {
+        (self.0 / 1000) as Sats
+    }
+
+    pub fn checked_add(&self, other: MilliSatoshi) -> Option<Self> {
+        self.0.checked_add(other.0).map(MilliSatoshi)
+    }
+
+    pub fn checked_sub(&self, other: MilliSatoshi) -> Option<Self> {
+        self.0.checked_sub(other.0).map(MilliSatoshi)
+    }
+
+    pub fn checked_mul(&self, n: u64) -> Option<Self> {
+        self.0.checked_mul(n).map(MilliSatoshi)
+    }
+}
+impl core::fmt::Display for MilliSatoshi {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} mSAT", self.0)
+    }
+}
+impl WireItem for MilliSatoshi {
+    const FIXED_SIZE: Option<usize> = u64::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        u64::decode(r).map(MilliSatoshi)
+    }
+}
@@ -1,16 +1,32 @@
-use crate::WireItem;
-use std::io::{Read, Write};
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+
+// The genesis block hash of bitcoin mainnet, in the byte order used for
+// `chain_hash` fields.
+pub const BITCOIN_CHAIN_HASH: [u8; 32] = [
+    0x6f, 0xe2, 0x8c, 0x0a, 0xb6, 0xf1, 0xb3, 0x72, 0xc1, 0xa6, 0xa2, 0x46, 0xae, 0x63, 0xf7, 0x4f,
+    0x93, 0x1e, 0x83, 0x65, 0xe1, 0x5a, 0x08, 0x9c, 0x68, 0xd6, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
 
 #[derive(Clone, Debug)]
-pub struct Hash(pub [u8; 32]);
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Hash(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] pub [u8; 32]);
 impl WireItem for Hash {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
-        let count = w.write(&self.0)?;
-        w.flush()?;
-        Ok(count)
+    const FIXED_SIZE: Option<usize> = Some(32);
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        w.write(&self.0)
+    }
+
+    fn encoded_len(&self) -> usize {
+        32
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         let mut hash = [0_u8; 32];
         r.read_exact(&mut hash)?;
         Ok(Hash(hash))
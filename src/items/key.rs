@@ -0,0 +1,234 @@
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+
+// With the `secp256k1` feature, the library's own types, checked on decode.
+#[cfg(feature = "secp256k1")]
+pub use secp256k1::{
+    ecdsa::Signature, schnorr::Signature as SchnorrSignature, PublicKey, XOnlyPublicKey,
+};
+
+#[cfg(feature = "secp256k1")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyError {
+    // Not a compressed point on the curve.
+    InvalidPublicKey,
+    // Not an x coordinate on the curve.
+    InvalidXOnlyPublicKey,
+    // `r` or `s` is out of range.
+    InvalidSignature,
+    // `s` is in the upper half of the range, which is not standard.
+    HighS,
+    // `r` is not an x coordinate on the curve, or `s` is out of range.
+    InvalidSchnorrSignature,
+}
+#[cfg(feature = "secp256k1")]
+impl core::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            KeyError::InvalidPublicKey => write!(f, "invalid public key"),
+            KeyError::InvalidXOnlyPublicKey => write!(f, "invalid x-only public key"),
+            KeyError::InvalidSignature => write!(f, "invalid signature"),
+            KeyError::HighS => write!(f, "signature is not low-S"),
+            KeyError::InvalidSchnorrSignature => write!(f, "invalid schnorr signature"),
+        }
+    }
+}
+#[cfg(feature = "secp256k1")]
+impl core::error::Error for KeyError {}
+#[cfg(feature = "secp256k1")]
+impl From<KeyError> for crate::io::Error {
+    fn from(e: KeyError) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::InvalidData, e)
+    }
+}
+
+macro_rules! raw_key_items {
+    (
+        $(
+            $name:ident[$bytes:literal],
+        )*
+    ) => {
+        $(
+            #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+            #[cfg_attr(
+                feature = "serde",
+                derive(serde::Serialize, serde::Deserialize),
+                serde(transparent)
+            )]
+            pub struct $name(
+                #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] pub [u8; $bytes],
+            );
+            impl WireItem for $name {
+                const FIXED_SIZE: Option<usize> = Some($bytes);
+
+                fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+                    self.0.encode(w)
+                }
+
+                fn encoded_len(&self) -> usize {
+                    $bytes
+                }
+
+                fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+                    <[u8; $bytes]>::decode(r).map($name)
+                }
+            }
+            impl From<[u8; $bytes]> for $name {
+                fn from(bytes: [u8; $bytes]) -> Self {
+                    $name(bytes)
+                }
+            }
+        )*
+    };
+}
+
+// The raw bytes of keys and signatures, as sent, for messages that carry
+// them unchecked.
+raw_key_items!(
+    RawPublicKey[33],
+    RawXOnlyPublicKey[32],
+    RawSignature[64],
+    RawSchnorrSignature[64],
+);
+
+// Each checked type is read as its raw bytes, then converted.
+macro_rules! checked_key_items {
+    (
+        $(
+            $name:ident($raw:ident, $serialize:expr),
+        )*
+    ) => {
+        $(
+            #[cfg(feature = "secp256k1")]
+            impl From<$name> for $raw {
+                fn from(key: $name) -> Self {
+                    $raw($serialize(&key))
+                }
+            }
+            #[cfg(feature = "secp256k1")]
+            impl WireItem for $name {
+                const FIXED_SIZE: Option<usize> = $raw::FIXED_SIZE;
+
+                fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+                    $raw::from(*self).encode(w)
+                }
+
+                fn encoded_len(&self) -> usize {
+                    $raw::from(*self).encoded_len()
+                }
+
+                fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+                    Ok(core::convert::TryFrom::try_from($raw::decode(r)?)?)
+                }
+            }
+        )*
+    };
+}
+
+checked_key_items!(
+    PublicKey(RawPublicKey, PublicKey::serialize),
+    XOnlyPublicKey(RawXOnlyPublicKey, XOnlyPublicKey::serialize),
+    Signature(RawSignature, Signature::serialize_compact),
+    SchnorrSignature(RawSchnorrSignature, |sig: &SchnorrSignature| *sig.as_ref()),
+);
+
+#[cfg(feature = "secp256k1")]
+impl core::convert::TryFrom<RawPublicKey> for PublicKey {
+    type Error = KeyError;
+
+    fn try_from(raw: RawPublicKey) -> Result<Self, KeyError> {
+        PublicKey::from_slice(&raw.0).map_err(|_| KeyError::InvalidPublicKey)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl core::convert::TryFrom<RawXOnlyPublicKey> for XOnlyPublicKey {
+    type Error = KeyError;
+
+    fn try_from(raw: RawXOnlyPublicKey) -> Result<Self, KeyError> {
+        XOnlyPublicKey::from_slice(&raw.0).map_err(|_| KeyError::InvalidXOnlyPublicKey)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl core::convert::TryFrom<RawSignature> for Signature {
+    type Error = KeyError;
+
+    fn try_from(raw: RawSignature) -> Result<Self, KeyError> {
+        let sig = Signature::from_compact(&raw.0).map_err(|_| KeyError::InvalidSignature)?;
+        let mut normalized = sig;
+        normalized.normalize_s();
+        if normalized != sig {
+            return Err(KeyError::HighS);
+        }
+        Ok(sig)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+impl core::convert::TryFrom<RawSchnorrSignature> for SchnorrSignature {
+    type Error = KeyError;
+
+    fn try_from(raw: RawSchnorrSignature) -> Result<Self, KeyError> {
+        let mut s = [0_u8; 32];
+        s.copy_from_slice(&raw.0[32..]);
+        if XOnlyPublicKey::from_slice(&raw.0[..32]).is_err()
+            || secp256k1::Scalar::from_be_bytes(s).is_err()
+        {
+            return Err(KeyError::InvalidSchnorrSignature);
+        }
+        SchnorrSignature::from_slice(&raw.0).map_err(|_| KeyError::InvalidSchnorrSignature)
+    }
+}
+
+#[cfg(feature = "secp256k1")]
+#[test]
+fn validate_on_decode() {
+    use core::convert::TryFrom;
+    use secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+
+    let secp = Secp256k1::new();
+    let key = SecretKey::from_slice(&[1; 32]).unwrap();
+    let msg = Message::from_digest([2; 32]);
+
+    let point = PublicKey::from_secret_key(&secp, &key);
+    let mut bytes = Vec::new();
+    WireItem::encode(&point, &mut bytes).unwrap();
+    assert_eq!(PublicKey::decode(&mut &bytes[..]).unwrap(), point);
+    bytes[0] = 0x04;
+    // The raw bytes are taken as they are.
+    let raw = RawPublicKey::decode(&mut &bytes[..]).unwrap();
+    assert_eq!(raw.0[..], bytes[..]);
+    assert_eq!(PublicKey::try_from(raw), Err(KeyError::InvalidPublicKey));
+    let err = PublicKey::decode(&mut &bytes[..]).unwrap_err();
+    assert_eq!(
+        err.get_ref().and_then(|e| e.downcast_ref::<KeyError>()),
+        Some(&KeyError::InvalidPublicKey)
+    );
+
+    // The same signature with `s` negated is rejected.
+    let sig = secp.sign_ecdsa(&msg, &key);
+    let mut bytes = sig.serialize_compact();
+    assert_eq!(Signature::decode(&mut &bytes[..]).unwrap(), sig);
+    let mut s = [0_u8; 32];
+    s.copy_from_slice(&bytes[32..]);
+    let high_s = secp256k1::SecretKey::from_slice(&s).unwrap().negate();
+    bytes[32..].copy_from_slice(&high_s.secret_bytes());
+    let err = Signature::decode(&mut &bytes[..]).unwrap_err();
+    assert_eq!(
+        err.get_ref().and_then(|e| e.downcast_ref::<KeyError>()),
+        Some(&KeyError::HighS)
+    );
+
+    let keypair = Keypair::from_secret_key(&secp, &key);
+    let sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair);
+    let mut bytes = sig.as_ref().to_vec();
+    assert_eq!(SchnorrSignature::decode(&mut &bytes[..]).unwrap(), sig);
+    bytes[32..].copy_from_slice(&[0xff; 32]);
+    assert!(SchnorrSignature::decode(&mut &bytes[..]).is_err());
+    let x_only = keypair.x_only_public_key().0;
+    assert_eq!(
+        XOnlyPublicKey::decode(&mut &x_only.serialize()[..]).unwrap(),
+        x_only
+    );
+}
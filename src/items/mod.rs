@@ -1,10 +1,19 @@
+pub mod blinded_path;
+pub mod channel_id;
+pub mod channel_type;
+pub mod expiry;
 pub mod feature;
 pub mod fees;
 pub mod hash;
+pub mod key;
+pub mod prefixed;
+pub mod short_channel_id;
+pub mod truncated;
 
-use crate::{TLVWireItem, WireItem};
-use std::borrow::Borrow;
-use std::io::{Read, Write};
+use crate::io::Write;
+use crate::{TLVWireItem, WireItem, WireRead};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
 
 macro_rules! impl_wire_item_for_nums {
     (
@@ -16,38 +25,20 @@ macro_rules! impl_wire_item_for_nums {
         $(
             $(#[$attr])*
             impl WireItem for $num_ty {
-                fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+                const FIXED_SIZE: Option<usize> = Some($bytes);
+
+                fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
                     w.write(&<$num_ty>::to_be_bytes(*self))
                 }
 
-                fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
-                   let mut buf = [0_u8; $bytes];
-                   r.read_exact(&mut buf)?;
-                    Ok(<$num_ty>::from_be_bytes(buf))
+                fn encoded_len(&self) -> usize {
+                    $bytes
                 }
-            }
-        )*
-    };
-}
 
-macro_rules! impl_wire_item_for_byte_array {
-    (
-        $(
-            $(#[$attr:meta])*
-            [u8; $bytes:literal],
-        )*
-    ) => {
-        $(
-            $(#[$attr])*
-            impl WireItem for [u8; $bytes] {
-                fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
-                    w.write(self)
-                }
-
-                fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+                fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
                    let mut buf = [0_u8; $bytes];
                    r.read_exact(&mut buf)?;
-                    Ok(buf)
+                    Ok(<$num_ty>::from_be_bytes(buf))
                 }
             }
         )*
@@ -55,7 +46,6 @@ macro_rules! impl_wire_item_for_byte_array {
 }
 
 impl_wire_item_for_nums!(
-    u8[1],
     i8[1],
     u16[2],
     i16[2],
@@ -79,7 +69,58 @@ impl_wire_item_for_nums!(
     isize[8],
 );
 
-impl_wire_item_for_byte_array!([u8; 16],);
+impl WireItem for u8 {
+    const FIXED_SIZE: Option<usize> = Some(1);
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        w.write(&[*self])
+    }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let mut buf = [0_u8; 1];
+        r.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn encode_slice<W: Write>(items: &[Self], w: &mut W) -> crate::io::Result<usize> {
+        w.write(items)
+    }
+
+    fn decode_array<R: WireRead, const N: usize>(r: &mut R) -> crate::io::Result<[Self; N]> {
+        let mut buf = [0_u8; N];
+        r.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<T, const N: usize> WireItem for [T; N]
+where
+    T: WireItem,
+{
+    const FIXED_SIZE: Option<usize> = match T::FIXED_SIZE {
+        Some(size) => Some(size * N),
+        None => None,
+    };
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        T::encode_slice(self, w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        match Self::FIXED_SIZE {
+            Some(size) => size,
+            None => self.iter().map(WireItem::encoded_len).sum(),
+        }
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        T::decode_array(r)
+    }
+}
 
 pub enum MaybeOwned<'a, O: Borrow<B>, B> {
     Owned(O),
@@ -101,21 +142,31 @@ where
     O: Borrow<B> + WireItem,
     B: crate::WireItemWriter,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         Borrow::<B>::borrow(self).encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        Borrow::<B>::borrow(self).encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         Ok(MaybeOwned::Owned(O::decode(r)?))
     }
 }
 
 impl WireItem for () {
-    fn encode<W: Write>(&self, _: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = Some(0);
+
+    fn encode<W: Write>(&self, _: &mut W) -> crate::io::Result<usize> {
         Ok(0)
     }
 
-    fn decode<R: Read>(_: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        0
+    }
+
+    fn decode<R: WireRead>(_: &mut R) -> crate::io::Result<Self> {
         Ok(())
     }
 }
@@ -124,6 +175,10 @@ impl WireItem for () {
 pub enum Buffer<T: Borrow<[u8]>> {
     Vector(Vec<u8>),
     Other(T),
+    // Decoded with `bytes`. From `DecodeLimits::decode_bytes` over `Bytes`,
+    // a slice sharing the frame.
+    #[cfg(feature = "bytes")]
+    Shared(bytes::Bytes),
 }
 impl<T> Buffer<T>
 where
@@ -134,6 +189,8 @@ where
         match self {
             Buffer::Vector(a) => a,
             Buffer::Other(a) => a.borrow().to_vec(),
+            #[cfg(feature = "bytes")]
+            Buffer::Shared(a) => a.to_vec(),
         }
     }
 }
@@ -145,6 +202,8 @@ where
         match self {
             Buffer::Vector(a) => a.borrow(),
             Buffer::Other(a) => a.borrow(),
+            #[cfg(feature = "bytes")]
+            Buffer::Shared(a) => a.borrow(),
         }
     }
 }
@@ -160,7 +219,7 @@ impl<T> WireItem for Buffer<T>
 where
     T: Borrow<[u8]>,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         let mut count = 0;
         let slice: &[u8] = self.borrow();
         count += crate::write_varint(slice.len() as u64, w)?;
@@ -168,11 +227,36 @@ where
         Ok(count)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
-        let len = crate::read_varint(r)? as usize;
-        let mut buf = vec![0_u8; len];
-        r.read_exact(&mut buf)?;
-        Ok(Buffer::Vector(buf))
+    fn encoded_len(&self) -> usize {
+        let slice: &[u8] = self.borrow();
+        crate::varint_len(slice.len() as u64) + slice.len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let len = crate::read_varint(r)?;
+        #[cfg(feature = "bytes")]
+        let buffer = Buffer::Shared(r.read_bytes(len)?);
+        #[cfg(not(feature = "bytes"))]
+        let buffer = Buffer::Vector(crate::read_bytes(r, len)?);
+        Ok(buffer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Buffer<T>
+where
+    T: Borrow<[u8]>,
+{
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        crate::serde_hex::serialize_bytes(self.borrow(), s)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Buffer<T>
+where
+    T: Borrow<[u8]>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        crate::serde_hex::deserialize_bytes(d).map(Buffer::Vector)
     }
 }
 
@@ -180,6 +264,8 @@ where
 pub enum TLVBuffer<T: Borrow<[u8]>> {
     Vector(Vec<u8>),
     Other(T),
+    #[cfg(feature = "bytes")]
+    Shared(bytes::Bytes),
 }
 impl<T> Borrow<[u8]> for TLVBuffer<T>
 where
@@ -189,6 +275,8 @@ where
         match self {
             TLVBuffer::Vector(a) => a.borrow(),
             TLVBuffer::Other(a) => a.borrow(),
+            #[cfg(feature = "bytes")]
+            TLVBuffer::Shared(a) => a.borrow(),
         }
     }
 }
@@ -204,13 +292,87 @@ impl<T> TLVWireItem for TLVBuffer<T>
 where
     T: Borrow<[u8]>,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         w.write(self.borrow())
     }
 
-    fn decode<R: Read>(r: &mut R, len: usize) -> std::io::Result<Self> {
-        let mut buf = vec![0_u8; len];
-        r.read_exact(&mut buf)?;
-        Ok(TLVBuffer::Vector(buf))
+    fn encoded_len(&self) -> usize {
+        Borrow::<[u8]>::borrow(self).len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        #[cfg(feature = "bytes")]
+        let buffer = TLVBuffer::Shared(r.read_bytes(len as u64)?);
+        #[cfg(not(feature = "bytes"))]
+        let buffer = TLVBuffer::Vector(crate::read_bytes(r, len as u64)?);
+        Ok(buffer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for TLVBuffer<T>
+where
+    T: Borrow<[u8]>,
+{
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        crate::serde_hex::serialize_bytes(self.borrow(), s)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for TLVBuffer<T>
+where
+    T: Borrow<[u8]>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        crate::serde_hex::deserialize_bytes(d).map(TLVBuffer::Vector)
     }
 }
+
+// Raw bytes, as many as the enclosing TLV record or length prefix says.
+impl TLVWireItem for Vec<u8> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        w.write(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        crate::read_bytes(r, len as u64)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl TLVWireItem for bytes::Bytes {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        w.write(self)
+    }
+
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+        r.read_bytes(len as u64)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+}
+
+#[test]
+fn arrays() {
+    let points = [[2_u8; 33], [3_u8; 33]];
+    let mut bytes = Vec::new();
+    assert_eq!(WireItem::encode(&points, &mut bytes).unwrap(), 66);
+    assert_eq!(
+        <[[u8; 33]; 2] as WireItem>::decode(&mut &bytes[..]).unwrap(),
+        points
+    );
+
+    let mut bytes = Vec::new();
+    WireItem::encode(&[1_u16, 2, 3], &mut bytes).unwrap();
+    assert_eq!(bytes, [0, 1, 0, 2, 0, 3]);
+    assert_eq!(
+        <[u16; 3] as WireItem>::decode(&mut &bytes[..]).unwrap(),
+        [1, 2, 3]
+    );
+    assert!(<[u16; 4] as WireItem>::decode(&mut &bytes[..]).is_err());
+}
@@ -0,0 +1,261 @@
+use crate::io::Write;
+use crate::{LimitedReader, TLVWireItem, WireItem, WireRead};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+// A length or count in front of a variable-length field: a fixed-width
+// big-endian integer, or a BigSize.
+pub trait LenPrefix: WireItem {
+    fn from_len(len: usize) -> Option<Self>;
+
+    fn to_len(&self) -> Option<usize>;
+}
+
+macro_rules! impl_len_prefix_for_nums {
+    ($($num_ty:ty,)*) => {
+        $(
+            impl LenPrefix for $num_ty {
+                fn from_len(len: usize) -> Option<Self> {
+                    core::convert::TryFrom::try_from(len).ok()
+                }
+
+                fn to_len(&self) -> Option<usize> {
+                    core::convert::TryFrom::try_from(*self).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_len_prefix_for_nums!(u8, u16, u32, u64,);
+
+// The variable-length integer of BOLT #1, which must use the shortest
+// encoding for its value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct BigSize(pub u64);
+impl WireItem for BigSize {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        crate::write_varint(self.0, w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        crate::varint_len(self.0)
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let mut b = [0_u8];
+        r.read_exact(&mut b)?;
+        let (n, min) = match b[0] {
+            0xff => (<u64 as WireItem>::decode(r)?, 0x1_0000_0000),
+            0xfe => (u64::from(<u32 as WireItem>::decode(r)?), 0x10000),
+            0xfd => (u64::from(<u16 as WireItem>::decode(r)?), 0xfd),
+            n => (u64::from(n), 0),
+        };
+        if n < min {
+            return Err(crate::io::ErrorKind::InvalidData.into());
+        }
+        Ok(BigSize(n))
+    }
+}
+impl LenPrefix for BigSize {
+    fn from_len(len: usize) -> Option<Self> {
+        Some(BigSize(len as u64))
+    }
+
+    fn to_len(&self) -> Option<usize> {
+        core::convert::TryFrom::try_from(self.0).ok()
+    }
+}
+
+// An item prefixed by the length of its encoding in bytes, e.g.
+// `LenPrefixed<u16, Vec<u8>>` for a `u16` length followed by that many bytes.
+// The item must use up exactly the length it is given.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct LenPrefixed<P, T> {
+    pub value: T,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    prefix: PhantomData<P>,
+}
+impl<P, T> LenPrefixed<P, T> {
+    pub fn new(value: T) -> Self {
+        LenPrefixed {
+            value,
+            prefix: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+impl<P, T> From<T> for LenPrefixed<P, T> {
+    fn from(value: T) -> Self {
+        LenPrefixed::new(value)
+    }
+}
+impl<P, T> LenPrefixed<P, T>
+where
+    P: LenPrefix,
+    T: TLVWireItem,
+{
+    // Encodes `value` as a `LenPrefixed` without moving it into one, for the
+    // `#[len_prefix = P]` fields of `wire_message!`.
+    pub fn encode_value<W: Write>(value: &T, w: &mut W) -> crate::io::Result<usize> {
+        let len = P::from_len(TLVWireItem::encoded_len(value))
+            .ok_or(crate::io::ErrorKind::InvalidInput)?;
+        let mut count = len.encode(w)?;
+        count += TLVWireItem::encode(value, w)?;
+        Ok(count)
+    }
+
+    pub fn value_len(value: &T) -> usize {
+        let len = TLVWireItem::encoded_len(value);
+        P::from_len(len).map_or(0, |p| p.encoded_len()) + len
+    }
+}
+impl<P, T> WireItem for LenPrefixed<P, T>
+where
+    P: LenPrefix,
+    T: TLVWireItem,
+{
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Self::encode_value(&self.value, w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        Self::value_len(&self.value)
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let len = P::decode(r)?
+            .to_len()
+            .ok_or(crate::io::ErrorKind::InvalidData)?;
+        LimitedReader::decode(r, len, |r| <T as TLVWireItem>::decode(r, len)).map(LenPrefixed::new)
+    }
+}
+
+// A list of items prefixed by how many there are, e.g.
+// `Counted<u16, Vec<[u8; 64]>>` for the `htlc_signature`s of
+// `commitment_signed`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Counted<P, T> {
+    pub items: T,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    prefix: PhantomData<P>,
+}
+impl<P, T> Counted<P, T> {
+    pub fn new(items: T) -> Self {
+        Counted {
+            items,
+            prefix: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.items
+    }
+}
+impl<P, T> From<T> for Counted<P, T> {
+    fn from(items: T) -> Self {
+        Counted::new(items)
+    }
+}
+impl<P, T> Counted<P, Vec<T>>
+where
+    P: LenPrefix,
+    T: WireItem,
+{
+    // Encodes `items` as a `Counted` without moving them into one, for the
+    // `#[count_prefix = P]` fields of `wire_message!`.
+    pub fn encode_items<W: Write>(items: &[T], w: &mut W) -> crate::io::Result<usize> {
+        let len = P::from_len(items.len()).ok_or(crate::io::ErrorKind::InvalidInput)?;
+        let mut count = len.encode(w)?;
+        count += T::encode_slice(items, w)?;
+        Ok(count)
+    }
+
+    pub fn items_len(items: &[T]) -> usize {
+        let len: usize = items.iter().map(WireItem::encoded_len).sum();
+        P::from_len(items.len()).map_or(0, |p| p.encoded_len()) + len
+    }
+}
+impl<P, T> WireItem for Counted<P, Vec<T>>
+where
+    P: LenPrefix,
+    T: WireItem,
+{
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        Self::encode_items(&self.items, w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        Self::items_len(&self.items)
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let len = P::decode(r)?
+            .to_len()
+            .ok_or(crate::io::ErrorKind::InvalidData)?;
+        // The count is untrusted, so the capacity only grows as items arrive.
+        let mut items = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            items.push(T::decode(r)?);
+        }
+        Ok(Counted::new(items))
+    }
+}
+
+#[test]
+fn prefixes() {
+    let script = LenPrefixed::<u16, Vec<u8>>::new(vec![0x00, 0x14, 0xab]);
+    let mut bytes = Vec::new();
+    assert_eq!(WireItem::encode(&script, &mut bytes).unwrap(), 5);
+    assert_eq!(bytes, [0, 3, 0x00, 0x14, 0xab]);
+    assert_eq!(
+        <LenPrefixed<u16, Vec<u8>> as WireItem>::decode(&mut &bytes[..]).unwrap(),
+        script
+    );
+    assert!(<LenPrefixed<u16, Vec<u8>> as WireItem>::decode(&mut &bytes[..4]).is_err());
+
+    // A fixed-size item has to fill the length it is given.
+    assert_eq!(
+        <LenPrefixed<u8, u16> as WireItem>::decode(&mut &[2, 0, 1][..])
+            .unwrap()
+            .into_inner(),
+        1
+    );
+    assert!(<LenPrefixed<u8, u16> as WireItem>::decode(&mut &[3, 0, 1, 2][..]).is_err());
+
+    let sigs = Counted::<u16, Vec<[u8; 2]>>::new(vec![[1, 2], [3, 4]]);
+    let mut bytes = Vec::new();
+    WireItem::encode(&sigs, &mut bytes).unwrap();
+    assert_eq!(bytes, [0, 2, 1, 2, 3, 4]);
+    assert_eq!(
+        <Counted<u16, Vec<[u8; 2]>> as WireItem>::decode(&mut &bytes[..]).unwrap(),
+        sigs
+    );
+
+    let mut bytes = Vec::new();
+    WireItem::encode(
+        &LenPrefixed::<BigSize, Vec<u8>>::new(vec![7; 300]),
+        &mut bytes,
+    )
+    .unwrap();
+    assert_eq!(bytes[..3], [0xfd, 0x01, 0x2c]);
+    assert!(<BigSize as WireItem>::decode(&mut &[0xfd, 0x00, 0xfc][..]).is_err());
+}
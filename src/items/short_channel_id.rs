@@ -0,0 +1,121 @@
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+
+// The position of a channel's funding output in the chain: 3 bytes of block
+// height, 3 bytes of transaction index and 2 bytes of output index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShortChannelId(pub u64);
+impl ShortChannelId {
+    // None if the block height or transaction index take more than 3 bytes.
+    pub fn new(block_height: u32, tx_index: u32, output_index: u16) -> Option<Self> {
+        if block_height > 0xff_ffff || tx_index > 0xff_ffff {
+            return None;
+        }
+        Some(ShortChannelId(
+            u64::from(block_height) << 40 | u64::from(tx_index) << 16 | u64::from(output_index),
+        ))
+    }
+
+    pub fn block_height(&self) -> u32 {
+        (self.0 >> 40) as u32
+    }
+
+    pub fn tx_index(&self) -> u32 {
+        (self.0 >> 16) as u32 & 0xff_ffff
+    }
+
+    pub fn output_index(&self) -> u16 {
+        self.0 as u16
+    }
+}
+impl From<u64> for ShortChannelId {
+    fn from(n: u64) -> Self {
+        ShortChannelId(n)
+    }
+}
+impl WireItem for ShortChannelId {
+    const FIXED_SIZE: Option<usize> = u64::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        self.0.encode(w)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        u64::decode(r).map(ShortChannelId)
+    }
+}
+// The usual `BxTxO` form, e.g. `539268x845x1`.
+impl core::fmt::Display for ShortChannelId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}x{}x{}",
+            self.block_height(),
+            self.tx_index(),
+            self.output_index()
+        )
+    }
+}
+impl core::str::FromStr for ShortChannelId {
+    type Err = ParseShortChannelIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('x');
+        let mut next = || parts.next().ok_or(ParseShortChannelIdError);
+        let block_height = next()?.parse().map_err(|_| ParseShortChannelIdError)?;
+        let tx_index = next()?.parse().map_err(|_| ParseShortChannelIdError)?;
+        let output_index = next()?.parse().map_err(|_| ParseShortChannelIdError)?;
+        if parts.next().is_some() {
+            return Err(ParseShortChannelIdError);
+        }
+        ShortChannelId::new(block_height, tx_index, output_index).ok_or(ParseShortChannelIdError)
+    }
+}
+
+// `BxTxO` in human-readable formats, the number in the others.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShortChannelId {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            s.serialize_u64(self.0)
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ShortChannelId {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        if d.is_human_readable() {
+            <alloc::string::String as serde::Deserialize>::deserialize(d)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        } else {
+            <u64 as serde::Deserialize>::deserialize(d).map(ShortChannelId)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseShortChannelIdError;
+impl core::fmt::Display for ParseShortChannelIdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid short channel id, expected BxTxO")
+    }
+}
+impl core::error::Error for ParseShortChannelIdError {}
+
+#[test]
+fn parse_and_display() {
+    let scid: ShortChannelId = "539268x845x1".parse().unwrap();
+    assert_eq!(scid, ShortChannelId::new(539_268, 845, 1).unwrap());
+    assert_eq!(scid.to_string(), "539268x845x1");
+    assert_eq!(ShortChannelId::new(1 << 24, 0, 0), None);
+    assert!("539268x845".parse::<ShortChannelId>().is_err());
+    assert!("539268x845x1x0".parse::<ShortChannelId>().is_err());
+    assert!("539268x16777216x1".parse::<ShortChannelId>().is_err());
+}
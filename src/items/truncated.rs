@@ -0,0 +1,78 @@
+use crate::io::Write;
+use crate::{TLVWireItem, WireRead};
+
+// Truncated integers: big-endian with the leading zero bytes left out, so
+// zero takes no bytes at all. They only appear where their length is known,
+// at the end of a TLV value, and decoding rejects any other than the shortest
+// encoding.
+macro_rules! impl_truncated_nums {
+    (
+        $(
+            $(#[$attr:meta])*
+            $name:ident($num_ty:ty),
+        )*
+    ) => {
+        $(
+            $(#[$attr])*
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            #[cfg_attr(
+                feature = "serde",
+                derive(serde::Serialize, serde::Deserialize),
+                serde(transparent)
+            )]
+            pub struct $name(pub $num_ty);
+            impl From<$num_ty> for $name {
+                fn from(n: $num_ty) -> Self {
+                    $name(n)
+                }
+            }
+            impl TLVWireItem for $name {
+                fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+                    let bytes = self.0.to_be_bytes();
+                    w.write(&bytes[(self.0.leading_zeros() / 8) as usize..])
+                }
+
+                fn encoded_len(&self) -> usize {
+                    core::mem::size_of::<$num_ty>() - (self.0.leading_zeros() / 8) as usize
+                }
+
+                fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
+                    let mut bytes = [0_u8; core::mem::size_of::<$num_ty>()];
+                    if len > bytes.len() {
+                        return Err(crate::io::ErrorKind::InvalidData.into());
+                    }
+                    let start = bytes.len() - len;
+                    r.read_exact(&mut bytes[start..])?;
+                    if len > 0 && bytes[start] == 0 {
+                        return Err(crate::io::ErrorKind::InvalidData.into());
+                    }
+                    Ok($name(<$num_ty>::from_be_bytes(bytes)))
+                }
+            }
+        )*
+    };
+}
+
+impl_truncated_nums!(Tu16(u16), Tu32(u32), Tu64(u64),);
+
+#[test]
+fn minimal_encoding() {
+    let mut bytes = Vec::new();
+    TLVWireItem::encode(&Tu64(0x01_0000), &mut bytes).unwrap();
+    assert_eq!(bytes, [1, 0, 0]);
+    assert_eq!(
+        <Tu64 as TLVWireItem>::decode(&mut &bytes[..], 3).unwrap(),
+        Tu64(0x01_0000)
+    );
+
+    let mut bytes = Vec::new();
+    assert_eq!(TLVWireItem::encode(&Tu16(0), &mut bytes).unwrap(), 0);
+    assert_eq!(
+        <Tu16 as TLVWireItem>::decode(&mut &[][..], 0).unwrap(),
+        Tu16(0)
+    );
+
+    assert!(<Tu32 as TLVWireItem>::decode(&mut &[0, 1][..], 2).is_err());
+    assert!(<Tu32 as TLVWireItem>::decode(&mut &[1, 2, 3, 4, 5][..], 5).is_err());
+    assert!(<Tu32 as TLVWireItem>::decode(&mut &[1][..], 2).is_err());
+}
@@ -1,12 +1,46 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
 #[macro_use]
 extern crate lightning_wire_msgs_derive;
+extern crate alloc;
+
+// The derives write out `std::io` and `std::convert` paths, which without
+// `std` have to find the crate's own `io` and `core::convert`.
+#[cfg(not(feature = "std"))]
+extern crate self as std;
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+pub use core::convert;
+
+#[cfg(all(test, not(feature = "std")))]
+compile_error!("the tests need the `std` feature");
 
-use std::io::{Read, Write};
+use crate::io::{Read, Write};
+use alloc::vec::Vec;
 
+pub mod bech32;
+#[cfg(feature = "secp256k1")]
+pub mod bolt11;
+#[cfg(feature = "secp256k1")]
+pub mod bolt12;
+pub mod bolt2;
+#[cfg(feature = "secp256k1")]
+pub mod bolt4;
+mod display;
+#[cfg(feature = "bytes")]
+mod frame;
+pub mod io;
 pub mod items;
+pub mod messages;
+#[cfg(feature = "serde")]
+mod serde_flag;
+#[cfg(feature = "serde")]
+mod serde_hex;
+pub mod tlv;
 pub mod watchtower;
+mod wire_message;
 
-fn write_varint<W: Write>(num: u64, w: &mut W) -> std::io::Result<usize> {
+fn write_varint<W: Write>(num: u64, w: &mut W) -> crate::io::Result<usize> {
     match num {
         n if n < 0xfd => w.write(&[n as u8]),
         n if n < 0x10000 => {
@@ -30,10 +64,30 @@ fn write_varint<W: Write>(num: u64, w: &mut W) -> std::io::Result<usize> {
     }
 }
 
-fn read_varint<R: Read>(r: &mut R) -> std::io::Result<u64> {
+fn varint_len(num: u64) -> usize {
+    match num {
+        n if n < 0xfd => 1,
+        n if n < 0x10000 => 3,
+        n if n < 0x100000000 => 5,
+        _ => 9,
+    }
+}
+
+// The length of a record of `value`, header included.
+fn tlv_record_len<T: TLVWireItemWriter>(tlv_type: u64, value: &T) -> usize {
+    let len = value.encoded_len();
+    varint_len(tlv_type) + varint_len(len as u64) + len
+}
+
+fn read_varint<R: Read>(r: &mut R) -> crate::io::Result<u64> {
     let mut b = [0_u8];
     r.read_exact(&mut b)?;
-    Ok(match b[0] {
+    read_varint_from(b[0], r)
+}
+
+// Reads the rest of a varint whose first byte is `first`.
+fn read_varint_from<R: Read>(first: u8, r: &mut R) -> crate::io::Result<u64> {
+    Ok(match first {
         0xff => {
             let mut b = [0_u8; 8];
             r.read_exact(&mut b)?;
@@ -53,7 +107,8 @@ fn read_varint<R: Read>(r: &mut R) -> std::io::Result<u64> {
     })
 }
 
-fn peek_varint<'a, R: Read>(r: &mut PeekReader<'a, R>) -> std::io::Result<u64> {
+fn peek_varint<'a, R: Read>(r: &mut PeekReader<'a, R>) -> crate::io::Result<u64> {
+    r.rewind();
     let mut b = [0_u8];
     r.peek_exact(&mut b)?;
     Ok(match b[0] {
@@ -79,7 +134,7 @@ fn peek_varint<'a, R: Read>(r: &mut PeekReader<'a, R>) -> std::io::Result<u64> {
 pub trait AnyWireMessageWriter {
     fn msg_type(&self) -> u16;
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
 }
 
 pub trait AnyWireMessageReader
@@ -88,7 +143,13 @@ where
 {
     fn msg_type(&self) -> u16;
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self>;
+    fn decode<R: Read>(r: &mut R) -> crate::io::Result<Self>;
+
+    // Decodes under the limits of `r`. Messages that cannot pass them on to
+    // their items decode under the default limits.
+    fn decode_wire<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Self::decode(r)
+    }
 }
 
 pub trait AnyWireMessage
@@ -97,9 +158,17 @@ where
 {
     fn msg_type(&self) -> u16;
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
+
+    fn decode<R: Read>(r: &mut R) -> crate::io::Result<Self>;
+
+    fn decode_wire<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        Self::decode(r)
+    }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self>;
+    // The length of the encoded message up to its TLV stream, type included.
+    // Without TLV fields that is all of it.
+    fn fixed_len(&self) -> usize;
 }
 impl<T> AnyWireMessageWriter for T
 where
@@ -109,7 +178,7 @@ where
         self.msg_type()
     }
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
 }
@@ -121,9 +190,13 @@ where
         self.msg_type()
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn decode<R: Read>(r: &mut R) -> crate::io::Result<Self> {
         Self::decode(r)
     }
+
+    fn decode_wire<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        <T as AnyWireMessage>::decode_wire(r)
+    }
 }
 
 impl<T> AnyWireMessage for T
@@ -134,18 +207,38 @@ where
         T::MSG_TYPE
     }
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn decode<R: Read>(r: &mut R) -> crate::io::Result<Self> {
         T::decode(r, true)
     }
+
+    fn decode_wire<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        <T as WireMessage>::decode_wire(r, true)
+    }
+
+    fn fixed_len(&self) -> usize {
+        <T as WireMessage>::fixed_len(self)
+    }
 }
 
 pub trait WireMessageWriter {
     const MSG_TYPE: u16;
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
+
+    // The length of the encoded message, type included.
+    fn encoded_len(&self) -> usize;
+
+    fn encode_to_slice(&self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        encode_to_slice(self.encoded_len(), buf, |w| self.encode(w))
+    }
+
+    #[cfg(feature = "bytes")]
+    fn encode_to_buf<B: bytes::BufMut>(&self, buf: &mut B) -> crate::io::Result<usize> {
+        encode_to_buf(self.encoded_len(), buf, |w| self.encode(w))
+    }
 }
 
 pub trait WireMessageReader
@@ -153,7 +246,13 @@ where
     Self: Sized,
 {
     const MSG_TYPE: u16;
-    fn decode<R: Read>(r: &mut R, check_type: bool) -> std::io::Result<Self>;
+    fn decode<R: Read>(r: &mut R, check_type: bool) -> crate::io::Result<Self>;
+
+    // Decodes under the limits of `r`. Messages that cannot pass them on to
+    // their items decode under the default limits.
+    fn decode_wire<R: WireRead>(r: &mut R, check_type: bool) -> crate::io::Result<Self> {
+        Self::decode(r, check_type)
+    }
 }
 
 pub trait WireMessage
@@ -162,9 +261,22 @@ where
 {
     const MSG_TYPE: u16;
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
+
+    fn decode<R: Read>(r: &mut R, check_type: bool) -> crate::io::Result<Self>;
+
+    fn decode_wire<R: WireRead>(r: &mut R, check_type: bool) -> crate::io::Result<Self> {
+        Self::decode(r, check_type)
+    }
+
+    // The length of the encoded message, type included.
+    fn encoded_len(&self) -> usize;
 
-    fn decode<R: Read>(r: &mut R, check_type: bool) -> std::io::Result<Self>;
+    // The length of the encoded message up to its TLV stream, type included.
+    // Without TLV fields that is all of it.
+    fn fixed_len(&self) -> usize {
+        self.encoded_len()
+    }
 }
 impl<T> WireMessageWriter for T
 where
@@ -172,98 +284,565 @@ where
 {
     const MSG_TYPE: u16 = T::MSG_TYPE;
 
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
+
+    fn encoded_len(&self) -> usize {
+        WireMessage::encoded_len(self)
+    }
 }
 impl<T> WireMessageReader for T
 where
     T: WireMessage,
 {
     const MSG_TYPE: u16 = T::MSG_TYPE;
-    fn decode<R: Read>(r: &mut R, check_type: bool) -> std::io::Result<Self> {
+    fn decode<R: Read>(r: &mut R, check_type: bool) -> crate::io::Result<Self> {
         Self::decode(r, check_type)
     }
+
+    fn decode_wire<R: WireRead>(r: &mut R, check_type: bool) -> crate::io::Result<Self> {
+        <T as WireMessage>::decode_wire(r, check_type)
+    }
 }
 
+// Bounds on what a peer can make us read or allocate while decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    // The largest message BOLT #8 can carry is 65535 bytes.
+    pub max_message_size: usize,
+    // The longest variable-length byte field.
+    pub max_buffer_len: usize,
+    // The most TLV records read or skipped in one go.
+    pub max_tlv_records: usize,
+}
+impl DecodeLimits {
+    pub const DEFAULT: DecodeLimits = DecodeLimits {
+        max_message_size: 65535,
+        max_buffer_len: 65535,
+        max_tlv_records: 1024,
+    };
+
+    // Decodes a message under these limits. A message that needs more than
+    // `max_message_size` bytes fails rather than being cut short, and
+    // nothing after that many is read from `r`.
+    pub fn decode_message<T, R>(&self, r: &mut R, check_type: bool) -> crate::io::Result<T>
+    where
+        T: WireMessageReader,
+        R: Read,
+    {
+        MessageReader::decode(r, *self, |r| T::decode_wire(r, check_type))
+    }
+}
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits::DEFAULT
+    }
+}
+
+// A reader items decode from, which tells them the limits they are decoded
+// under.
+pub trait WireRead: Read {
+    fn limits(&self) -> DecodeLimits {
+        DecodeLimits::DEFAULT
+    }
+
+    // Reads a byte string of `len` bytes. Readers over `Bytes` hand out
+    // slices of them rather than copies.
+    #[cfg(feature = "bytes")]
+    fn read_bytes(&mut self, len: u64) -> crate::io::Result<bytes::Bytes> {
+        crate::read_bytes(self, len).map(bytes::Bytes::from)
+    }
+
+    // Whether a message skips TLV records of unknown even types rather than
+    // failing on them, as `TlvStream::decode_message` has it do so that it
+    // can hand them back.
+    #[doc(hidden)]
+    fn allows_unknown_even(&self) -> bool {
+        false
+    }
+}
+impl WireRead for &[u8] {}
+impl<R: WireRead + ?Sized> WireRead for &mut R {
+    fn limits(&self) -> DecodeLimits {
+        (**self).limits()
+    }
+
+    fn allows_unknown_even(&self) -> bool {
+        (**self).allows_unknown_even()
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_bytes(&mut self, len: u64) -> crate::io::Result<bytes::Bytes> {
+        (**self).read_bytes(len)
+    }
+}
+
+// The reader a message is decoded from, giving its items `limits` and
+// ending after `limits.max_message_size` bytes. Nothing past that is read
+// from `reader`, so `decode` can only tell a message that goes on from one
+// that ends there by whether it needs more: a TLV stream that reaches the
+// limit is taken to end there, as it would at the end of the reader.
+pub struct MessageReader<'a, R: Read> {
+    reader: &'a mut R,
+    limits: DecodeLimits,
+    remaining: usize,
+    overrun: bool,
+}
+impl<'a, R: Read> MessageReader<'a, R> {
+    pub fn new(reader: &'a mut R, limits: DecodeLimits) -> Self {
+        MessageReader {
+            reader,
+            limits,
+            remaining: limits.max_message_size,
+            overrun: false,
+        }
+    }
+
+    // Decodes a message from `reader`, failing with `MessageTooLarge` if it
+    // fails for want of the bytes past the limit.
+    pub fn decode<T, F>(reader: &'a mut R, limits: DecodeLimits, f: F) -> crate::io::Result<T>
+    where
+        F: FnOnce(&mut Self) -> crate::io::Result<T>,
+    {
+        let mut r = MessageReader::new(reader, limits);
+        match f(&mut r) {
+            Err(_) if r.overrun => Err(LimitError::MessageTooLarge.into()),
+            res => res,
+        }
+    }
+}
+impl<'a, R: Read> Read for MessageReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        if self.remaining == 0 {
+            self.overrun |= !buf.is_empty();
+            return Ok(0);
+        }
+        let max = buf.len().min(self.remaining);
+        let count = self.reader.read(&mut buf[..max])?;
+        self.remaining -= count;
+        Ok(count)
+    }
+}
+impl<'a, R: Read> WireRead for MessageReader<'a, R> {
+    fn limits(&self) -> DecodeLimits {
+        self.limits
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    MessageTooLarge,
+    BufferTooLarge(u64),
+    TooManyTlvRecords,
+}
+impl core::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LimitError::MessageTooLarge => write!(f, "message too large"),
+            LimitError::BufferTooLarge(len) => write!(f, "buffer of {} bytes too large", len),
+            LimitError::TooManyTlvRecords => write!(f, "too many tlv records"),
+        }
+    }
+}
+impl core::error::Error for LimitError {}
+impl From<LimitError> for crate::io::Error {
+    fn from(e: LimitError) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::InvalidData, e)
+    }
+}
+
+// Reads `len` bytes, a chunk at a time, so that a bogus length fails at the
+// end of the input rather than on allocation.
+fn read_bytes<R: WireRead + ?Sized>(r: &mut R, len: u64) -> crate::io::Result<Vec<u8>> {
+    if len > r.limits().max_buffer_len as u64 {
+        return Err(LimitError::BufferTooLarge(len).into());
+    }
+    let mut buf = Vec::new();
+    (&mut *r).take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(crate::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+// The longest lookahead a decoder needs: a BigSize TLV type.
+const MAX_PEEK: usize = 9;
+
+// A reader that can look at the next few bytes without consuming them. Each
+// run of `peek_exact` calls continues where the last left off, until
+// `rewind` goes back to the first unconsumed byte or `flush_peeked` consumes
+// everything peeked so far.
 pub struct PeekReader<'a, R: Read> {
-    peeked: Vec<u8>,
+    buf: [u8; MAX_PEEK],
+    // Bytes in `buf` read from `reader` but not yet consumed.
+    filled: usize,
+    // Bytes of those handed out by `peek_exact`.
+    peeked: usize,
     reader: &'a mut R,
+    limits: DecodeLimits,
 }
 impl<'a, R: Read> From<&'a mut R> for PeekReader<'a, R> {
     fn from(r: &'a mut R) -> Self {
+        PeekReader::with_limits(r, DecodeLimits::DEFAULT)
+    }
+}
+impl<'a, R: Read> PeekReader<'a, R> {
+    // A reader whose items decode under `limits`. `from` gives them the
+    // default limits.
+    pub fn with_limits(r: &'a mut R, limits: DecodeLimits) -> Self {
         PeekReader {
-            peeked: Vec::new(),
+            buf: [0; MAX_PEEK],
+            filled: 0,
+            peeked: 0,
             reader: r,
+            limits,
         }
     }
-}
-impl<'a, R: Read> PeekReader<'a, R> {
-    pub fn peek_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
-        self.reader.read_exact(buf)?;
-        self.peeked.extend_from_slice(buf);
+
+    pub fn peek_exact(&mut self, buf: &mut [u8]) -> crate::io::Result<()> {
+        let end = self.peeked + buf.len();
+        if end > MAX_PEEK {
+            return Err(crate::io::ErrorKind::InvalidInput.into());
+        }
+        if end > self.filled {
+            self.reader.read_exact(&mut self.buf[self.filled..end])?;
+            self.filled = end;
+        }
+        buf.copy_from_slice(&self.buf[self.peeked..end]);
+        self.peeked = end;
         Ok(())
     }
 
-    pub fn flush_peeked(&mut self) -> () {
-        self.peeked.truncate(0);
+    pub fn rewind(&mut self) {
+        self.peeked = 0;
+    }
+
+    pub fn flush_peeked(&mut self) {
+        self.buf.copy_within(self.peeked..self.filled, 0);
+        self.filled -= self.peeked;
+        self.peeked = 0;
+    }
+
+    // Consumes the next `len` bytes without keeping them anywhere.
+    pub fn skip(&mut self, len: u64) -> crate::io::Result<()> {
+        let buffered = (self.filled as u64).min(len) as usize;
+        self.buf.copy_within(buffered..self.filled, 0);
+        self.filled -= buffered;
+        self.peeked = 0;
+        let rest = len - buffered as u64;
+        if crate::io::copy(&mut self.reader.take(rest), &mut crate::io::sink())? != rest {
+            return Err(crate::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+impl<'a, R: Read> WireRead for PeekReader<'a, R> {
+    fn limits(&self) -> DecodeLimits {
+        self.limits
     }
 }
 impl<'a, R: Read> Read for PeekReader<'a, R> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let expected = buf.len();
-        let mut count = 0;
-        let mut buf_slice = buf;
-        if !self.peeked.is_empty() {
-            count += self.peeked.as_slice().read(buf_slice)?;
-            self.peeked.drain(0..count);
-            if count < expected {
-                buf_slice = &mut buf_slice[count..];
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        if self.filled == 0 {
+            return self.reader.read(buf);
+        }
+        let count = self.filled.min(buf.len());
+        buf[..count].copy_from_slice(&self.buf[..count]);
+        self.buf.copy_within(count..self.filled, 0);
+        self.filled -= count;
+        self.peeked = self.peeked.saturating_sub(count);
+        Ok(count)
+    }
+}
+
+// A reader over the next `len` bytes of another, for a TLV value or a
+// length-prefixed field, so that the item inside can neither run into what
+// follows nor leave part of its value behind.
+pub struct LimitedReader<'a, R: Read> {
+    reader: &'a mut R,
+    remaining: usize,
+    overrun: bool,
+}
+impl<'a, R: Read> LimitedReader<'a, R> {
+    pub fn new(reader: &'a mut R, len: usize) -> Self {
+        LimitedReader {
+            reader,
+            remaining: len,
+            overrun: false,
+        }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    // Decodes an item out of the next `len` bytes of `reader`, which it must
+    // use up exactly.
+    pub fn decode<T, F>(reader: &'a mut R, len: usize, f: F) -> crate::io::Result<T>
+    where
+        F: FnOnce(&mut Self) -> crate::io::Result<T>,
+    {
+        let mut limited = LimitedReader::new(reader, len);
+        let res = f(&mut limited);
+        if limited.overrun {
+            return Err(LengthError::Overrun { len }.into());
+        }
+        let item = res?;
+        if limited.remaining != 0 {
+            return Err(LengthError::Underrun {
+                len,
+                unread: limited.remaining,
             }
+            .into());
+        }
+        Ok(item)
+    }
+}
+impl<'a, R: Read> Read for LimitedReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        if self.remaining == 0 {
+            self.overrun |= !buf.is_empty();
+            return Ok(0);
+        }
+        let max = buf.len().min(self.remaining);
+        let count = self.reader.read(&mut buf[..max])?;
+        self.remaining -= count;
+        if count == 0 && max > 0 {
+            // The record itself is cut short.
+            return Err(crate::io::ErrorKind::UnexpectedEof.into());
         }
-        count += self.reader.read(buf_slice)?;
         Ok(count)
     }
 }
+impl<'a, R: WireRead> WireRead for LimitedReader<'a, R> {
+    fn limits(&self) -> DecodeLimits {
+        self.reader.limits()
+    }
+
+    fn allows_unknown_even(&self) -> bool {
+        self.reader.allows_unknown_even()
+    }
+
+    #[cfg(feature = "bytes")]
+    fn read_bytes(&mut self, len: u64) -> crate::io::Result<bytes::Bytes> {
+        if len > self.remaining as u64 {
+            self.overrun = true;
+            return Err(crate::io::ErrorKind::UnexpectedEof.into());
+        }
+        let bytes = self.reader.read_bytes(len)?;
+        self.remaining -= len as usize;
+        Ok(bytes)
+    }
+}
+
+// An item that did not fit the length of its TLV value or length prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LengthError {
+    // The item ended before the end of its value.
+    Underrun { len: usize, unread: usize },
+    // The item tried to read past the end of its value.
+    Overrun { len: usize },
+}
+impl core::fmt::Display for LengthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LengthError::Underrun { len, unread } => {
+                write!(f, "{} of {} bytes left unread", unread, len)
+            }
+            LengthError::Overrun { len } => write!(f, "item longer than its {} bytes", len),
+        }
+    }
+}
+impl core::error::Error for LengthError {}
+impl From<LengthError> for crate::io::Error {
+    fn from(e: LengthError) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::InvalidData, e)
+    }
+}
+
+// The buffer given to `encode_to_slice` or `encode_to_buf` cannot hold the
+// encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    pub needed: usize,
+    pub available: usize,
+}
+impl core::fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "encoding needs {} bytes but the buffer has {}",
+            self.needed, self.available
+        )
+    }
+}
+impl core::error::Error for BufferTooSmall {}
+impl From<BufferTooSmall> for crate::io::Error {
+    fn from(e: BufferTooSmall) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::WriteZero, e)
+    }
+}
+
+// Writes into a fixed buffer, failing rather than writing short, since items
+// count the return value of `write` instead of calling `write_all`.
+struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+impl<'a> Write for SliceWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> crate::io::Result<usize> {
+        let end = self.pos + data.len();
+        if end > self.buf.len() {
+            return Err(BufferTooSmall {
+                needed: end,
+                available: self.buf.len(),
+            }
+            .into());
+        }
+        self.buf[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn encode_to_slice<F>(needed: usize, buf: &mut [u8], encode: F) -> crate::io::Result<usize>
+where
+    F: FnOnce(&mut SliceWriter) -> crate::io::Result<usize>,
+{
+    if needed > buf.len() {
+        return Err(BufferTooSmall {
+            needed,
+            available: buf.len(),
+        }
+        .into());
+    }
+    encode(&mut SliceWriter { buf, pos: 0 })
+}
+
+#[cfg(feature = "bytes")]
+struct BufMutWriter<'a, B> {
+    buf: &'a mut B,
+}
+#[cfg(feature = "bytes")]
+impl<'a, B: bytes::BufMut> Write for BufMutWriter<'a, B> {
+    fn write(&mut self, data: &[u8]) -> crate::io::Result<usize> {
+        if data.len() > self.buf.remaining_mut() {
+            return Err(BufferTooSmall {
+                needed: data.len(),
+                available: self.buf.remaining_mut(),
+            }
+            .into());
+        }
+        self.buf.put_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> crate::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes")]
+fn encode_to_buf<B, F>(needed: usize, buf: &mut B, encode: F) -> crate::io::Result<usize>
+where
+    B: bytes::BufMut,
+    F: FnOnce(&mut BufMutWriter<B>) -> crate::io::Result<usize>,
+{
+    if needed > buf.remaining_mut() {
+        return Err(BufferTooSmall {
+            needed,
+            available: buf.remaining_mut(),
+        }
+        .into());
+    }
+    encode(&mut BufMutWriter { buf })
+}
 
 pub trait WireItemWriter {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
+
+    // The number of bytes `encode` writes.
+    fn encoded_len(&self) -> usize;
+
+    fn encode_to_slice(&self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        encode_to_slice(self.encoded_len(), buf, |w| self.encode(w))
+    }
+
+    #[cfg(feature = "bytes")]
+    fn encode_to_buf<B: bytes::BufMut>(&self, buf: &mut B) -> crate::io::Result<usize> {
+        encode_to_buf(self.encoded_len(), buf, |w| self.encode(w))
+    }
 }
 
 pub trait WireItemReader
 where
     Self: Sized,
 {
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self>;
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self>;
 }
 
 pub trait WireItem
 where
     Self: Sized,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    // The encoded size of every value of a fixed-layout type.
+    const FIXED_SIZE: Option<usize> = None;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self>;
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self>;
+    // The number of bytes `encode` writes, which for a fixed-layout type is
+    // `FIXED_SIZE`.
+    fn encoded_len(&self) -> usize;
+
+    // Used by the `[T; N]` impl, so bytes can be copied in one go rather
+    // than one at a time.
+    #[doc(hidden)]
+    fn encode_slice<W: Write>(items: &[Self], w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        for item in items {
+            count += item.encode(w)?;
+        }
+        Ok(count)
+    }
+
+    #[doc(hidden)]
+    fn decode_array<R: WireRead, const N: usize>(r: &mut R) -> crate::io::Result<[Self; N]> {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(Self::decode(r)?);
+        }
+        match core::convert::TryFrom::try_from(items) {
+            Ok(items) => Ok(items),
+            Err(_) => unreachable!("decoded exactly N items"),
+        }
+    }
 }
 impl<T> WireItemReader for T
 where
     T: WireItem,
 {
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         T::decode(r)
     }
 }
 
 pub trait TLVWireItemWriter {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
 
-    fn encode_tlv<W: Write>(&self, w: &mut W, tlv_type: u64) -> std::io::Result<usize> {
-        let mut count = 0;
-        let mut data = Vec::new();
-        self.encode(&mut data)?;
-        count += write_varint(tlv_type, w)?;
-        count += write_varint(data.len() as u64, w)?;
-        count += w.write(&data)?;
+    // The number of bytes `encode` writes, the length of the record.
+    fn encoded_len(&self) -> usize;
+
+    fn encode_tlv<W: Write>(&self, w: &mut W, tlv_type: u64) -> crate::io::Result<usize> {
+        let mut count = write_varint(tlv_type, w)?;
+        count += write_varint(self.encoded_len() as u64, w)?;
+        count += self.encode(w)?;
         Ok(count)
     }
 }
@@ -272,18 +851,18 @@ pub trait TLVWireItemReader
 where
     Self: Sized,
 {
-    fn decode<R: Read>(r: &mut R, len: usize) -> std::io::Result<Self>;
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self>;
 
     fn decode_tlv<'a, R: Read>(
         reader: &mut PeekReader<'a, R>,
         tlv_type: u64,
-    ) -> std::io::Result<Option<Self>> {
-        loop {
-            use std::cmp::Ordering::*;
+    ) -> crate::io::Result<Option<Self>> {
+        for _ in 0..reader.limits().max_tlv_records {
+            use core::cmp::Ordering::*;
 
             let t = match peek_varint(reader) {
                 Ok(t) => t,
-                Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(ref e) if e.kind() == crate::io::ErrorKind::UnexpectedEof => return Ok(None),
                 Err(e) => return Err(e),
             };
             match t.cmp(&tlv_type) {
@@ -291,39 +870,131 @@ where
                 Equal => {
                     reader.flush_peeked();
                     let len = read_varint(reader)? as usize;
-                    return Ok(Some(Self::decode(reader, len)?));
+                    return LimitedReader::decode(reader, len, |r| Self::decode(r, len)).map(Some);
                 }
                 Less => {
                     reader.flush_peeked();
-                    let skip = read_varint(reader)? as usize;
-                    reader.read_exact(&mut vec![0_u8; skip])?;
+                    let skip = read_varint(reader)?;
+                    reader.skip(skip)?;
+                }
+            }
+        }
+        Err(LimitError::TooManyTlvRecords.into())
+    }
+}
+
+// Reads the TLV fields of a message, in order of type, holding on to the
+// header of a record until the field of its type, if any, is reached. The
+// records between fields are checked as they are skipped, and `finish` checks
+// the ones after the last field.
+#[derive(Default)]
+pub(crate) struct TlvFields {
+    next: Option<(u64, u64)>,
+    last: Option<u64>,
+    records: usize,
+}
+impl TlvFields {
+    pub(crate) fn decode<T, R>(&mut self, r: &mut R, tlv_type: u64) -> crate::io::Result<Option<T>>
+    where
+        T: TLVWireItemReader,
+        R: WireRead,
+    {
+        loop {
+            use core::cmp::Ordering::*;
+
+            let (t, len) = match self.next_record(r)? {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+            match t.cmp(&tlv_type) {
+                Greater => {
+                    self.next = Some((t, len));
+                    return Ok(None);
                 }
+                Equal => {
+                    let len = len as usize;
+                    return LimitedReader::decode(r, len, |r| T::decode(r, len)).map(Some);
+                }
+                Less => skip_record(r, t, len)?,
             }
         }
     }
+
+    // Reads the rest of the stream, none of which is a field.
+    pub(crate) fn finish<R: WireRead>(mut self, r: &mut R) -> crate::io::Result<()> {
+        while let Some((t, len)) = self.next_record(r)? {
+            skip_record(r, t, len)?;
+        }
+        Ok(())
+    }
+
+    // The header of the next record, or None at the end of the stream.
+    fn next_record<R: WireRead>(&mut self, r: &mut R) -> crate::io::Result<Option<(u64, u64)>> {
+        use crate::tlv::TlvStreamError;
+
+        if let Some(next) = self.next.take() {
+            return Ok(Some(next));
+        }
+        let mut first = [0_u8];
+        if r.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        if self.records == r.limits().max_tlv_records {
+            return Err(LimitError::TooManyTlvRecords.into());
+        }
+        let truncated = |_| crate::io::Error::from(TlvStreamError::Truncated);
+        let t = read_varint_from(first[0], r).map_err(truncated)?;
+        if matches!(self.last, Some(last) if t <= last) {
+            return Err(TlvStreamError::NotIncreasing(t).into());
+        }
+        self.last = Some(t);
+        self.records += 1;
+        Ok(Some((t, read_varint(r).map_err(truncated)?)))
+    }
+}
+
+// Skips a record no field takes, which must be of an odd type.
+fn skip_record<R: WireRead>(r: &mut R, tlv_type: u64, len: u64) -> crate::io::Result<()> {
+    use crate::tlv::TlvStreamError;
+
+    if tlv_type & 1 == 0 && !r.allows_unknown_even() {
+        return Err(TlvStreamError::UnknownEvenType(tlv_type).into());
+    }
+    let skipped = crate::io::copy(&mut r.by_ref().take(len), &mut crate::io::sink())?;
+    if skipped != len {
+        return Err(TlvStreamError::Truncated.into());
+    }
+    Ok(())
 }
 
 pub trait TLVWireItem
 where
     Self: Sized,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize>;
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize>;
 
-    fn decode<R: Read>(r: &mut R, len: usize) -> std::io::Result<Self>;
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self>;
+
+    // The number of bytes `encode` writes, the length of the record.
+    fn encoded_len(&self) -> usize;
 }
 impl<T> TLVWireItemWriter for T
 where
     T: TLVWireItem,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
+
+    fn encoded_len(&self) -> usize {
+        TLVWireItem::encoded_len(self)
+    }
 }
 impl<T> TLVWireItemReader for T
 where
     T: TLVWireItem,
 {
-    fn decode<R: Read>(r: &mut R, len: usize) -> std::io::Result<Self> {
+    fn decode<R: WireRead>(r: &mut R, len: usize) -> crate::io::Result<Self> {
         T::decode(r, len)
     }
 }
@@ -332,30 +1003,38 @@ impl<T> TLVWireItem for T
 where
     T: WireItem,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R, _: usize) -> std::io::Result<Self> {
+    fn decode<R: WireRead>(r: &mut R, _: usize) -> crate::io::Result<Self> {
         T::decode(r)
     }
+
+    fn encoded_len(&self) -> usize {
+        WireItem::encoded_len(self)
+    }
 }
 impl<T> WireItemWriter for T
 where
     T: TLVWireItem,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.encode(w)
     }
+
+    fn encoded_len(&self) -> usize {
+        TLVWireItem::encoded_len(self)
+    }
 }
 
 #[test]
 fn bench() {
+    use watchtower::items::feature::Feature;
     use watchtower::messages::Init;
     let mut features = items::feature::RawFeatureVector::new();
-    features.add(items::feature::Feature::DataLossProtectRequired);
-    features.add(items::feature::Feature::GossipQueriesRequired);
-    features.add(items::feature::Feature::InitialRoutingSync);
+    features.add(Feature::AltruistSessionsRequired);
+    features.add(Feature::AnchorCommitOptional);
     let mut init = Init {
         conn_features: features,
         chain_hash: items::hash::Hash([0; 32]),
@@ -368,7 +1047,370 @@ fn bench() {
         buf.truncate(0);
         <Init as WireMessageWriter>::encode(&init, &mut buf).expect("encode");
         assert!(&buf == &expected);
-        init = WireMessageReader::decode(&mut std::io::Cursor::new(&buf), true).expect("decode");
+        init = WireMessageReader::decode(&mut &buf[..], true).expect("decode");
     }
     println!("{:?}", now.elapsed());
 }
+
+#[test]
+fn tlv_value_length() {
+    let length_error = |e: crate::io::Error| {
+        e.get_ref()
+            .and_then(|e| e.downcast_ref::<LengthError>())
+            .copied()
+    };
+
+    // A u16 in a 3 byte record leaves a byte behind.
+    let bytes = [1, 3, 0, 1, 2];
+    let err = <u16 as TLVWireItemReader>::decode_tlv(&mut PeekReader::from(&mut &bytes[..]), 1)
+        .unwrap_err();
+    assert_eq!(
+        length_error(err),
+        Some(LengthError::Underrun { len: 3, unread: 1 })
+    );
+
+    // A u32 in a 2 byte record would run into the next one.
+    let bytes = [1, 2, 0, 1, 3, 0];
+    let err = <u32 as TLVWireItemReader>::decode_tlv(&mut PeekReader::from(&mut &bytes[..]), 1)
+        .unwrap_err();
+    assert_eq!(length_error(err), Some(LengthError::Overrun { len: 2 }));
+
+    let bytes = [1, 2, 0, 1];
+    let value = <u16 as TLVWireItemReader>::decode_tlv(&mut PeekReader::from(&mut &bytes[..]), 1);
+    assert_eq!(value.unwrap(), Some(1));
+}
+
+#[test]
+fn decode_limits() {
+    use bolt2::messages::TxAbort;
+    use items::Buffer;
+
+    let limit_error = |e: crate::io::Error| {
+        e.get_ref()
+            .and_then(|e| e.downcast_ref::<LimitError>())
+            .copied()
+    };
+
+    // A length of 2^64 - 1 fails before anything is allocated.
+    let bytes = [0xff; 9];
+    let err = <Buffer<Vec<u8>> as WireItem>::decode(&mut &bytes[..]).unwrap_err();
+    assert_eq!(limit_error(err), Some(LimitError::BufferTooLarge(u64::MAX)));
+
+    let mut bytes = vec![0, 74];
+    bytes.extend_from_slice(&[0; 32]);
+    bytes.extend_from_slice(&[0, 100]);
+    bytes.extend_from_slice(&[1; 100]);
+    let limits = DecodeLimits {
+        max_buffer_len: 99,
+        ..DecodeLimits::DEFAULT
+    };
+    let err = limits
+        .decode_message::<TxAbort, _>(&mut &bytes[..], true)
+        .unwrap_err();
+    assert_eq!(limit_error(err), Some(LimitError::BufferTooLarge(100)));
+    let msg: TxAbort = DecodeLimits::DEFAULT
+        .decode_message(&mut &bytes[..], true)
+        .unwrap();
+    assert_eq!(msg.data, [1; 100]);
+
+    // A message longer than the limit fails rather than being cut short.
+    let limits = DecodeLimits {
+        max_message_size: bytes.len() - 1,
+        ..DecodeLimits::DEFAULT
+    };
+    // Nothing past the limit is read, even of the next message.
+    let len = bytes.len();
+    bytes.push(0xaa);
+    let mut rest = &bytes[..];
+    let err = limits
+        .decode_message::<TxAbort, _>(&mut rest, true)
+        .unwrap_err();
+    assert_eq!(limit_error(err), Some(LimitError::MessageTooLarge));
+    assert_eq!(rest, &bytes[len - 1..]);
+    let limits = DecodeLimits {
+        max_message_size: len,
+        ..DecodeLimits::DEFAULT
+    };
+    let mut rest = &bytes[..];
+    assert!(limits.decode_message::<TxAbort, _>(&mut rest, true).is_ok());
+    assert_eq!(rest, [0xaa]);
+
+    // Skipping TLV records counts towards the limit.
+    let mut bytes = Vec::new();
+    for t in 0..2000_u64 {
+        write_varint(t * 2 + 1, &mut bytes).unwrap();
+        bytes.push(0);
+    }
+    let err = <u8 as TLVWireItemReader>::decode_tlv(&mut PeekReader::from(&mut &bytes[..]), 5000)
+        .unwrap_err();
+    assert_eq!(limit_error(err), Some(LimitError::TooManyTlvRecords));
+    let limits = DecodeLimits {
+        max_tlv_records: 2001,
+        ..DecodeLimits::DEFAULT
+    };
+    let mut bytes = &bytes[..];
+    let mut reader = PeekReader::with_limits(&mut bytes, limits);
+    assert_eq!(reader.limits(), limits);
+    assert_eq!(
+        <u8 as TLVWireItemReader>::decode_tlv(&mut reader, 5000).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn message_tlv_stream() {
+    use bolt2::messages::{Stfu, TxAckRbf};
+    use tlv::TlvStreamError;
+
+    let stream_error = |e: crate::io::Error| {
+        e.get_ref()
+            .and_then(|e| e.downcast_ref::<TlvStreamError>())
+            .copied()
+    };
+    let decode = |tlvs: &[u8]| {
+        let mut bytes = vec![0, 2];
+        bytes.extend_from_slice(&[1; 32]);
+        bytes.push(1);
+        bytes.extend_from_slice(tlvs);
+        <Stfu as WireMessageReader>::decode(&mut &bytes[..], true)
+    };
+
+    // Everything after the last field is checked, with or without TLV fields.
+    assert!(decode(&[]).is_ok());
+    assert!(decode(&[1, 1, 0, 3, 0]).is_ok());
+    assert_eq!(
+        stream_error(decode(&[2, 1, 0]).unwrap_err()),
+        Some(TlvStreamError::UnknownEvenType(2))
+    );
+    assert_eq!(
+        stream_error(decode(&[5, 1, 0, 3, 1, 0]).unwrap_err()),
+        Some(TlvStreamError::NotIncreasing(3))
+    );
+    assert_eq!(
+        stream_error(decode(&[1, 0, 1, 0]).unwrap_err()),
+        Some(TlvStreamError::NotIncreasing(1))
+    );
+    assert_eq!(
+        stream_error(decode(&[0xff]).unwrap_err()),
+        Some(TlvStreamError::Truncated)
+    );
+    assert_eq!(
+        stream_error(decode(&[1, 2, 0]).unwrap_err()),
+        Some(TlvStreamError::Truncated)
+    );
+
+    let decode = |tlvs: &[u8]| {
+        let mut bytes = vec![0, 73];
+        bytes.extend_from_slice(&[1; 32]);
+        bytes.extend_from_slice(tlvs);
+        <TxAckRbf as WireMessageReader>::decode(&mut &bytes[..], true)
+    };
+    let msg = decode(&[1, 0, 2, 0, 3, 0]).unwrap();
+    assert_eq!(msg.require_confirmed_inputs, Some(()));
+    assert_eq!(
+        stream_error(decode(&[2, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap_err()),
+        Some(TlvStreamError::NotIncreasing(0))
+    );
+    assert_eq!(
+        stream_error(decode(&[2, 0, 4, 0]).unwrap_err()),
+        Some(TlvStreamError::UnknownEvenType(4))
+    );
+    assert_eq!(
+        stream_error(decode(&[0xfd, 0x01]).unwrap_err()),
+        Some(TlvStreamError::Truncated)
+    );
+}
+
+#[test]
+fn peek_past_missing_tlv() {
+    // Looking for type 1 peeks at type 0xfd00, which must still be there
+    // when type 0xfd00 is looked for.
+    let bytes = [0, 1, 7, 0xfd, 0xfd, 0x00, 2, 0, 9];
+    let mut bytes = &bytes[..];
+    let mut reader = PeekReader::from(&mut bytes);
+    assert_eq!(
+        <u8 as TLVWireItemReader>::decode_tlv(&mut reader, 1).unwrap(),
+        None
+    );
+    assert_eq!(
+        <u16 as TLVWireItemReader>::decode_tlv(&mut reader, 0xfd00).unwrap(),
+        Some(9)
+    );
+    assert_eq!(
+        <u8 as TLVWireItemReader>::decode_tlv(&mut reader, 0xfe00).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn encoded_len() {
+    use bolt2::messages::TxAddInput;
+    use items::channel_id::ChannelId;
+
+    assert_eq!(<[[u8; 33]; 2] as WireItem>::FIXED_SIZE, Some(66));
+    assert_eq!(<items::prefixed::BigSize as WireItem>::FIXED_SIZE, None);
+
+    let msg = TxAddInput {
+        channel_id: ChannelId([1; 32]),
+        serial_id: 2,
+        prevtx: vec![3; 300],
+        prevtx_vout: 4,
+        sequence: 5,
+        shared_input_txid: Some([6; 32]),
+    };
+    let mut bytes = Vec::new();
+    WireMessage::encode(&msg, &mut bytes).unwrap();
+    assert_eq!(WireMessage::encoded_len(&msg), bytes.len());
+
+    let mut bytes = Vec::new();
+    TLVWireItemWriter::encode_tlv(&items::truncated::Tu64(0x0102), &mut bytes, 0xfd01).unwrap();
+    assert_eq!(bytes, [0xfd, 0xfd, 0x01, 2, 1, 2]);
+}
+
+#[test]
+fn encode_into_buffers() {
+    use items::hash::Hash;
+
+    let mut buf = [0_u8; 40];
+    assert_eq!(Hash([7; 32]).encode_to_slice(&mut buf).unwrap(), 32);
+    assert_eq!(buf[..32], [7; 32]);
+    let err = Hash([7; 32]).encode_to_slice(&mut buf[..31]).unwrap_err();
+    assert_eq!(
+        err.get_ref()
+            .and_then(|e| e.downcast_ref::<BufferTooSmall>()),
+        Some(&BufferTooSmall {
+            needed: 32,
+            available: 31
+        })
+    );
+
+    let msg = bolt2::messages::TxAbort {
+        channel_id: items::channel_id::ChannelId([1; 32]),
+        data: vec![2; 3],
+    };
+    let mut bytes = Vec::new();
+    WireMessage::encode(&msg, &mut bytes).unwrap();
+    let mut buf = [0_u8; 64];
+    assert_eq!(msg.encode_to_slice(&mut buf).unwrap(), bytes.len());
+    assert_eq!(buf[..bytes.len()], bytes[..]);
+
+    #[cfg(feature = "bytes")]
+    {
+        let mut out = bytes::BytesMut::new();
+        assert_eq!(msg.encode_to_buf(&mut out).unwrap(), bytes.len());
+        assert_eq!(out[..], bytes[..]);
+        let mut small = &mut buf[..10];
+        assert!(msg.encode_to_buf(&mut small).is_err());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_json() {
+    use items::feature::{Feature, InitFeatures};
+    use serde_json::json;
+    use watchtower::items::blob::{Flag, Type};
+    use watchtower::items::error::{BaseError, ErrorCode};
+    use watchtower::messages::{CreateSession, Error, Init};
+
+    let msg = CreateSession {
+        blob_type: Type(Flag::CommitOutputs.flag() | 1 << 5),
+        max_updates: 1024,
+        reward_base: 0,
+        reward_rate: 0,
+        sweep_fee_rate: items::fees::SatPerKWeight(12500),
+    };
+    let value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "blob_type": ["CommitOutputs", 5],
+            "max_updates": 1024,
+            "reward_base": 0,
+            "reward_rate": 0,
+            "sweep_fee_rate": 12500,
+        })
+    );
+    let decoded: CreateSession = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded.blob_type, msg.blob_type);
+
+    let mut features: InitFeatures = [
+        Feature::StaticRemoteKeyRequired,
+        Feature::GossipQueriesOptional,
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    features.set_bit(99);
+    let msg = Init {
+        conn_features: [watchtower::items::feature::Feature::AltruistSessionsOptional]
+            .iter()
+            .cloned()
+            .collect(),
+        chain_hash: items::hash::Hash([0xab; 32]),
+    };
+    let value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(
+        value["conn_features"],
+        json!(["altruist-sessions/optional"])
+    );
+    assert_eq!(value["chain_hash"], json!("ab".repeat(32)));
+    let value = serde_json::to_value(&features).unwrap();
+    assert_eq!(
+        value,
+        json!([
+            "gossip_queries/optional",
+            "option_static_remotekey/required",
+            99
+        ])
+    );
+    assert_eq!(
+        serde_json::from_value::<InitFeatures>(value).unwrap(),
+        features
+    );
+    assert!(serde_json::from_value::<InitFeatures>(json!(["gossip_queries"])).is_err());
+
+    let msg = Error::<Vec<u8>> {
+        code: ErrorCode::from(BaseError::TemporaryFailure),
+        data: items::Buffer::Other(vec![0xde, 0xad]),
+    };
+    let value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(
+        value,
+        json!({ "code": "CodeTemporaryFailure", "data": "dead" })
+    );
+    let decoded: Error<Vec<u8>> = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded.code.0, 40);
+    assert_eq!(decoded.data.to_vec(), [0xde, 0xad]);
+    let decoded: Error<Vec<u8>> =
+        serde_json::from_value(json!({ "code": 99, "data": "" })).unwrap();
+    assert_eq!(decoded.code.0, 99);
+
+    let msg = bolt2::messages::TxAddInput {
+        channel_id: items::channel_id::ChannelId([1; 32]),
+        serial_id: 2,
+        prevtx: vec![3],
+        prevtx_vout: 4,
+        sequence: 5,
+        shared_input_txid: None,
+    };
+    let value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(value["channel_id"], json!("01".repeat(32)));
+    assert_eq!(value["prevtx"], json!("03"));
+    let mut value = value.as_object().unwrap().clone();
+    value.remove("shared_input_txid");
+    let decoded: bolt2::messages::TxAddInput = serde_json::from_value(value.into()).unwrap();
+    assert_eq!(decoded.shared_input_txid, None);
+    assert!(serde_json::from_value::<items::hash::Hash>(json!("abcd")).is_err());
+
+    // Records without a value are flags rather than `null` either way.
+    let msg = bolt2::messages::TxAckRbf {
+        channel_id: items::channel_id::ChannelId([1; 32]),
+        funding_output_contribution: None,
+        require_confirmed_inputs: Some(()),
+    };
+    let value = serde_json::to_value(&msg).unwrap();
+    assert_eq!(value["require_confirmed_inputs"], json!(true));
+    let decoded: bolt2::messages::TxAckRbf = serde_json::from_value(value).unwrap();
+    assert_eq!(decoded.require_confirmed_inputs, Some(()));
+}
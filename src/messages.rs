@@ -0,0 +1,113 @@
+// Every message this crate knows, for when the type of a message is not known
+// in advance, as when inspecting captured traffic.
+use crate::bolt2::messages::{
+    AcceptChannel2, OpenChannel2, SpliceAck, SpliceInit, SpliceLocked, Stfu, TxAbort, TxAckRbf,
+    TxAddInput, TxAddOutput, TxComplete, TxInitRbf, TxRemoveInput, TxRemoveOutput, TxSignatures,
+    UpdateAddHtlc,
+};
+#[cfg(feature = "secp256k1")]
+use crate::bolt4::messages::OnionMessage;
+use crate::watchtower::messages::{
+    CreateSession, CreateSessionReply, DeleteSession, DeleteSessionReply, Error, Init, StateUpdate,
+    StateUpdateReply,
+};
+use crate::wire_message::wire_message;
+use crate::WireMessage;
+use core::borrow::Borrow;
+
+macro_rules! any_message {
+    ($(
+        $(#[$variant_attr:meta])*
+        $variant:ident($inner:ty),
+    )*) => {
+        wire_message! {
+            // `update_add_htlc` carries its whole onion.
+            #[allow(clippy::large_enum_variant)]
+            #[cfg_attr(
+                feature = "serde",
+                derive(serde::Serialize, serde::Deserialize),
+                serde(bound = "")
+            )]
+            pub enum AnyMessage<T: Borrow<[u8]>> {
+                $(
+                    $(#[$variant_attr])*
+                    $variant($inner),
+                )*
+            }
+        }
+        impl<T: Borrow<[u8]>> AnyMessage<T> {
+            // The name of the message of this type, if it is one we know.
+            pub fn name_of(msg_type: u16) -> Option<&'static str> {
+                $(
+                    $(#[$variant_attr])*
+                    {
+                        if msg_type == <$inner as WireMessage>::MSG_TYPE {
+                            return Some(stringify!($variant));
+                        }
+                    }
+                )*
+                None
+            }
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        $(#[$variant_attr])*
+                        AnyMessage::$variant(_) => stringify!($variant),
+                    )*
+                }
+            }
+        }
+    };
+}
+
+any_message! {
+    Stfu(Stfu),
+    OpenChannel2(OpenChannel2),
+    AcceptChannel2(AcceptChannel2),
+    TxAddInput(TxAddInput),
+    TxAddOutput(TxAddOutput),
+    TxRemoveInput(TxRemoveInput),
+    TxRemoveOutput(TxRemoveOutput),
+    TxComplete(TxComplete),
+    TxSignatures(TxSignatures),
+    TxInitRbf(TxInitRbf),
+    TxAckRbf(TxAckRbf),
+    TxAbort(TxAbort),
+    SpliceLocked(SpliceLocked),
+    SpliceInit(SpliceInit),
+    SpliceAck(SpliceAck),
+    UpdateAddHtlc(UpdateAddHtlc),
+    #[cfg(feature = "secp256k1")]
+    OnionMessage(OnionMessage),
+    Init(Init),
+    Error(Error<T>),
+    CreateSession(CreateSession),
+    CreateSessionReply(CreateSessionReply<T>),
+    StateUpdate(StateUpdate<T>),
+    StateUpdateReply(StateUpdateReply),
+    DeleteSession(DeleteSession),
+    DeleteSessionReply(DeleteSessionReply),
+}
+
+#[test]
+fn any_message() {
+    use crate::items::hash::Hash;
+    use crate::AnyWireMessage;
+    use alloc::vec::Vec;
+
+    assert_eq!(AnyMessage::<Vec<u8>>::name_of(74), Some("TxAbort"));
+    assert_eq!(AnyMessage::<Vec<u8>>::name_of(602), Some("CreateSession"));
+    assert_eq!(AnyMessage::<Vec<u8>>::name_of(1), None);
+
+    let msg = AnyMessage::<Vec<u8>>::Init(Init {
+        conn_features: Default::default(),
+        chain_hash: Hash([6; 32]),
+    });
+    let mut bytes = Vec::new();
+    AnyWireMessage::encode(&msg, &mut bytes).unwrap();
+    assert_eq!(&bytes[..2], &600_u16.to_be_bytes());
+    let decoded = <AnyMessage<Vec<u8>> as AnyWireMessage>::decode(&mut &bytes[..]).unwrap();
+    assert_eq!(decoded.name(), "Init");
+    assert_eq!(decoded.msg_type(), 600);
+}
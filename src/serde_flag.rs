@@ -0,0 +1,12 @@
+// TLV records without a value, `Option<()>`, as `true` or `false`: both
+// `Some(())` and None would otherwise be `null`. Fields use it with
+// `#[serde(default, with = "crate::serde_flag")]`.
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(flag: &Option<()>, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_bool(flag.is_some())
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<()>, D::Error> {
+    Ok(bool::deserialize(d)?.then_some(()))
+}
@@ -0,0 +1,185 @@
+// Byte strings as lowercase hex in human-readable formats such as JSON, and
+// as bytes in the others. Fields use it with
+// `#[serde(with = "crate::serde_hex")]`.
+use crate::items::prefixed::{Counted, LenPrefixed};
+use alloc::{string::String, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt;
+use serde::de::{Error as _, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub(crate) fn serialize_bytes<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    if s.is_human_readable() {
+        s.serialize_str(&encode(bytes))
+    } else {
+        s.serialize_bytes(bytes)
+    }
+}
+
+pub(crate) fn deserialize_bytes<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    if d.is_human_readable() {
+        d.deserialize_str(BytesVisitor)
+    } else {
+        d.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut res = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        res.push(DIGITS[(b >> 4) as usize] as char);
+        res.push(DIGITS[(b & 0xf) as usize] as char);
+    }
+    res
+}
+
+pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+    fn digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+    let pairs = s.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+struct BytesVisitor;
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a hex string or bytes")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Vec<u8>, E> {
+        decode(s).ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(s), &self))
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut res = Vec::new();
+        while let Some(b) = seq.next_element()? {
+            res.push(b);
+        }
+        Ok(res)
+    }
+}
+
+// The field types that are written as hex.
+pub(crate) trait Hex: Sized {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error>;
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error>;
+}
+
+impl Hex for Vec<u8> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self, s)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        deserialize_bytes(d)
+    }
+}
+
+impl<const N: usize> Hex for [u8; N] {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(self, s)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let bytes = deserialize_bytes(d)?;
+        let len = bytes.len();
+        <[u8; N]>::try_from(bytes)
+            .map_err(|_| D::Error::invalid_length(len, &&*alloc::format!("{} bytes", N)))
+    }
+}
+
+impl<T: Hex> Hex for Vec<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.iter().map(HexRef))
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Vec::<AsHex<T>>::deserialize(d)?
+            .into_iter()
+            .map(|a| a.0)
+            .collect())
+    }
+}
+
+impl<T: Hex> Hex for Option<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(value) => s.serialize_some(&HexRef(value)),
+            None => s.serialize_none(),
+        }
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Option::<AsHex<T>>::deserialize(d)?.map(|a| a.0))
+    }
+}
+
+impl<P> Hex for LenPrefixed<P, Vec<u8>> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serialize_bytes(&self.value, s)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        deserialize_bytes(d).map(LenPrefixed::new)
+    }
+}
+
+impl<P, T: Hex> Hex for Counted<P, Vec<T>> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.items.iter().map(HexRef))
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Counted::new(
+            Vec::<AsHex<T>>::deserialize(d)?
+                .into_iter()
+                .map(|a| a.0)
+                .collect(),
+        ))
+    }
+}
+
+struct HexRef<'a, T>(&'a T);
+impl<T: Hex> Serialize for HexRef<'_, T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        Hex::serialize(self.0, s)
+    }
+}
+
+struct AsHex<T>(T);
+impl<'de, T: Hex> Deserialize<'de> for AsHex<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        T::deserialize(d).map(AsHex)
+    }
+}
+
+pub(crate) fn serialize<T: Hex, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+    Hex::serialize(value, s)
+}
+
+pub(crate) fn deserialize<'de, T: Hex, D: Deserializer<'de>>(d: D) -> Result<T, D::Error> {
+    T::deserialize(d)
+}
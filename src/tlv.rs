@@ -0,0 +1,393 @@
+use crate::io::Write;
+use crate::items::prefixed::BigSize;
+use crate::{io::Read, AnyWireMessage, LimitError};
+use crate::{DecodeLimits, LimitedReader, TLVWireItem, WireItem, WireRead};
+use alloc::vec::Vec;
+
+// A raw TLV record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TlvRecord {
+    pub tlv_type: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))]
+    pub value: Vec<u8>,
+}
+impl TlvRecord {
+    pub fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = crate::write_varint(self.tlv_type, w)?;
+        count += crate::write_varint(self.value.len() as u64, w)?;
+        count += w.write(&self.value)?;
+        Ok(count)
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        crate::varint_len(self.tlv_type)
+            + crate::varint_len(self.value.len() as u64)
+            + self.value.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlvStreamError {
+    // A type or length is cut short or not minimally encoded, or a value is
+    // cut short.
+    Truncated,
+    // A type that is not greater than the one before it.
+    NotIncreasing(u64),
+    TooManyRecords,
+    UnknownEvenType(u64),
+}
+impl core::fmt::Display for TlvStreamError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TlvStreamError::Truncated => write!(f, "truncated tlv stream"),
+            TlvStreamError::NotIncreasing(t) => write!(f, "tlv type {} out of order", t),
+            TlvStreamError::TooManyRecords => write!(f, "too many tlv records"),
+            TlvStreamError::UnknownEvenType(t) => write!(f, "unknown even tlv type {}", t),
+        }
+    }
+}
+impl core::error::Error for TlvStreamError {}
+impl From<TlvStreamError> for crate::io::Error {
+    fn from(e: TlvStreamError) -> Self {
+        crate::io::Error::new(crate::io::ErrorKind::InvalidData, e)
+    }
+}
+
+// A whole TLV stream, for when the records are not fields of a derived
+// message: extension records, BOLT #12 signing, or records passed on as they
+// are. The records are always in strictly increasing type order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlvStream {
+    records: Vec<TlvRecord>,
+}
+impl TlvStream {
+    pub fn new() -> Self {
+        TlvStream::default()
+    }
+
+    pub fn from_records(records: Vec<TlvRecord>) -> Result<Self, TlvStreamError> {
+        for pair in records.windows(2) {
+            if pair[1].tlv_type <= pair[0].tlv_type {
+                return Err(TlvStreamError::NotIncreasing(pair[1].tlv_type));
+            }
+        }
+        Ok(TlvStream { records })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TlvStreamError> {
+        TlvStream::from_bytes_limited(bytes, DecodeLimits::DEFAULT.max_tlv_records)
+    }
+
+    fn from_bytes_limited(mut bytes: &[u8], max_records: usize) -> Result<Self, TlvStreamError> {
+        let mut records: Vec<TlvRecord> = Vec::new();
+        while !bytes.is_empty() {
+            if records.len() == max_records {
+                return Err(TlvStreamError::TooManyRecords);
+            }
+            let tlv_type = <BigSize as WireItem>::decode(&mut bytes)
+                .map_err(|_| TlvStreamError::Truncated)?
+                .0;
+            let len = <BigSize as WireItem>::decode(&mut bytes)
+                .map_err(|_| TlvStreamError::Truncated)?
+                .0;
+            if len > bytes.len() as u64 {
+                return Err(TlvStreamError::Truncated);
+            }
+            if matches!(records.last(), Some(r) if r.tlv_type >= tlv_type) {
+                return Err(TlvStreamError::NotIncreasing(tlv_type));
+            }
+            let (value, rest) = bytes.split_at(len as usize);
+            records.push(TlvRecord {
+                tlv_type,
+                value: value.to_vec(),
+            });
+            bytes = rest;
+        }
+        Ok(TlvStream { records })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        WireItem::encode(self, &mut res).expect("writing to a Vec");
+        res
+    }
+
+    pub fn records(&self) -> &[TlvRecord] {
+        &self.records
+    }
+
+    pub fn into_records(self) -> Vec<TlvRecord> {
+        self.records
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, TlvRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn position(&self, tlv_type: u64) -> Result<usize, usize> {
+        self.records.binary_search_by_key(&tlv_type, |r| r.tlv_type)
+    }
+
+    pub fn get(&self, tlv_type: u64) -> Option<&[u8]> {
+        self.position(tlv_type)
+            .ok()
+            .map(|i| self.records[i].value.as_slice())
+    }
+
+    // Decodes the value of a record, which the item must use up exactly.
+    pub fn get_item<T: TLVWireItem>(&self, tlv_type: u64) -> crate::io::Result<Option<T>> {
+        match self.get(tlv_type) {
+            Some(mut value) => {
+                let len = value.len();
+                LimitedReader::decode(&mut value, len, |r| <T as TLVWireItem>::decode(r, len))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Sets the value of a record, returning the one it replaces.
+    pub fn insert(&mut self, tlv_type: u64, value: Vec<u8>) -> Option<Vec<u8>> {
+        match self.position(tlv_type) {
+            Ok(i) => Some(core::mem::replace(&mut self.records[i].value, value)),
+            Err(i) => {
+                self.records.insert(i, TlvRecord { tlv_type, value });
+                None
+            }
+        }
+    }
+
+    pub fn insert_item<T: TLVWireItem>(
+        &mut self,
+        tlv_type: u64,
+        item: &T,
+    ) -> crate::io::Result<Option<Vec<u8>>> {
+        let mut value = Vec::new();
+        TLVWireItem::encode(item, &mut value)?;
+        Ok(self.insert(tlv_type, value))
+    }
+
+    pub fn remove(&mut self, tlv_type: u64) -> Option<Vec<u8>> {
+        self.position(tlv_type)
+            .ok()
+            .map(|i| self.records.remove(i).value)
+    }
+
+    // Fails on the first even type not in `known`, as the sender requires
+    // us to understand it.
+    pub fn check_known(&self, known: &[u64]) -> Result<(), TlvStreamError> {
+        match self
+            .records
+            .iter()
+            .find(|r| r.tlv_type & 1 == 0 && !known.contains(&r.tlv_type))
+        {
+            Some(r) => Err(TlvStreamError::UnknownEvenType(r.tlv_type)),
+            None => Ok(()),
+        }
+    }
+}
+impl TlvStream {
+    // Decodes a message up to the end of `r`, along with the records of its
+    // TLV stream that none of its fields take, which a plain decode skips.
+    // Unlike a plain decode, records of unknown even types are kept rather
+    // than failing the message, for the caller to reject with `check_known`.
+    pub fn decode_message<T, R>(r: &mut R) -> crate::io::Result<(T, TlvStream)>
+    where
+        T: AnyWireMessage,
+        R: Read,
+    {
+        let max = DecodeLimits::DEFAULT.max_message_size;
+        let mut bytes = Vec::new();
+        r.take(max as u64 + 1).read_to_end(&mut bytes)?;
+        if bytes.len() > max {
+            return Err(LimitError::MessageTooLarge.into());
+        }
+        let msg = T::decode_wire(&mut KeepUnknownEven(&bytes[..]))?;
+        let fixed_len = msg.fixed_len();
+        let mut rest = TlvStream::from_bytes(&bytes[fixed_len..])?;
+        // The records the message took are the ones it writes back.
+        let mut own = Vec::new();
+        msg.encode(&mut own)?;
+        for record in TlvStream::from_bytes(&own[fixed_len..])?.iter() {
+            rest.remove(record.tlv_type);
+        }
+        Ok((msg, rest))
+    }
+
+    // Encodes a message with these records merged into its TLV stream. They
+    // must not have the type of a record the message has itself.
+    pub fn encode_message<T, W>(&self, msg: &T, w: &mut W) -> crate::io::Result<usize>
+    where
+        T: AnyWireMessage,
+        W: Write,
+    {
+        let mut own = Vec::new();
+        msg.encode(&mut own)?;
+        let fixed_len = msg.fixed_len();
+        let mut stream = TlvStream::from_bytes(&own[fixed_len..])?;
+        for record in &self.records {
+            if stream
+                .insert(record.tlv_type, record.value.clone())
+                .is_some()
+            {
+                return Err(TlvStreamError::NotIncreasing(record.tlv_type).into());
+            }
+        }
+        let mut count = w.write(&own[..fixed_len])?;
+        count += WireItem::encode(&stream, w)?;
+        Ok(count)
+    }
+}
+
+// The bytes of a message for `decode_message`, whose records of unknown even
+// types are skipped so that they can be returned.
+struct KeepUnknownEven<'a>(&'a [u8]);
+impl<'a> Read for KeepUnknownEven<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> crate::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+impl<'a> WireRead for KeepUnknownEven<'a> {
+    fn allows_unknown_even(&self) -> bool {
+        true
+    }
+}
+
+impl<'a> IntoIterator for &'a TlvStream {
+    type Item = &'a TlvRecord;
+    type IntoIter = core::slice::Iter<'a, TlvRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.iter()
+    }
+}
+// The records, checked to be in order when deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for TlvStream {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.records, s)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TlvStream {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        TlvStream::from_records(<Vec<TlvRecord> as serde::Deserialize>::deserialize(d)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+impl WireItem for TlvStream {
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+        let mut count = 0;
+        for record in &self.records {
+            count += record.encode(w)?;
+        }
+        Ok(count)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.records.iter().map(TlvRecord::encoded_len).sum()
+    }
+
+    // Reads up to the end of `r`.
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+        Ok(TlvStream::from_bytes_limited(
+            &bytes,
+            r.limits().max_tlv_records,
+        )?)
+    }
+}
+
+#[test]
+fn stream() {
+    use crate::items::truncated::Tu64;
+
+    let mut stream = TlvStream::new();
+    stream.insert_item(3, &Tu64(0x0102)).unwrap();
+    stream.insert(1, vec![0xaa]);
+    assert_eq!(stream.insert(3, vec![7]), Some(vec![1, 2]));
+    stream.insert(0xfd01, vec![]);
+    let bytes = stream.to_bytes();
+    assert_eq!(bytes, [1, 1, 0xaa, 3, 1, 7, 0xfd, 0xfd, 0x01, 0]);
+
+    let mut decoded = TlvStream::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded, stream);
+    assert_eq!(decoded.get_item::<Tu64>(3).unwrap(), Some(Tu64(7)));
+    assert!(decoded.get_item::<u16>(3).is_err());
+    assert_eq!(decoded.check_known(&[]), Ok(()));
+    decoded.insert(2, vec![]);
+    assert_eq!(
+        decoded.check_known(&[]),
+        Err(TlvStreamError::UnknownEvenType(2))
+    );
+    assert_eq!(decoded.remove(2), Some(vec![]));
+    assert_eq!(decoded.remove(2), None);
+    assert_eq!(
+        decoded.iter().map(|r| r.tlv_type).collect::<Vec<_>>(),
+        [1, 3, 0xfd01]
+    );
+
+    assert_eq!(
+        TlvStream::from_bytes(&[3, 0, 1, 0]),
+        Err(TlvStreamError::NotIncreasing(1))
+    );
+    assert_eq!(
+        TlvStream::from_bytes(&[3, 0, 3, 0]),
+        Err(TlvStreamError::NotIncreasing(3))
+    );
+    assert_eq!(
+        TlvStream::from_bytes(&[1, 2, 0]),
+        Err(TlvStreamError::Truncated)
+    );
+    assert_eq!(
+        TlvStream::from_bytes(&[0xfd, 0x00, 0x01, 0]),
+        Err(TlvStreamError::Truncated)
+    );
+}
+
+#[test]
+fn extra_records() {
+    use crate::bolt2::messages::{TxAckRbf, TxComplete};
+
+    let mut bytes = vec![0, 73];
+    bytes.extend_from_slice(&[7; 32]);
+    bytes.extend_from_slice(&[1, 1, 0xaa, 2, 0, 5, 1, 0xbb]);
+    let (msg, extra) = TlvStream::decode_message::<TxAckRbf, _>(&mut &bytes[..]).unwrap();
+    assert_eq!(msg.funding_output_contribution, None);
+    assert_eq!(msg.require_confirmed_inputs, Some(()));
+    assert_eq!(extra.iter().map(|r| r.tlv_type).collect::<Vec<_>>(), [1, 5]);
+    assert_eq!(crate::WireMessage::fixed_len(&msg), 34);
+    let mut encoded = Vec::new();
+    extra.encode_message(&msg, &mut encoded).unwrap();
+    assert_eq!(encoded, bytes);
+
+    // A record of a type the message has itself cannot be added.
+    let mut clash = extra.clone();
+    clash.insert(2, vec![]);
+    assert!(clash.encode_message(&msg, &mut Vec::new()).is_err());
+
+    // The whole stream is checked, not just the records that are looked for.
+    let mut bytes = vec![0, 73];
+    bytes.extend_from_slice(&[7; 32]);
+    bytes.extend_from_slice(&[2, 0, 1, 0]);
+    assert!(TlvStream::decode_message::<TxAckRbf, _>(&mut &bytes[..]).is_err());
+
+    // Without TLV fields the stream is whatever follows the message.
+    let mut bytes = vec![0, 70];
+    bytes.extend_from_slice(&[7; 32]);
+    bytes.extend_from_slice(&[3, 1, 9]);
+    let (_, extra) = TlvStream::decode_message::<TxComplete, _>(&mut &bytes[..]).unwrap();
+    assert_eq!(extra.get(3), Some(&[9][..]));
+    bytes.push(0xfd);
+    assert!(TlvStream::decode_message::<TxComplete, _>(&mut &bytes[..]).is_err());
+}
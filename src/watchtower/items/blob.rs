@@ -1,8 +1,9 @@
-use crate::WireItem;
-use std::convert::TryFrom;
-use std::io::{Read, Write};
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+use core::convert::TryFrom;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum Flag {
     Reward = 0,
@@ -20,8 +21,8 @@ impl Flag {
         [Reward, CommitOutputs]
     }
 }
-impl std::fmt::Display for Flag {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Flag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Flag{:?}", self)
     }
 }
@@ -36,10 +37,10 @@ impl Type {
         self.0 == t as u16
     }
     pub fn has_unknown_flags(&self) -> bool {
-        std::iter::successors(Some(15), |i| if *i > 0 { Some(i - 1) } else { None })
+        core::iter::successors(Some(15), |i| if *i > 0 { Some(i - 1) } else { None })
             .filter(|i: &u16| Flag::try_from(*i).is_err())
             .map(|i| 1 << i)
-            .filter(|i| self.0 & i == 0)
+            .filter(|i| self.0 & i != 0)
             .next()
             .is_some()
     }
@@ -52,22 +53,28 @@ where
         Type(u16::from(t))
     }
 }
-impl std::iter::FromIterator<Flag> for Type {
+impl core::iter::FromIterator<Flag> for Type {
     fn from_iter<I: IntoIterator<Item = Flag>>(iter: I) -> Self {
         Type(iter.into_iter().fold(0_u16, |acc, x| acc & x.flag()))
     }
 }
 impl WireItem for Type {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = u16::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         self.0.encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         u16::decode(r).map(Type)
     }
 }
-impl std::fmt::Display for Type {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Type {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         if self.has_unknown_flags() {
             write!(f, "{:016b}", self.0)?;
         }
@@ -90,7 +97,62 @@ impl std::fmt::Display for Type {
     }
 }
 
+// The flags that are set in human-readable formats, e.g.
+// `["Reward", "CommitOutputs"]`, with unknown ones as their bit numbers. The
+// other formats get the number.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Type {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+        if !s.is_human_readable() {
+            return s.serialize_u16(self.0);
+        }
+        let bits = (0..16_u16).filter(|i| self.0 & 1 << i != 0);
+        let mut seq = s.serialize_seq(Some(bits.clone().count()))?;
+        for bit in bits {
+            match Flag::try_from(bit) {
+                Ok(flag) => seq.serialize_element(&flag)?,
+                Err(_) => seq.serialize_element(&bit)?,
+            }
+        }
+        seq.end()
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Type {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Bit {
+            Flag(Flag),
+            Number(u16),
+        }
+
+        if !d.is_human_readable() {
+            return u16::deserialize(d).map(Type);
+        }
+        let mut t = 0_u16;
+        for bit in alloc::vec::Vec::<Bit>::deserialize(d)? {
+            t |= match bit {
+                Bit::Flag(flag) => flag.flag(),
+                Bit::Number(bit) if bit < 16 => 1 << bit,
+                Bit::Number(bit) => {
+                    return Err(D::Error::custom(alloc::format!(
+                        "flag bit {} out of range",
+                        bit
+                    )))
+                }
+            };
+        }
+        Ok(Type(t))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum KnownType {
     TypeAltruistCommit = Flag::CommitOutputs.flag(),
@@ -102,12 +164,18 @@ impl From<KnownType> for u16 {
     }
 }
 impl WireItem for KnownType {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = u16::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         (*self as u16).encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
-        KnownType::try_from(u16::decode(r)?).map_err(|_| std::io::ErrorKind::InvalidData.into())
+    fn encoded_len(&self) -> usize {
+        2
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
+        KnownType::try_from(u16::decode(r)?).map_err(|_| crate::io::ErrorKind::InvalidData.into())
     }
 }
 impl TryFrom<Type> for KnownType {
@@ -117,3 +185,12 @@ impl TryFrom<Type> for KnownType {
         KnownType::try_from(t.0).map_err(Type)
     }
 }
+
+#[test]
+fn unknown_flags() {
+    assert!(!Type(Flag::CommitOutputs.flag()).has_unknown_flags());
+    assert!(!Type(Flag::Reward.flag() | Flag::CommitOutputs.flag()).has_unknown_flags());
+    assert!(!Type(0).has_unknown_flags());
+    assert!(Type(Flag::CommitOutputs.flag() | 1 << 5).has_unknown_flags());
+    assert!(Type(1 << 15).has_unknown_flags());
+}
@@ -1,6 +1,7 @@
-use crate::WireItem;
-use std::convert::TryFrom;
-use std::io::{Read, Write};
+use crate::io::Write;
+use crate::{WireItem, WireRead};
+use alloc::format;
+use core::convert::TryFrom;
 
 #[derive(Clone, Copy, Debug)]
 pub struct ErrorCode(pub u16);
@@ -9,8 +10,8 @@ impl ErrorCode {
         self.0 == BaseError::OK as u16
     }
 }
-impl std::fmt::Display for ErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             "{}",
@@ -26,12 +27,60 @@ impl std::fmt::Display for ErrorCode {
         )
     }
 }
+
+// The symbolic name, as displayed, in human-readable formats, or the number
+// if the code is not one we know. The other formats get the number.
+#[cfg(feature = "serde")]
+const KNOWN_CODES: &[u16] = &[0, 40, 50, 60, 61, 62, 63, 64, 70, 71, 72, 80];
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorCode {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() && KNOWN_CODES.contains(&self.0) {
+            s.collect_str(self)
+        } else {
+            s.serialize_u16(self.0)
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ErrorCode {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        use alloc::string::{String, ToString};
+        use serde::de::Error;
+        use serde::Deserialize;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Code {
+            Name(String),
+            Number(u16),
+        }
+
+        if !d.is_human_readable() {
+            return u16::deserialize(d).map(ErrorCode);
+        }
+        match Code::deserialize(d)? {
+            Code::Number(code) => Ok(ErrorCode(code)),
+            Code::Name(name) => KNOWN_CODES
+                .iter()
+                .map(|code| ErrorCode(*code))
+                .find(|code| code.to_string() == name)
+                .ok_or_else(|| D::Error::custom(format!("unknown error code {}", name))),
+        }
+    }
+}
 impl WireItem for ErrorCode {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = Some(2);
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         w.write(&u16::to_be_bytes(self.0))
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        2
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         let mut code = [0_u8; 2];
         r.read_exact(&mut code)?;
         Ok(ErrorCode(u16::from_be_bytes(code)))
@@ -42,7 +91,9 @@ where
     ErrorCode: From<T>,
     T: Copy + TryFrom<u16>,
 {
-    fn encode<W: Write>(&self, w: &mut W) -> std::io::Result<usize> {
+    const FIXED_SIZE: Option<usize> = ErrorCode::FIXED_SIZE;
+
+    fn encode<W: Write>(&self, w: &mut W) -> crate::io::Result<usize> {
         if let Some(t) = self {
             ErrorCode::from(*t)
         } else {
@@ -51,19 +102,24 @@ where
         .encode(w)
     }
 
-    fn decode<R: Read>(r: &mut R) -> std::io::Result<Self> {
+    fn encoded_len(&self) -> usize {
+        2
+    }
+
+    fn decode<R: WireRead>(r: &mut R) -> crate::io::Result<Self> {
         let ecode = ErrorCode::decode(r)?;
         if ecode.is_ok() {
             Ok(None)
         } else {
             T::try_from(ecode.0)
                 .map(Some)
-                .map_err(|_| std::io::ErrorKind::InvalidData.into())
+                .map_err(|_| crate::io::ErrorKind::InvalidData.into())
         }
     }
 }
 
 #[derive(Clone, Copy, Debug, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum BaseError {
     // CodeOK signals that the request was successfully processed by the
@@ -85,6 +141,7 @@ impl From<BaseError> for ErrorCode {
 }
 
 #[derive(Clone, Copy, Debug, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum CreateSessionError {
     // CreateSessionCodeAlreadyExists is returned when a session is already
@@ -116,6 +173,7 @@ impl From<CreateSessionError> for ErrorCode {
 }
 
 #[derive(Clone, Copy, Debug, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum StateUpdateError {
     // StateUpdateCodeClientBehind signals that the client's sequence number
@@ -147,6 +205,7 @@ impl From<StateUpdateError> for ErrorCode {
 }
 
 #[derive(Clone, Copy, Debug, TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 pub enum DeleteSessionError {
     // DeleteSessionCodeNotFound is returned when the watchtower does not
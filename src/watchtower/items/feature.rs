@@ -0,0 +1,40 @@
+use crate::items::feature::feature_set;
+
+feature_set!(
+    // Feature bits negotiated between a watchtower client and tower in the
+    // `Init` message. These live in their own namespace and are unrelated to
+    // the BOLT #9 bits in `items::feature::Feature`.
+    pub enum Feature {
+        // AltruistSessionsRequired specifies that the advertising node
+        // requires the remote party to understand the protocol for creating
+        // and updating watchtower sessions.
+        AltruistSessionsRequired = 0 => "altruist-sessions",
+
+        // AltruistSessionsOptional specifies that the advertising node can
+        // support a remote party who understand the protocol for creating and
+        // updating watchtower sessions.
+        AltruistSessionsOptional = 1 => "altruist-sessions",
+
+        // AnchorCommitRequired specifies that the advertising tower requires
+        // the remote party to negotiate sessions for protecting anchor
+        // channels.
+        AnchorCommitRequired = 2 => "anchor-commit",
+
+        // AnchorCommitOptional specifies that the advertising tower allows
+        // the remote party to negotiate sessions for protecting anchor
+        // channels.
+        AnchorCommitOptional = 3 => "anchor-commit",
+
+        // TaprootCommitRequired specifies that the advertising tower requires
+        // the remote party to negotiate sessions for protecting taproot
+        // channels.
+        TaprootCommitRequired = 4 => "taproot-commit",
+
+        // TaprootCommitOptional specifies that the advertising tower allows
+        // the remote party to negotiate sessions for protecting taproot
+        // channels.
+        TaprootCommitOptional = 5 => "taproot-commit",
+    }
+);
+
+pub type Features = crate::items::feature::RawFeatureVector<Feature>;
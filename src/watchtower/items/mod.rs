@@ -1,2 +1,3 @@
 pub mod blob;
 pub mod error;
+pub mod feature;
@@ -0,0 +1,349 @@
+// Defines a message struct, or an enum of messages, as written, implements
+// `WireMessage` or `AnyWireMessage` for it, and gives it a `Display` listing
+// its fields by name. A message struct starts with its `#[msg_type = N]`,
+// and each of its TLV fields with its `#[tlv_type = N]`.
+//
+// The messages of this crate use it rather than the derives from
+// lightning-wire-msgs-derive, whose decoders read through a `PeekReader` they
+// create themselves and so always decode under `DecodeLimits::DEFAULT`.
+macro_rules! wire_message {
+    (
+        @display $name:ident <$($gen:ident: $bound:path),*>
+        [$(($kind:ident $field:ident $($arg:tt)?))*]
+    ) => {
+        impl<$($gen: $bound),*> core::fmt::Display for $name<$($gen),*> {
+            #[allow(unused_mut)]
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                let mut first = true;
+                write!(f, "{}(", stringify!($name))?;
+                $(
+                    crate::wire_message::wire_message!(@show self f first $kind $field);
+                )*
+                write!(f, ")")
+            }
+        }
+    };
+    (@show $self:ident $f:ident $first:ident count $field:ident) => {
+        crate::display::fmt_named(
+            $f,
+            &mut $first,
+            stringify!($field),
+            &crate::display::Items(&$self.$field),
+        )?
+    };
+    (@show $self:ident $f:ident $first:ident $kind:ident $field:ident) => {
+        crate::display::fmt_named($f, &mut $first, stringify!($field), &$self.$field)?
+    };
+    (@encode $self:ident $w:ident field $field:ident) => {
+        crate::WireItemWriter::encode(&$self.$field, $w)?
+    };
+    (@encode $self:ident $w:ident len $field:ident $prefix:ty) => {
+        crate::items::prefixed::LenPrefixed::<$prefix, _>::encode_value(&$self.$field, $w)?
+    };
+    (@encode $self:ident $w:ident count $field:ident $prefix:ty) => {
+        crate::items::prefixed::Counted::<$prefix, _>::encode_items(&$self.$field, $w)?
+    };
+    (@encode $self:ident $w:ident tlv $field:ident $tlv_type:literal) => {
+        match &$self.$field {
+            Some(value) => crate::TLVWireItemWriter::encode_tlv(value, $w, $tlv_type)?,
+            None => 0,
+        }
+    };
+    (@len $self:ident field $field:ident) => {
+        crate::WireItemWriter::encoded_len(&$self.$field)
+    };
+    (@len $self:ident len $field:ident $prefix:ty) => {
+        crate::items::prefixed::LenPrefixed::<$prefix, _>::value_len(&$self.$field)
+    };
+    (@len $self:ident count $field:ident $prefix:ty) => {
+        crate::items::prefixed::Counted::<$prefix, _>::items_len(&$self.$field)
+    };
+    (@len $self:ident tlv $field:ident $tlv_type:literal) => {
+        match &$self.$field {
+            Some(value) => crate::tlv_record_len($tlv_type, value),
+            None => 0,
+        }
+    };
+    (@fixed_len $self:ident tlv $field:ident $tlv_type:literal) => {
+        0
+    };
+    (@fixed_len $self:ident $kind:ident $field:ident $($prefix:ty)?) => {
+        crate::wire_message::wire_message!(@len $self $kind $field $($prefix)?)
+    };
+    (@decode $r:ident $tlvs:ident field) => {
+        crate::WireItemReader::decode($r)?
+    };
+    (@decode $r:ident $tlvs:ident len $prefix:ty) => {
+        <crate::items::prefixed::LenPrefixed<$prefix, _> as crate::WireItem>::decode($r)?.value
+    };
+    (@decode $r:ident $tlvs:ident count $prefix:ty) => {
+        <crate::items::prefixed::Counted<$prefix, _> as crate::WireItem>::decode($r)?.items
+    };
+    (@decode $r:ident $tlvs:ident tlv $tlv_type:literal) => {
+        $tlvs.decode($r, $tlv_type)?
+    };
+    // Takes the fields one at a time, stripping the `#[tlv_type = N]` of
+    // those that are TLV records and the `#[len_prefix = P]` or
+    // `#[count_prefix = P]` of those whose prefix it picks.
+    (
+        @fields $head:tt [$($fields:tt)*] [$($items:tt)*]
+        #[len_prefix = $prefix:ty]
+        $(#[$field_attr:meta])*
+        $field_vis:vis $field:ident: $ty:ty,
+        $($rest:tt)*
+    ) => {
+        crate::wire_message::wire_message!(
+            @fields $head
+            [$($fields)* $(#[$field_attr])* $field_vis $field: $ty,]
+            [$($items)* (len $field $prefix)]
+            $($rest)*
+        );
+    };
+    (
+        @fields $head:tt [$($fields:tt)*] [$($items:tt)*]
+        #[count_prefix = $prefix:ty]
+        $(#[$field_attr:meta])*
+        $field_vis:vis $field:ident: $ty:ty,
+        $($rest:tt)*
+    ) => {
+        crate::wire_message::wire_message!(
+            @fields $head
+            [$($fields)* $(#[$field_attr])* $field_vis $field: $ty,]
+            [$($items)* (count $field $prefix)]
+            $($rest)*
+        );
+    };
+    (
+        @fields $head:tt [$($fields:tt)*] [$($items:tt)*]
+        #[tlv_type = $tlv_type:literal]
+        $(#[$field_attr:meta])*
+        $field_vis:vis $field:ident: $ty:ty,
+        $($rest:tt)*
+    ) => {
+        crate::wire_message::wire_message!(
+            @fields $head
+            [$($fields)* $(#[$field_attr])* $field_vis $field: $ty,]
+            [$($items)* (tlv $field $tlv_type)]
+            $($rest)*
+        );
+    };
+    (
+        @fields $head:tt [$($fields:tt)*] [$($items:tt)*]
+        $(#[$field_attr:meta])*
+        $field_vis:vis $field:ident: $ty:ty,
+        $($rest:tt)*
+    ) => {
+        crate::wire_message::wire_message!(
+            @fields $head
+            [$($fields)* $(#[$field_attr])* $field_vis $field: $ty,]
+            [$($items)* (field $field)]
+            $($rest)*
+        );
+    };
+    (
+        @fields {
+            $msg_type:literal
+            $(#[$attr:meta])*
+            $vis:vis struct $name:ident <$($gen:ident: $bound:path),*>
+        }
+        [$($fields:tt)*]
+        [$(($kind:ident $field:ident $($arg:tt)?))*]
+    ) => {
+        $(#[$attr])*
+        $vis struct $name<$($gen: $bound),*> {
+            $($fields)*
+        }
+        crate::wire_message::wire_message!(
+            @display $name<$($gen: $bound),*> [$(($kind $field $($arg)?))*]
+        );
+        impl<$($gen: $bound),*> crate::WireMessage for $name<$($gen),*> {
+            const MSG_TYPE: u16 = $msg_type;
+
+            fn encode<W: crate::io::Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+                #[allow(unused_mut)]
+                let mut count = w.write(&u16::to_be_bytes(Self::MSG_TYPE))?;
+                $(
+                    count += crate::wire_message::wire_message!(
+                        @encode self w $kind $field $($arg)?
+                    );
+                )*
+                Ok(count)
+            }
+
+            fn decode<R: crate::io::Read>(r: &mut R, check_type: bool) -> crate::io::Result<Self> {
+                crate::MessageReader::decode(r, crate::DecodeLimits::DEFAULT, |r| {
+                    <Self as crate::WireMessage>::decode_wire(r, check_type)
+                })
+            }
+
+            fn decode_wire<R: crate::WireRead>(
+                r: &mut R,
+                check_type: bool,
+            ) -> crate::io::Result<Self> {
+                if check_type {
+                    let mut msg_type = [0_u8; 2];
+                    r.read_exact(&mut msg_type)?;
+                    if u16::from_be_bytes(msg_type) != Self::MSG_TYPE {
+                        return Err(crate::io::ErrorKind::InvalidData.into());
+                    }
+                }
+                #[allow(unused_mut)]
+                let mut tlvs = crate::TlvFields::default();
+                let msg = $name {
+                    $(
+                        $field: crate::wire_message::wire_message!(
+                            @decode r tlvs $kind $($arg)?
+                        ),
+                    )*
+                };
+                tlvs.finish(r)?;
+                Ok(msg)
+            }
+
+            fn encoded_len(&self) -> usize {
+                2 $(+ crate::wire_message::wire_message!(@len self $kind $field $($arg)?))*
+            }
+
+            fn fixed_len(&self) -> usize {
+                2 $(+ crate::wire_message::wire_message!(@fixed_len self $kind $field $($arg)?))*
+            }
+        }
+    };
+    (
+        #[msg_type = $msg_type:literal]
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident $(<$($gen:ident: $bound:path),*>)? {
+            $($body:tt)*
+        }
+    ) => {
+        crate::wire_message::wire_message!(
+            @fields {
+                $msg_type
+                $(#[$attr])*
+                $vis struct $name <$($($gen: $bound),*)?>
+            }
+            []
+            []
+            $($body)*
+        );
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident $(<$($gen:ident: $bound:path),*>)? {
+            $(
+                $(#[$variant_attr:meta])*
+                $variant:ident($inner:ty),
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name $(<$($gen: $bound),*>)? {
+            $(
+                $(#[$variant_attr])*
+                $variant($inner),
+            )*
+        }
+        impl $(<$($gen: $bound),*>)? core::fmt::Display for $name $(<$($gen),*>)? {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    $(
+                        $(#[$variant_attr])*
+                        $name::$variant(msg) => core::fmt::Display::fmt(msg, f),
+                    )*
+                }
+            }
+        }
+        impl $(<$($gen: $bound),*>)? crate::AnyWireMessage for $name $(<$($gen),*>)? {
+            fn msg_type(&self) -> u16 {
+                match self {
+                    $(
+                        $(#[$variant_attr])*
+                        $name::$variant(_) => <$inner as crate::WireMessage>::MSG_TYPE,
+                    )*
+                }
+            }
+
+            fn encode<W: crate::io::Write>(&self, w: &mut W) -> crate::io::Result<usize> {
+                match self {
+                    $(
+                        $(#[$variant_attr])*
+                        $name::$variant(msg) => crate::WireMessage::encode(msg, w),
+                    )*
+                }
+            }
+
+            fn decode<R: crate::io::Read>(r: &mut R) -> crate::io::Result<Self> {
+                crate::MessageReader::decode(r, crate::DecodeLimits::DEFAULT, |r| {
+                    <Self as crate::AnyWireMessage>::decode_wire(r)
+                })
+            }
+
+            fn decode_wire<R: crate::WireRead>(r: &mut R) -> crate::io::Result<Self> {
+                let mut msg_type = [0_u8; 2];
+                r.read_exact(&mut msg_type)?;
+                let msg_type = u16::from_be_bytes(msg_type);
+                $(
+                    $(#[$variant_attr])*
+                    {
+                        if msg_type == <$inner as crate::WireMessage>::MSG_TYPE {
+                            let msg = <$inner as crate::WireMessage>::decode_wire(r, false)?;
+                            return Ok($name::$variant(msg));
+                        }
+                    }
+                )*
+                Err(crate::io::ErrorKind::InvalidData.into())
+            }
+
+            fn fixed_len(&self) -> usize {
+                match self {
+                    $(
+                        $(#[$variant_attr])*
+                        $name::$variant(msg) => crate::WireMessage::fixed_len(msg),
+                    )*
+                }
+            }
+        }
+    };
+}
+pub(crate) use wire_message;
+
+#[test]
+fn prefix_attributes() {
+    use crate::items::prefixed::BigSize;
+    use crate::WireMessage;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    wire_message! {
+        #[msg_type = 1000]
+        #[derive(Debug, PartialEq)]
+        struct Prefixed {
+            #[len_prefix = BigSize]
+            script: Vec<u8>,
+            #[count_prefix = u16]
+            sigs: Vec<[u8; 2]>,
+            #[tlv_type = 1]
+            extra: Option<u8>,
+        }
+    }
+
+    let msg = Prefixed {
+        script: alloc::vec![7; 3],
+        sigs: alloc::vec![[1, 2], [3, 4]],
+        extra: Some(9),
+    };
+    let mut bytes = Vec::new();
+    msg.encode(&mut bytes).unwrap();
+    assert_eq!(bytes, [0x03, 0xe8, 3, 7, 7, 7, 0, 2, 1, 2, 3, 4, 1, 1, 9]);
+    assert_eq!(msg.encoded_len(), bytes.len());
+    assert_eq!(msg.fixed_len(), bytes.len() - 3);
+    assert_eq!(Prefixed::decode(&mut &bytes[..], true).unwrap(), msg);
+    assert_eq!(
+        msg.to_string(),
+        "Prefixed(script=070707, sigs=[0102, 0304], extra=9)"
+    );
+
+    // A count the items do not fill is cut short.
+    bytes.truncate(10);
+    assert!(Prefixed::decode(&mut &bytes[..], true).is_err());
+}